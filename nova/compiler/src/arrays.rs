@@ -0,0 +1,311 @@
+//! Array functions, most of which call back into scripts: `map`, `filter`,
+//! `reduce`, `find`, `any`, `all`, `flat_map`, `group_by`, `partition`,
+//! `sort_by` and `sort_with`, plus `zip`, `chunk`, `window`, `unique` and
+//! `sort`.
+//!
+//! Callbacks are called like any other function, so script functions, bound
+//! methods and built-ins such as `str` all work, and an error raised by a
+//! callback stops the whole call. Each runs in a single pass over the input.
+//!
+//! All three sorts are stable. `sort` and `sort_by` order values of
+//! different types by `compare_values`, and instances through their `__lt__`
+//! method; `sort_with` uses the order its comparator gives.
+
+use crate::interpreter::{Interpreter, RuntimeError, RuntimeResult};
+use crate::numeric;
+use crate::value::Value;
+use indexmap::IndexMap;
+use num_traits::ToPrimitive;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+/// What `unique` hashes a value by. Values that are equal get the same key,
+/// including numbers of different types: `1`, `1.0`, `1d` and `1n` are all
+/// `Integer(1)`.
+#[derive(PartialEq, Eq, Hash)]
+enum UniqueKey<'a> {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    String(&'a str),
+    Bytes(&'a [u8]),
+}
+
+/// The key of `value`, or `None` for values that can only be compared one
+/// by one: arrays, objects, functions, instances and numbers with no exact
+/// 64-bit integer value. None of those equals a value that has a key.
+fn unique_key(value: &Value) -> Option<UniqueKey<'_>> {
+    match value {
+        Value::Null => Some(UniqueKey::Null),
+        Value::Boolean(b) => Some(UniqueKey::Boolean(*b)),
+        Value::Integer(n) => Some(UniqueKey::Integer(*n)),
+        Value::BigInt(n) => n.to_i64().map(UniqueKey::Integer),
+        Value::Decimal(n) if n.is_integer() => n.trunc().to_i64().map(UniqueKey::Integer),
+        Value::Number(n) if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => {
+            Some(UniqueKey::Integer(*n as i64))
+        }
+        Value::String(s) => Some(UniqueKey::String(s.as_str())),
+        Value::Bytes(bytes) => Some(UniqueKey::Bytes(bytes)),
+        _ => None,
+    }
+}
+
+/// Where values of each type go relative to other types when sorted.
+fn type_rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Boolean(_) => 1,
+        Value::Integer(_) | Value::BigInt(_) | Value::Decimal(_) | Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Bytes(_) => 4,
+        Value::Array(_) => 5,
+        Value::Object(_) => 6,
+        _ => 7,
+    }
+}
+
+/// A total order over all values, used by `sort` and `sort_by`: `null`,
+/// then booleans, numbers, strings, bytes, arrays and objects, with anything
+/// else last. Numbers compare by value whatever their type, with NaN after
+/// every other number; strings by code point; arrays element by element.
+/// Other values of the same type are equal to one another.
+pub(crate) fn compare_values(left: &Value, right: &Value) -> Ordering {
+    let by_type = type_rank(left).cmp(&type_rank(right));
+    if by_type != Ordering::Equal {
+        return by_type;
+    }
+    match (left, right) {
+        (Value::Boolean(l), Value::Boolean(r)) => l.cmp(r),
+        (l, r) if l.is_number() => numeric::compare(l, r).unwrap_or_else(|| is_nan(l).cmp(&is_nan(r))),
+        (Value::String(l), Value::String(r)) => l.cmp(r),
+        (Value::Bytes(l), Value::Bytes(r)) => l.cmp(r),
+        (Value::Array(l), Value::Array(r)) => l
+            .iter()
+            .zip(r)
+            .map(|(l, r)| compare_values(l, r))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or_else(|| l.len().cmp(&r.len())),
+        _ => Ordering::Equal,
+    }
+}
+
+fn is_nan(value: &Value) -> bool {
+    matches!(value, Value::Number(n) if n.is_nan())
+}
+
+/// Sorts `items` stably with a comparison that may fail. Unlike the
+/// standard library's sorts this never panics when `compare` is not a
+/// consistent order, which a script's comparator need not be.
+fn merge_sort<T: Clone>(items: &mut [T], compare: &mut impl FnMut(&T, &T) -> RuntimeResult<Ordering>) -> RuntimeResult<()> {
+    if items.len() < 2 {
+        return Ok(());
+    }
+    let middle = items.len() / 2;
+    merge_sort(&mut items[..middle], compare)?;
+    merge_sort(&mut items[middle..], compare)?;
+
+    let (left, right) = items.split_at(middle);
+    let mut merged = Vec::with_capacity(items.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        // Taking from the left on ties keeps the sort stable
+        if compare(&right[j], &left[i])? == Ordering::Less {
+            merged.push(right[j].clone());
+            j += 1;
+        } else {
+            merged.push(left[i].clone());
+            i += 1;
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    items.clone_from_slice(&merged);
+    Ok(())
+}
+
+/// The size argument of `chunk` and `window`.
+fn size_arg(name: &str, value: &Value) -> RuntimeResult<usize> {
+    match value.as_i64() {
+        Some(size) if size > 0 => Ok(size as usize),
+        _ => Err(RuntimeError::InvalidOperation(format!("{}() requires a positive integer size, got {}", name, value))),
+    }
+}
+
+impl Interpreter {
+    /// Orders two values for `sort` and `sort_by`.
+    fn compare_for_sort(&mut self, left: &Value, right: &Value) -> RuntimeResult<Ordering> {
+        if let Some(less) = self.call_protocol(left, "__lt__", std::slice::from_ref(right))? {
+            if less.is_truthy() {
+                return Ok(Ordering::Less);
+            }
+            let greater = self.call_protocol(right, "__lt__", std::slice::from_ref(left))?;
+            return Ok(if greater.is_some_and(|greater| greater.is_truthy()) { Ordering::Greater } else { Ordering::Equal });
+        }
+        Ok(compare_values(left, right))
+    }
+
+    /// Calls `function` with `item` and reports whether the result is truthy.
+    fn test(&mut self, function: &Value, item: &Value) -> RuntimeResult<bool> {
+        Ok(self.call_function(function, std::slice::from_ref(item))?.is_truthy())
+    }
+
+    /// Runs the array function `name`; `args` have been checked against its
+    /// arity, except for the variadic `reduce`.
+    pub(crate) fn call_array_function(&mut self, name: &str, args: &[Value]) -> RuntimeResult<Value> {
+        if name == "reduce" && !(2..=3).contains(&args.len()) {
+            return Err(RuntimeError::InvalidOperation("reduce() requires an array, a function and an optional initial value".to_string()));
+        }
+        let Value::Array(items) = &args[0] else {
+            return Err(RuntimeError::TypeError(format!("{}() requires an array, got {}", name, args[0].type_name())));
+        };
+        let takes_function = !matches!(name, "zip" | "chunk" | "window" | "unique" | "sort");
+        if takes_function && !args[1].is_callable() {
+            return Err(RuntimeError::TypeError(format!("{}() requires a function, got {}", name, args[1].type_name())));
+        }
+
+        match name {
+            "map" => items
+                .iter()
+                .map(|item| self.call_function(&args[1], std::slice::from_ref(item)))
+                .collect::<RuntimeResult<Vec<_>>>()
+                .map(Value::Array),
+            "filter" => {
+                let mut kept = Vec::new();
+                for item in items {
+                    if self.test(&args[1], item)? {
+                        kept.push(item.clone());
+                    }
+                }
+                Ok(Value::Array(kept))
+            }
+            "reduce" => {
+                let mut rest = items.iter();
+                let mut accumulator = match args.get(2) {
+                    Some(initial) => initial.clone(),
+                    None => rest.next().cloned().ok_or_else(|| {
+                        RuntimeError::InvalidOperation("reduce() of an empty array needs an initial value".to_string())
+                    })?,
+                };
+                for item in rest {
+                    accumulator = self.call_function(&args[1], &[accumulator, item.clone()])?;
+                }
+                Ok(accumulator)
+            }
+            "find" => {
+                for item in items {
+                    if self.test(&args[1], item)? {
+                        return Ok(item.clone());
+                    }
+                }
+                Ok(Value::Null)
+            }
+            "any" | "all" => {
+                // Both stop at the first item that decides the answer
+                let wanted = name == "any";
+                for item in items {
+                    if self.test(&args[1], item)? == wanted {
+                        return Ok(Value::Boolean(wanted));
+                    }
+                }
+                Ok(Value::Boolean(!wanted))
+            }
+            "flat_map" => {
+                let mut results = Vec::with_capacity(items.len());
+                for item in items {
+                    match self.call_function(&args[1], std::slice::from_ref(item))? {
+                        Value::Array(inner) => results.extend(inner),
+                        other => results.push(other),
+                    }
+                }
+                Ok(Value::Array(results))
+            }
+            "partition" => {
+                let (mut matching, mut rest) = (Vec::new(), Vec::new());
+                for item in items {
+                    if self.test(&args[1], item)? {
+                        matching.push(item.clone());
+                    } else {
+                        rest.push(item.clone());
+                    }
+                }
+                Ok(Value::Array(vec![Value::Array(matching), Value::Array(rest)]))
+            }
+            "group_by" => {
+                let mut groups: IndexMap<String, Value> = IndexMap::new();
+                for item in items {
+                    let key = match self.call_function(&args[1], std::slice::from_ref(item))? {
+                        Value::String(key) => key.to_string(),
+                        other => self.stringify(&other)?,
+                    };
+                    match groups.entry(key).or_insert_with(|| Value::Array(Vec::new())) {
+                        Value::Array(group) => group.push(item.clone()),
+                        _ => unreachable!("groups only hold arrays"),
+                    }
+                }
+                Ok(Value::Object(groups))
+            }
+            "zip" => match &args[1] {
+                Value::Array(others) => Ok(Value::Array(
+                    items.iter().zip(others).map(|(a, b)| Value::Array(vec![a.clone(), b.clone()])).collect(),
+                )),
+                other => Err(RuntimeError::TypeError(format!("zip() requires two arrays, got {}", other.type_name()))),
+            },
+            "chunk" => {
+                let size = size_arg(name, &args[1])?;
+                Ok(Value::Array(items.chunks(size).map(|chunk| Value::Array(chunk.to_vec())).collect()))
+            }
+            "window" => {
+                let size = size_arg(name, &args[1])?;
+                Ok(Value::Array(items.windows(size).map(|window| Value::Array(window.to_vec())).collect()))
+            }
+            "unique" => {
+                let mut keys = HashSet::new();
+                let mut scanned: Vec<&Value> = Vec::new();
+                let mut unique = Vec::new();
+                for item in items {
+                    let new = match unique_key(item) {
+                        Some(key) => keys.insert(key),
+                        None if scanned.contains(&item) => false,
+                        None => {
+                            scanned.push(item);
+                            true
+                        }
+                    };
+                    if new {
+                        unique.push(item.clone());
+                    }
+                }
+                Ok(Value::Array(unique))
+            }
+            "sort" => {
+                let mut sorted = items.clone();
+                merge_sort(&mut sorted, &mut |a, b| self.compare_for_sort(a, b))?;
+                Ok(Value::Array(sorted))
+            }
+            "sort_by" => {
+                // Each key is computed once, then the items sort by their keys
+                let mut keyed = items
+                    .iter()
+                    .map(|item| Ok((self.call_function(&args[1], std::slice::from_ref(item))?, item.clone())))
+                    .collect::<RuntimeResult<Vec<_>>>()?;
+                merge_sort(&mut keyed, &mut |(a, _), (b, _)| self.compare_for_sort(a, b))?;
+                Ok(Value::Array(keyed.into_iter().map(|(_, item)| item).collect()))
+            }
+            "sort_with" => {
+                let mut sorted = items.clone();
+                let comparator = &args[1];
+                merge_sort(&mut sorted, &mut |a, b| {
+                    let result = self.call_function(comparator, &[a.clone(), b.clone()])?;
+                    match numeric::compare(&result, &Value::Integer(0)) {
+                        Some(ordering) if result.is_number() => Ok(ordering),
+                        _ => Err(RuntimeError::TypeError(format!(
+                            "sort_with() comparator must return a number, got {}", result.type_name()
+                        ))),
+                    }
+                })?;
+                Ok(Value::Array(sorted))
+            }
+            _ => Err(RuntimeError::InvalidOperation(format!("Unknown built-in function: {}", name))),
+        }
+    }
+}
@@ -14,10 +14,12 @@ pub enum Expr {
     Call {
         callee: Box<Expr>,
         args: Vec<Expr>,
+        location: SourceLocation,
     },
     New {
         class: Box<Expr>,
         args: Vec<Expr>,
+        location: SourceLocation,
     },
     Block(Vec<Stmt>),
     If {
@@ -47,11 +49,17 @@ pub enum Expr {
     Assignment {
         target: Box<Expr>,
         value: Box<Expr>,
+        location: SourceLocation,
     },
     StringInterpolation(Vec<Expr>),
+    /// `${value:spec}` inside an interpolated string.
+    Format {
+        value: Box<Expr>,
+        spec: String,
+    },
     Try {
         body: Box<Expr>,
-        catch: Option<(String, Box<Expr>)>,
+        catches: Vec<CatchClause>,
         finally: Option<Box<Expr>>,
     },
     Throw(Box<Expr>),
@@ -63,17 +71,71 @@ pub enum Expr {
     Super,
 }
 
+/// Name under which the getter of `property` is stored among the methods of
+/// its class. The space keeps it apart from regular method names.
+pub fn getter_key(property: &str) -> String {
+    format!("get {}", property)
+}
+
+/// Like `getter_key`, for setters.
+pub fn setter_key(property: &str) -> String {
+    format!("set {}", property)
+}
+
+/// A field declared in a class body, such as `private static count = 0`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldDecl {
+    pub name: String,
+    pub initializer: Option<Expr>,
+    pub is_static: bool,
+    pub is_private: bool,
+    pub annotation: Option<TypeAnnotation>,
+}
+
+/// A type written in the source, such as `number`, `[string]`, `Point?`
+/// or `number | string`. Types are only read by the checker; they never
+/// affect how a program runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeExpr {
+    /// A built-in type such as `number`, or a class or trait name.
+    Named(String),
+    Array(Box<TypeExpr>),
+    /// `T?`: `T` or `null`.
+    Nullable(Box<TypeExpr>),
+    Union(Vec<TypeExpr>),
+}
+
+/// A type annotation and where it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAnnotation {
+    pub ty: TypeExpr,
+    pub location: SourceLocation,
+}
+
+/// `catch (variable)` or `catch (variable: Class)`, which only handles
+/// instances of `Class` and its subclasses.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CatchClause {
+    pub variable: String,
+    pub class: Option<String>,
+    pub body: Box<Expr>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Stmt {
     Expression(Expr),
     Let {
         name: String,
         value: Expr,
+        annotation: Option<TypeAnnotation>,
     },
     Function {
         name: String,
         params: Vec<String>,
         body: Expr,
+        /// The annotation of each parameter, if any.
+        param_types: Vec<Option<TypeAnnotation>>,
+        return_type: Option<TypeAnnotation>,
     },
     Return(Option<Expr>),
     Import {
@@ -84,6 +146,18 @@ pub enum Stmt {
         name: String,
         superclass: Option<String>,
         methods: Vec<Stmt>,
+        fields: Vec<FieldDecl>,
+        /// Names of the methods declared `private`.
+        private_methods: Vec<String>,
+        /// Traits listed after `implements`.
+        traits: Vec<String>,
+    },
+    Trait {
+        name: String,
+        /// Methods declared without a body, which implementing classes must define.
+        required: Vec<String>,
+        /// Default methods, copied into classes that don't define their own.
+        methods: Vec<Stmt>,
     },
     Break,
     Continue,
@@ -91,8 +165,12 @@ pub enum Stmt {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
+    Integer(i64),
+    BigInt(num_bigint::BigInt),
+    Decimal(crate::decimal::Decimal),
     Number(f64),
     String(String),
+    Bytes(Vec<u8>),
     Boolean(bool),
     Array(Vec<Literal>),
     Object(Vec<(String, Literal)>),
@@ -105,6 +183,7 @@ pub enum BinaryOp {
     Subtract,
     Multiply,
     Divide,
+    FloorDivide,
     Modulo,
     Power,
     Equal,
@@ -113,6 +192,7 @@ pub enum BinaryOp {
     Greater,
     LessEqual,
     GreaterEqual,
+    InstanceOf,
     And,
     Or,
     BitwiseAnd,
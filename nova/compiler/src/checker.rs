@@ -0,0 +1,1043 @@
+//! Static checking of optional type annotations.
+//!
+//! The checker reads the annotations on `let` bindings, function parameters
+//! and return types, and class fields. It infers the types of expressions
+//! from literals, built-in functions, annotated names and the classes
+//! declared in the program, and reports values that cannot fit where they
+//! are used. Anything it cannot infer is `any` and accepted, so unannotated
+//! code passes unchanged. Checking never affects how a program runs.
+
+use crate::ast::*;
+use crate::methods;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// The type of a value as far as the checker can tell.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Any,
+    Number,
+    String,
+    Bytes,
+    Bool,
+    Null,
+    Function,
+    Object,
+    Array(Box<Type>),
+    /// An instance of the named class, or of a class implementing the named trait.
+    Instance(String),
+    Class(String),
+    Union(Vec<Type>),
+}
+
+impl Type {
+    /// The union of `types`, flattened and without duplicates.
+    pub fn union(types: impl IntoIterator<Item = Type>) -> Type {
+        let mut members: Vec<Type> = Vec::new();
+        for ty in types {
+            let flattened = match ty {
+                Type::Union(inner) => inner,
+                other => vec![other],
+            };
+            for member in flattened {
+                if member == Type::Any {
+                    return Type::Any;
+                }
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+        }
+        match members.len() {
+            0 => Type::Any,
+            1 => members.remove(0),
+            _ => Type::Union(members),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Any => write!(f, "any"),
+            Type::Number => write!(f, "number"),
+            Type::String => write!(f, "string"),
+            Type::Bytes => write!(f, "bytes"),
+            Type::Bool => write!(f, "bool"),
+            Type::Null => write!(f, "null"),
+            Type::Function => write!(f, "function"),
+            Type::Object => write!(f, "object"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Instance(name) => write!(f, "{}", name),
+            Type::Class(name) => write!(f, "class {}", name),
+            Type::Union(members) => {
+                let members: Vec<String> = members.iter().map(|member| member.to_string()).collect();
+                write!(f, "{}", members.join(" | "))
+            }
+        }
+    }
+}
+
+/// A problem found by the checker.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub location: SourceLocation,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Signature {
+    params: Vec<Type>,
+    /// How many of the leading parameters must be passed.
+    required: usize,
+    returns: Type,
+}
+
+impl Signature {
+    fn new(params: Vec<Type>, returns: Type) -> Self {
+        Signature { required: params.len(), params, returns }
+    }
+
+    /// Makes all parameters after the first `required` optional.
+    fn optional_after(mut self, required: usize) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// The signature of the built-in function as a method: without the
+    /// receiver it takes first.
+    fn without_receiver(mut self) -> Self {
+        if !self.params.is_empty() {
+            self.params.remove(0);
+            self.required = self.required.saturating_sub(1);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+struct ClassInfo {
+    superclass: Option<String>,
+    traits: Vec<String>,
+    fields: HashMap<String, Type>,
+    static_fields: HashMap<String, Type>,
+    methods: HashMap<String, Signature>,
+    static_methods: HashMap<String, Signature>,
+    constructor: Option<Signature>,
+}
+
+#[derive(Debug, Clone)]
+struct Binding {
+    ty: Type,
+    /// Whether the type was written in an annotation, so assignments must fit it.
+    declared: bool,
+    signature: Option<Signature>,
+}
+
+/// A variable known not to be null in part of a block, after a guard such
+/// as `if (x == null) return`.
+struct Narrowing {
+    name: String,
+    ty: Type,
+    /// The number of scopes when the guard was checked; a variable declared
+    /// in a deeper scope is a different one.
+    depth: usize,
+}
+
+/// The function whose body is being checked.
+struct FunctionContext {
+    returns: Type,
+    location: SourceLocation,
+}
+
+/// Checks a program against its type annotations.
+///
+/// ```
+/// use nova_compiler::{Lexer, Parser, TypeChecker};
+///
+/// let source = "let count: number = \"three\"";
+/// let (tokens, locations) = Lexer::new(source).tokenize_with_locations().unwrap();
+/// let program = Parser::with_locations(tokens, locations).parse().unwrap();
+/// let diagnostics = TypeChecker::new().check(&program);
+/// assert_eq!(diagnostics[0].to_string(), "1:12: 'count' is declared as number but initialized with string");
+/// ```
+#[derive(Default)]
+pub struct TypeChecker {
+    scopes: Vec<HashMap<String, Binding>>,
+    classes: HashMap<String, ClassInfo>,
+    traits: HashSet<String>,
+    /// Variables assigned somewhere after their declaration; their
+    /// unannotated declarations can't be trusted to give their type.
+    reassigned: HashSet<String>,
+    functions: Vec<FunctionContext>,
+    narrowings: Vec<Narrowing>,
+    this_type: Vec<Type>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl TypeChecker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Checks `program` and returns the problems found, in source order.
+    pub fn check(&mut self, program: &Program) -> Vec<Diagnostic> {
+        self.scopes = vec![HashMap::new()];
+        self.diagnostics.clear();
+        for statement in &program.statements {
+            collect_assigned(statement, &mut self.reassigned);
+        }
+
+        self.check_statements(&program.statements);
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.location.line, diagnostic.location.column));
+        diagnostics
+    }
+
+    fn report(&mut self, location: &SourceLocation, message: String) {
+        // Annotations are resolved more than once; report each problem once
+        let diagnostic = Diagnostic { message, location: location.clone() };
+        if !self.diagnostics.contains(&diagnostic) {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn define(&mut self, name: &str, binding: Binding) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(name.to_string(), binding);
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Binding> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    /// The type of variable `name` at this point, taking null checks into account.
+    fn variable_type(&self, name: &str) -> Type {
+        let Some((depth, binding)) = self.scopes.iter().enumerate().rev().find_map(|(depth, scope)| {
+            scope.get(name).map(|binding| (depth, binding))
+        }) else {
+            return Type::Any;
+        };
+        self.narrowings
+            .iter()
+            .rev()
+            .find(|narrowing| narrowing.name == name && narrowing.depth > depth)
+            .map_or_else(|| binding.ty.clone(), |narrowing| narrowing.ty.clone())
+    }
+
+    /// Treats `name` as not null until the current block ends or it is assigned.
+    fn narrow(&mut self, name: &str) {
+        let ty = match self.variable_type(name) {
+            Type::Union(members) => Type::union(members.into_iter().filter(|member| *member != Type::Null)),
+            _ => return,
+        };
+        self.narrowings.push(Narrowing { name: name.to_string(), ty, depth: self.scopes.len() });
+    }
+
+    /// Forgets what null checks established about `names`, which may be
+    /// assigned from here on.
+    fn widen(&mut self, names: &HashSet<String>) {
+        self.narrowings.retain(|narrowing| !names.contains(&narrowing.name));
+    }
+
+    fn with_scope<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> T {
+        self.scopes.push(HashMap::new());
+        let result = f(self);
+        self.scopes.pop();
+        result
+    }
+
+    fn resolve(&mut self, annotation: &TypeAnnotation) -> Type {
+        self.resolve_expr(&annotation.ty, &annotation.location)
+    }
+
+    fn resolve_optional(&mut self, annotation: Option<&TypeAnnotation>) -> Type {
+        annotation.map_or(Type::Any, |annotation| self.resolve(annotation))
+    }
+
+    fn resolve_expr(&mut self, ty: &TypeExpr, location: &SourceLocation) -> Type {
+        match ty {
+            TypeExpr::Named(name) => match name.as_str() {
+                "any" => Type::Any,
+                "number" => Type::Number,
+                "string" => Type::String,
+                "bytes" => Type::Bytes,
+                "bool" | "boolean" => Type::Bool,
+                "null" => Type::Null,
+                "function" => Type::Function,
+                "object" => Type::Object,
+                "array" => Type::Array(Box::new(Type::Any)),
+                // Capitalized names are classes and traits, possibly imported ones
+                _ if name.starts_with(|c: char| c.is_uppercase()) => Type::Instance(name.clone()),
+                _ => {
+                    self.report(location, format!("Unknown type '{}'", name));
+                    Type::Any
+                }
+            },
+            TypeExpr::Array(element) => Type::Array(Box::new(self.resolve_expr(element, location))),
+            TypeExpr::Nullable(inner) => Type::union([self.resolve_expr(inner, location), Type::Null]),
+            TypeExpr::Union(members) => {
+                let members: Vec<Type> = members.iter().map(|member| self.resolve_expr(member, location)).collect();
+                Type::union(members)
+            }
+        }
+    }
+
+    /// Whether a value of type `value` can be used where `target` is expected.
+    fn accepts(&self, target: &Type, value: &Type) -> bool {
+        match (target, value) {
+            (Type::Any, _) | (_, Type::Any) => true,
+            (_, Type::Union(members)) => members.iter().all(|member| self.accepts(target, member)),
+            (Type::Union(members), _) => members.iter().any(|member| self.accepts(member, value)),
+            (Type::Array(target), Type::Array(value)) => self.accepts(target, value),
+            (Type::Instance(target), Type::Instance(class)) => self.is_subtype(class, target),
+            (target, value) => target == value,
+        }
+    }
+
+    /// Whether instances of `class` are instances of `target`, a class or
+    /// trait. Classes the checker doesn't know, such as imported ones, are
+    /// given the benefit of the doubt.
+    fn is_subtype(&self, class: &str, target: &str) -> bool {
+        if !self.traits.contains(target) && !self.classes.contains_key(target) {
+            return true;
+        }
+        let mut current = Some(class.to_string());
+        while let Some(name) = current {
+            if name == target {
+                return true;
+            }
+            match self.classes.get(&name) {
+                Some(info) if info.traits.iter().any(|implemented| implemented == target) => return true,
+                Some(info) => current = info.superclass.clone(),
+                None => return true,
+            }
+        }
+        false
+    }
+
+    fn expect(&mut self, target: &Type, value: &Type, location: &SourceLocation, describe: impl FnOnce() -> String) {
+        if !self.accepts(target, value) {
+            let message = format!("{} {} but got {}", describe(), target, value);
+            self.report(location, message);
+        }
+    }
+
+    fn check_statements(&mut self, statements: &[Stmt]) -> Type {
+        // Functions and classes can be used before their declaration
+        for statement in statements {
+            match statement {
+                Stmt::Function { name, param_types, return_type, .. } => {
+                    let signature = self.signature(param_types, return_type.as_ref());
+                    self.define(name, Binding { ty: Type::Function, declared: true, signature: Some(signature) });
+                }
+                Stmt::Class { .. } => self.declare_class(statement),
+                Stmt::Trait { name, .. } => {
+                    self.traits.insert(name.clone());
+                }
+                _ => {}
+            }
+        }
+
+        let narrowings = self.narrowings.len();
+        let mut result = Type::Null;
+        for statement in statements {
+            result = self.check_statement(statement);
+            // After `if (x == null) return`, x isn't null in the rest of the block
+            if let Stmt::Expression(Expr::If { condition, then_branch, else_branch }) = statement {
+                if let Some((name, null_when_true)) = null_check(condition) {
+                    let null_branch = if null_when_true { Some(then_branch.as_ref()) } else { else_branch.as_deref() };
+                    if null_branch.is_some_and(always_exits) {
+                        self.narrow(name);
+                    }
+                }
+            }
+        }
+        self.narrowings.truncate(narrowings);
+        result
+    }
+
+    fn signature(&mut self, param_types: &[Option<TypeAnnotation>], return_type: Option<&TypeAnnotation>) -> Signature {
+        let params = param_types.iter().map(|annotation| self.resolve_optional(annotation.as_ref())).collect();
+        let returns = self.resolve_optional(return_type);
+        Signature::new(params, returns)
+    }
+
+    fn declare_class(&mut self, statement: &Stmt) {
+        let Stmt::Class { name, superclass, methods, fields, traits, .. } = statement else { return };
+        let mut info = ClassInfo {
+            superclass: superclass.clone(),
+            traits: traits.clone(),
+            ..ClassInfo::default()
+        };
+        for field in fields {
+            let ty = self.resolve_optional(field.annotation.as_ref());
+            if field.is_static {
+                info.static_fields.insert(field.name.clone(), ty);
+            } else {
+                info.fields.insert(field.name.clone(), ty);
+            }
+        }
+        for method in methods {
+            if let Stmt::Function { name: method_name, param_types, return_type, .. } = method {
+                let signature = self.signature(param_types, return_type.as_ref());
+                if let Some(static_name) = method_name.strip_prefix("static_") {
+                    info.static_methods.insert(static_name.to_string(), signature);
+                } else if method_name == "constructor" {
+                    info.constructor = Some(signature);
+                } else {
+                    info.methods.insert(method_name.clone(), signature);
+                }
+            }
+        }
+        self.classes.insert(name.clone(), info);
+        self.define(name, Binding { ty: Type::Class(name.clone()), declared: true, signature: None });
+    }
+
+    /// Walks `class` and its superclasses until `f` finds something.
+    fn find_in_class<T>(&self, class: &str, f: impl Fn(&ClassInfo) -> Option<T>) -> Option<T> {
+        let mut current = Some(class);
+        while let Some(name) = current {
+            let info = self.classes.get(name)?;
+            if let Some(found) = f(info) {
+                return Some(found);
+            }
+            current = info.superclass.as_deref();
+        }
+        None
+    }
+
+    fn check_statement(&mut self, statement: &Stmt) -> Type {
+        match statement {
+            Stmt::Expression(expr) => self.infer(expr),
+            Stmt::Let { name, value, annotation } => {
+                let value_type = self.infer(value);
+                let binding = match annotation {
+                    Some(annotation) => {
+                        let declared = self.resolve(annotation);
+                        if !self.accepts(&declared, &value_type) {
+                            self.report(&annotation.location, format!(
+                                "'{}' is declared as {} but initialized with {}", name, declared, value_type
+                            ));
+                        }
+                        Binding { ty: declared, declared: true, signature: None }
+                    }
+                    None if self.reassigned.contains(name) => Binding { ty: Type::Any, declared: false, signature: None },
+                    None => Binding { ty: value_type, declared: false, signature: self.signature_of(value) },
+                };
+                self.widen(&HashSet::from([name.clone()]));
+                self.define(name, binding);
+                Type::Null
+            }
+            Stmt::Function { params, body, param_types, return_type, .. } => {
+                self.check_function(params, param_types, return_type.as_ref(), body);
+                Type::Null
+            }
+            Stmt::Return(value) => {
+                let value_type = match value {
+                    Some(value) => self.infer(value),
+                    None => Type::Null,
+                };
+                self.check_return(&value_type);
+                Type::Null
+            }
+            Stmt::Import { alias, module } => {
+                let name = alias.clone().unwrap_or_else(|| module.trim_end_matches(".nova").to_string());
+                self.define(&name, Binding { ty: Type::Any, declared: false, signature: None });
+                Type::Null
+            }
+            Stmt::Class { name, methods, fields, .. } => {
+                self.declare_class(statement);
+                self.this_type.push(Type::Instance(name.clone()));
+                for field in fields {
+                    if let (Some(annotation), Some(initializer)) = (&field.annotation, &field.initializer) {
+                        let declared = self.resolve(annotation);
+                        let value_type = self.infer(initializer);
+                        if !self.accepts(&declared, &value_type) {
+                            self.report(&annotation.location, format!(
+                                "Field '{}' is declared as {} but initialized with {}", field.name, declared, value_type
+                            ));
+                        }
+                    }
+                }
+                for method in methods {
+                    if let Stmt::Function { name: method_name, params, body, param_types, return_type } = method {
+                        let is_static = method_name.starts_with("static_");
+                        self.this_type.push(if is_static { Type::Class(name.clone()) } else { Type::Instance(name.clone()) });
+                        self.check_function(params, param_types, return_type.as_ref(), body);
+                        self.this_type.pop();
+                    }
+                }
+                self.this_type.pop();
+                Type::Null
+            }
+            Stmt::Trait { name, methods, .. } => {
+                self.this_type.push(Type::Instance(name.clone()));
+                for method in methods {
+                    if let Stmt::Function { params, body, param_types, return_type, .. } = method {
+                        self.check_function(params, param_types, return_type.as_ref(), body);
+                    }
+                }
+                self.this_type.pop();
+                Type::Null
+            }
+            Stmt::Break | Stmt::Continue => Type::Null,
+        }
+    }
+
+    fn check_function(
+        &mut self,
+        params: &[String],
+        param_types: &[Option<TypeAnnotation>],
+        return_type: Option<&TypeAnnotation>,
+        body: &Expr,
+    ) {
+        let signature = self.signature(param_types, return_type);
+        let location = return_type.map_or_else(SourceLocation::unknown, |annotation| annotation.location.clone());
+        self.functions.push(FunctionContext { returns: signature.returns.clone(), location });
+        self.with_scope(|checker| {
+            for (param, ty) in params.iter().zip(&signature.params) {
+                let declared = *ty != Type::Any;
+                checker.define(param, Binding { ty: ty.clone(), declared, signature: None });
+            }
+            let result = match body {
+                Expr::Block(statements) => match statements.last() {
+                    // The value of a trailing expression is returned
+                    Some(Stmt::Expression(last)) if !is_control_flow(last) => {
+                        checker.check_statements(statements)
+                    }
+                    _ => {
+                        checker.check_statements(statements);
+                        Type::Any
+                    }
+                },
+                other => checker.infer(other),
+            };
+            checker.check_return(&result);
+        });
+        self.functions.pop();
+    }
+
+    fn check_return(&mut self, value: &Type) {
+        let Some(function) = self.functions.last() else { return };
+        if !self.accepts(&function.returns, value) {
+            let message = format!("Function is declared to return {} but returns {}", function.returns, value);
+            let location = function.location.clone();
+            self.report(&location, message);
+        }
+    }
+
+    /// The signature of a function a `let` binds, for checking later calls.
+    fn signature_of(&mut self, value: &Expr) -> Option<Signature> {
+        match value {
+            Expr::Identifier(name) => self.lookup(name).and_then(|binding| binding.signature.clone()),
+            _ => None,
+        }
+    }
+
+    fn infer(&mut self, expr: &Expr) -> Type {
+        match expr {
+            Expr::Literal(literal) => literal_type(literal),
+            Expr::Identifier(name) => self.variable_type(name),
+            Expr::Binary { left, operator, right } => {
+                let left = self.infer(left);
+                let right = self.infer(right);
+                binary_type(operator, &left, &right)
+            }
+            Expr::Unary { operator, operand } => {
+                let operand = self.infer(operand);
+                match operator {
+                    UnaryOp::Not => Type::Bool,
+                    UnaryOp::BitwiseNot => Type::Number,
+                    UnaryOp::Minus | UnaryOp::Plus if operand == Type::Number => Type::Number,
+                    UnaryOp::Minus | UnaryOp::Plus => Type::Any,
+                }
+            }
+            Expr::Call { callee, args, location } => self.infer_call(callee, args, location),
+            Expr::New { class, args, location } => {
+                let arg_types = self.infer_all(args);
+                match class.as_ref() {
+                    Expr::Identifier(name) if self.classes.contains_key(name) => {
+                        let constructor = self.find_in_class(name, |info| info.constructor.clone());
+                        if let Some(constructor) = constructor {
+                            self.check_args(&format!("new {}", name), &constructor, &arg_types, location);
+                        }
+                        Type::Instance(name.clone())
+                    }
+                    Expr::Identifier(name) if name.starts_with(|c: char| c.is_uppercase()) => Type::Instance(name.clone()),
+                    _ => Type::Any,
+                }
+            }
+            Expr::Block(statements) => {
+                self.with_scope(|checker| checker.check_statements(statements));
+                Type::Any
+            }
+            Expr::If { condition, then_branch, else_branch } => {
+                self.infer(condition);
+                let null_check = null_check(condition);
+                let then_type = self.infer_narrowed(then_branch, null_check.filter(|(_, null_when_true)| !null_when_true));
+                let else_type = match else_branch {
+                    Some(branch) => self.infer_narrowed(branch, null_check.filter(|(_, null_when_true)| *null_when_true)),
+                    None => Type::Null,
+                };
+                Type::union([then_type, else_type])
+            }
+            Expr::Array(elements) => {
+                let elements = self.infer_all(elements);
+                Type::Array(Box::new(Type::union(elements)))
+            }
+            Expr::Object(fields) => {
+                for (_, value) in fields {
+                    self.infer(value);
+                }
+                Type::Object
+            }
+            Expr::Index { object, index } => {
+                let object = self.infer(object);
+                self.infer(index);
+                match object {
+                    Type::Array(element) => *element,
+                    Type::String => Type::String,
+                    Type::Bytes => Type::Number,
+                    _ => Type::Any,
+                }
+            }
+            Expr::Property { object, property } => {
+                let object = self.infer(object);
+                self.property_type(&object, property)
+            }
+            Expr::While { condition, body } => {
+                // A later iteration may see what an earlier one assigned
+                let mut assigned = HashSet::new();
+                collect_assigned_expr(body, &mut assigned);
+                self.widen(&assigned);
+                self.infer(condition);
+                self.infer(body);
+                Type::Any
+            }
+            Expr::For { variable, iterable, body } => {
+                let mut assigned = HashSet::new();
+                collect_assigned_expr(body, &mut assigned);
+                self.widen(&assigned);
+                let element = match self.infer(iterable) {
+                    Type::Array(element) => *element,
+                    Type::String => Type::String,
+                    Type::Bytes => Type::Number,
+                    Type::Object => Type::String,
+                    _ => Type::Any,
+                };
+                self.with_scope(|checker| {
+                    checker.define(variable, Binding { ty: element, declared: false, signature: None });
+                    checker.infer(body);
+                });
+                Type::Any
+            }
+            Expr::Assignment { target, value, location } => {
+                let value_type = self.infer(value);
+                self.check_assignment(target, &value_type, location);
+                if let Expr::Identifier(name) = target.as_ref() {
+                    self.widen(&HashSet::from([name.clone()]));
+                }
+                value_type
+            }
+            Expr::StringInterpolation(parts) => {
+                self.infer_all(parts);
+                Type::String
+            }
+            Expr::Format { value, .. } => {
+                self.infer(value);
+                Type::String
+            }
+            Expr::Try { body, catches, finally } => {
+                self.infer(body);
+                for catch in catches {
+                    let ty = catch.class.clone().map_or(Type::Any, Type::Instance);
+                    self.with_scope(|checker| {
+                        checker.define(&catch.variable, Binding { ty, declared: false, signature: None });
+                        checker.infer(&catch.body);
+                    });
+                }
+                if let Some(finally) = finally {
+                    self.infer(finally);
+                }
+                Type::Any
+            }
+            Expr::Throw(value) => {
+                self.infer(value);
+                Type::Any
+            }
+            Expr::Lambda { params, body } => {
+                self.functions.push(FunctionContext { returns: Type::Any, location: SourceLocation::unknown() });
+                self.with_scope(|checker| {
+                    for param in params {
+                        checker.define(param, Binding { ty: Type::Any, declared: false, signature: None });
+                    }
+                    checker.infer(body);
+                });
+                self.functions.pop();
+                Type::Function
+            }
+            Expr::This => self.this_type.last().cloned().unwrap_or(Type::Any),
+            Expr::Super => Type::Any,
+        }
+    }
+
+    /// Infers `branch` of an `if`, in which the variable of `null_check`, if
+    /// any, is known not to be null.
+    fn infer_narrowed(&mut self, branch: &Expr, null_check: Option<(&str, bool)>) -> Type {
+        let narrowings = self.narrowings.len();
+        if let Some((name, _)) = null_check {
+            self.narrow(name);
+        }
+        let ty = self.infer(branch);
+        self.narrowings.truncate(narrowings);
+        ty
+    }
+
+    fn infer_all(&mut self, exprs: &[Expr]) -> Vec<Type> {
+        exprs.iter().map(|expr| self.infer(expr)).collect()
+    }
+
+    fn infer_call(&mut self, callee: &Expr, args: &[Expr], location: &SourceLocation) -> Type {
+        let arg_types = self.infer_all(args);
+        match callee {
+            Expr::Identifier(name) => {
+                let binding = self.lookup(name).cloned();
+                match binding {
+                    Some(Binding { signature: Some(signature), .. }) => {
+                        self.check_args(name, &signature, &arg_types, location);
+                        signature.returns
+                    }
+                    Some(Binding { ty: Type::Class(class), .. }) => Type::Instance(class),
+                    Some(_) => Type::Any,
+                    None => match builtin_signature(name) {
+                        Some(signature) => {
+                            self.check_args(name, &signature, &arg_types, location);
+                            signature.returns
+                        }
+                        None => Type::Any,
+                    },
+                }
+            }
+            Expr::Property { object, property } => {
+                let object = self.infer(object);
+                let signature = match &object {
+                    Type::Instance(class) => self.find_in_class(class, |info| info.methods.get(property).cloned()),
+                    Type::Class(class) => self.find_in_class(class, |info| info.static_methods.get(property).cloned()),
+                    _ => None,
+                };
+                let signature = signature.or_else(|| {
+                    let type_name = value_type_name(&object)?;
+                    methods::has_method(type_name, property)
+                        .then(|| builtin_signature(property))
+                        .flatten()
+                        .map(Signature::without_receiver)
+                });
+                match signature {
+                    Some(signature) => {
+                        self.check_args(&format!("{}.{}", object, property), &signature, &arg_types, location);
+                        signature.returns
+                    }
+                    None => Type::Any,
+                }
+            }
+            other => {
+                self.infer(other);
+                Type::Any
+            }
+        }
+    }
+
+    fn check_args(&mut self, name: &str, signature: &Signature, args: &[Type], location: &SourceLocation) {
+        if args.len() < signature.required || args.len() > signature.params.len() {
+            let expected = if signature.required == signature.params.len() {
+                signature.params.len().to_string()
+            } else {
+                format!("{} to {}", signature.required, signature.params.len())
+            };
+            self.report(location, format!("'{}' expects {} arguments but got {}", name, expected, args.len()));
+            return;
+        }
+        for (position, (param, arg)) in signature.params.iter().zip(args).enumerate() {
+            self.expect(param, arg, location, || format!("Argument {} of '{}' should be", position + 1, name));
+        }
+    }
+
+    fn property_type(&self, object: &Type, property: &str) -> Type {
+        let found = match object {
+            Type::Instance(class) => self.find_in_class(class, |info| {
+                info.fields.get(property).cloned()
+                    .or_else(|| info.methods.get(property).map(|_| Type::Function))
+                    .or_else(|| info.methods.get(&getter_key(property)).map(|getter| getter.returns.clone()))
+            }),
+            Type::Class(class) => self.find_in_class(class, |info| {
+                info.static_fields.get(property).cloned()
+                    .or_else(|| info.static_methods.get(property).map(|_| Type::Function))
+            }),
+            _ => None,
+        };
+        found.unwrap_or(Type::Any)
+    }
+
+    fn check_assignment(&mut self, target: &Expr, value: &Type, location: &SourceLocation) {
+        match target {
+            Expr::Identifier(name) => {
+                if let Some(Binding { ty, declared: true, .. }) = self.lookup(name).cloned() {
+                    self.expect(&ty, value, location, || format!("'{}' is declared as", name));
+                }
+            }
+            Expr::Property { object, property } => {
+                let object = self.infer(object);
+                let declared = match &object {
+                    Type::Instance(class) => self.find_in_class(class, |info| info.fields.get(property).cloned()),
+                    Type::Class(class) => self.find_in_class(class, |info| info.static_fields.get(property).cloned()),
+                    _ => None,
+                };
+                if let Some(declared) = declared {
+                    self.expect(&declared, value, location, || format!("Field '{}' is declared as", property));
+                }
+            }
+            other => {
+                self.infer(other);
+            }
+        }
+    }
+}
+
+fn literal_type(literal: &Literal) -> Type {
+    match literal {
+        Literal::Integer(_) | Literal::BigInt(_) | Literal::Decimal(_) | Literal::Number(_) => Type::Number,
+        Literal::String(_) => Type::String,
+        Literal::Bytes(_) => Type::Bytes,
+        Literal::Boolean(_) => Type::Bool,
+        Literal::Null => Type::Null,
+        Literal::Array(elements) => Type::Array(Box::new(Type::union(elements.iter().map(literal_type)))),
+        Literal::Object(_) => Type::Object,
+    }
+}
+
+fn binary_type(op: &BinaryOp, left: &Type, right: &Type) -> Type {
+    // Instances may overload operators
+    if matches!(left, Type::Instance(_)) || matches!(right, Type::Instance(_)) {
+        return match op {
+            BinaryOp::InstanceOf | BinaryOp::And | BinaryOp::Or => Type::Bool,
+            _ => Type::Any,
+        };
+    }
+    match op {
+        BinaryOp::Add => match (left, right) {
+            (Type::String, _) | (_, Type::String) => Type::String,
+            (Type::Bytes, Type::Bytes) => Type::Bytes,
+            (Type::Number, Type::Number) => Type::Number,
+            _ => Type::Any,
+        },
+        BinaryOp::Subtract | BinaryOp::Multiply | BinaryOp::Divide | BinaryOp::FloorDivide | BinaryOp::Modulo | BinaryOp::Power
+        | BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr | BinaryOp::BitwiseXor | BinaryOp::LeftShift
+        | BinaryOp::RightShift => Type::Number,
+        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::Greater | BinaryOp::LessEqual
+        | BinaryOp::GreaterEqual | BinaryOp::InstanceOf | BinaryOp::And | BinaryOp::Or => Type::Bool,
+    }
+}
+
+/// Whether `expr` is a statement-like expression whose value isn't
+/// meant to be returned.
+fn is_control_flow(expr: &Expr) -> bool {
+    matches!(expr, Expr::If { .. } | Expr::While { .. } | Expr::For { .. } | Expr::Try { .. } | Expr::Block(_))
+}
+
+/// The variable a condition such as `x == null` or `null != x` compares with
+/// null, and whether the condition holds when it is null.
+fn null_check(condition: &Expr) -> Option<(&str, bool)> {
+    let Expr::Binary { left, operator, right } = condition else { return None };
+    let null_when_true = match operator {
+        BinaryOp::Equal => true,
+        BinaryOp::NotEqual => false,
+        _ => return None,
+    };
+    match (left.as_ref(), right.as_ref()) {
+        (Expr::Identifier(name), Expr::Literal(Literal::Null)) | (Expr::Literal(Literal::Null), Expr::Identifier(name)) => {
+            Some((name, null_when_true))
+        }
+        _ => None,
+    }
+}
+
+/// Whether running `expr` always leaves the enclosing block, by returning,
+/// throwing, breaking or continuing.
+fn always_exits(expr: &Expr) -> bool {
+    match expr {
+        Expr::Block(statements) => statements.iter().any(|statement| match statement {
+            Stmt::Return(_) | Stmt::Break | Stmt::Continue => true,
+            Stmt::Expression(expr) => always_exits(expr),
+            _ => false,
+        }),
+        Expr::Throw(_) => true,
+        Expr::If { then_branch, else_branch: Some(else_branch), .. } => {
+            always_exits(then_branch) && always_exits(else_branch)
+        }
+        _ => false,
+    }
+}
+
+/// Collects the names of variables that are assigned to within `statement`.
+fn collect_assigned(statement: &Stmt, names: &mut HashSet<String>) {
+    match statement {
+        Stmt::Expression(expr) | Stmt::Let { value: expr, .. } | Stmt::Function { body: expr, .. } => {
+            collect_assigned_expr(expr, names)
+        }
+        Stmt::Return(Some(expr)) => collect_assigned_expr(expr, names),
+        Stmt::Class { methods, .. } | Stmt::Trait { methods, .. } => {
+            methods.iter().for_each(|method| collect_assigned(method, names))
+        }
+        _ => {}
+    }
+}
+
+fn collect_assigned_expr(expr: &Expr, names: &mut HashSet<String>) {
+    match expr {
+        Expr::Assignment { target, value, .. } => {
+            if let Expr::Identifier(name) = target.as_ref() {
+                names.insert(name.clone());
+            }
+            collect_assigned_expr(target, names);
+            collect_assigned_expr(value, names);
+        }
+        Expr::Binary { left, right, .. } => {
+            collect_assigned_expr(left, names);
+            collect_assigned_expr(right, names);
+        }
+        Expr::Unary { operand, .. } => collect_assigned_expr(operand, names),
+        Expr::Call { callee, args, .. } => {
+            collect_assigned_expr(callee, names);
+            args.iter().for_each(|expr| collect_assigned_expr(expr, names));
+        }
+        Expr::New { class, args, .. } => {
+            collect_assigned_expr(class, names);
+            args.iter().for_each(|expr| collect_assigned_expr(expr, names));
+        }
+        Expr::Block(statements) => statements.iter().for_each(|statement| collect_assigned(statement, names)),
+        Expr::If { condition, then_branch, else_branch } => {
+            collect_assigned_expr(condition, names);
+            collect_assigned_expr(then_branch, names);
+            if let Some(else_branch) = else_branch {
+                collect_assigned_expr(else_branch, names);
+            }
+        }
+        Expr::Array(elements) | Expr::StringInterpolation(elements) => elements.iter().for_each(|expr| collect_assigned_expr(expr, names)),
+        Expr::Object(fields) => fields.iter().for_each(|(_, value)| collect_assigned_expr(value, names)),
+        Expr::Index { object, index } => {
+            collect_assigned_expr(object, names);
+            collect_assigned_expr(index, names);
+        }
+        Expr::Property { object, .. } => collect_assigned_expr(object, names),
+        Expr::While { condition, body } => {
+            collect_assigned_expr(condition, names);
+            collect_assigned_expr(body, names);
+        }
+        Expr::For { iterable, body, .. } => {
+            collect_assigned_expr(iterable, names);
+            collect_assigned_expr(body, names);
+        }
+        Expr::Try { body, catches, finally } => {
+            collect_assigned_expr(body, names);
+            catches.iter().for_each(|catch| collect_assigned_expr(&catch.body, names));
+            if let Some(finally) = finally {
+                collect_assigned_expr(finally, names);
+            }
+        }
+        Expr::Throw(value) | Expr::Format { value, .. } => collect_assigned_expr(value, names),
+        Expr::Lambda { body, .. } => collect_assigned_expr(body, names),
+        Expr::Literal(_) | Expr::Identifier(_) | Expr::This | Expr::Super => {}
+    }
+}
+
+/// What `type()` returns for values of type `ty`, if there is one answer.
+fn value_type_name(ty: &Type) -> Option<&'static str> {
+    match ty {
+        Type::Number => Some("number"),
+        Type::String => Some("string"),
+        Type::Bytes => Some("bytes"),
+        Type::Array(_) => Some("array"),
+        Type::Object => Some("object"),
+        _ => None,
+    }
+}
+
+/// Parameter and result types of the built-in functions the checker knows.
+fn builtin_signature(name: &str) -> Option<Signature> {
+    use Type::*;
+    let any_array = || Array(Box::new(Any));
+    let strings = || Array(Box::new(String));
+    let data = || Type::union([Bytes, String]);
+    let signature = match name {
+        "print" | "println" => Signature::new(vec![Any], Null),
+        "input" => Signature::new(vec![Any], String),
+        "type" | "str" | "json_stringify" => Signature::new(vec![Any], String),
+        "int" | "float" | "bigint" | "decimal" => Signature::new(vec![Any], Number),
+        "num" => Signature::new(vec![Any, Number], Number).optional_after(1),
+        "to_base" => Signature::new(vec![Number, Number], String),
+        "round" => Signature::new(vec![Number, Number, String], Number).optional_after(1),
+        "bool" => Signature::new(vec![Any], Bool),
+        "len" => Signature::new(vec![Any], Number),
+        "push" => Signature::new(vec![any_array(), Any], any_array()),
+        "pop" => Signature::new(vec![any_array()], Any),
+        "keys" => Signature::new(vec![Any], strings()),
+        "values" => Signature::new(vec![Any], any_array()),
+        "entries" => Signature::new(vec![Object], Array(Box::new(any_array()))),
+        "has" => Signature::new(vec![Object, String], Bool),
+        "delete" => Signature::new(vec![Object, String], Object),
+        "json_parse" => Signature::new(vec![String], Any),
+        "read_file" => Signature::new(vec![String], String),
+        "write_file" => Signature::new(vec![String, String], Null),
+        "read_file_bytes" => Signature::new(vec![String], Bytes),
+        "write_file_bytes" => Signature::new(vec![String, Bytes], Null),
+        "exists" => Signature::new(vec![String], Bool),
+        "http_get" => Signature::new(vec![String], String),
+        "http_post" => Signature::new(vec![String, String], String),
+        "abs" | "sqrt" | "sin" | "cos" => Signature::new(vec![Number], Number),
+        "pow" => Signature::new(vec![Number, Number], Number),
+        "random" | "now" => Signature::new(vec![], Number),
+        "sleep" => Signature::new(vec![Number], Null),
+        "substr" => Signature::new(vec![String, Number, Number], String),
+        "slice" => Signature::new(vec![Any, Number, Number], Any).optional_after(2),
+        "upper" | "lower" | "trim" | "casefold" => Signature::new(vec![String], String),
+        "chars" => Signature::new(vec![String], strings()),
+        "normalize" => Signature::new(vec![String, String], String).optional_after(1),
+        "width" => Signature::new(vec![String], Number),
+        "split" => Signature::new(vec![String, String], strings()),
+        "join" => Signature::new(vec![any_array(), String], String),
+        "contains" => Signature::new(vec![Any, Any], Bool),
+        "sort" => Signature::new(vec![any_array()], any_array()),
+        "map" | "filter" | "flat_map" | "sort_by" | "sort_with" => Signature::new(vec![any_array(), Function], any_array()),
+        "reduce" => Signature::new(vec![any_array(), Function, Any], Any).optional_after(2),
+        "find" => Signature::new(vec![any_array(), Function], Any),
+        "any" | "all" => Signature::new(vec![any_array(), Function], Bool),
+        "group_by" => Signature::new(vec![any_array(), Function], Object),
+        "partition" => Signature::new(vec![any_array(), Function], Array(Box::new(any_array()))),
+        "zip" => Signature::new(vec![any_array(), any_array()], Array(Box::new(any_array()))),
+        "chunk" | "window" => Signature::new(vec![any_array(), Number], Array(Box::new(any_array()))),
+        "unique" => Signature::new(vec![any_array()], any_array()),
+        "memory_usage" => Signature::new(vec![], Object),
+        "decimal_context" => Signature::new(vec![], Object),
+        "set_decimal_context" => Signature::new(vec![Number, String], Null),
+        "implements" => Signature::new(vec![Any, Any], Bool),
+        "regex_match" => Signature::new(vec![String, String], Bool),
+        "regex_replace" => Signature::new(vec![String, String, String], String),
+        "regex_split" | "regex_find_all" => Signature::new(vec![String, String], strings()),
+        "bytes" => Signature::new(vec![Any], Bytes),
+        "encode" => Signature::new(vec![String, String], Bytes).optional_after(1),
+        "decode" => Signature::new(vec![Bytes, String], String).optional_after(1),
+        "base64_encode" | "hex_encode" => Signature::new(vec![data()], String),
+        "base64_decode" | "hex_decode" => Signature::new(vec![String], Bytes),
+        "hash" => Signature::new(vec![data(), String], String).optional_after(1),
+        _ => return None,
+    };
+    Some(signature)
+}
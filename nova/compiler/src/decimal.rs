@@ -0,0 +1,341 @@
+//! Exact decimal numbers for money and other quantities that must not
+//! pick up binary floating point error.
+//!
+//! A `Decimal` is an arbitrary-precision integer and a scale, the number of
+//! digits after the decimal point, so `12.30d` is 1230 with a scale of 2.
+//! Addition, subtraction and multiplication are exact and keep trailing
+//! zeros the way a person doing the sum by hand would. Division can't always
+//! be exact; quotients are rounded to the number of places in the
+//! interpreter's `DecimalContext`, using its rounding mode.
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// How to round a value that lies between two representable results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    /// To the nearest value, ties to the even neighbour (banker's rounding).
+    #[default]
+    HalfEven,
+    /// To the nearest value, ties away from zero.
+    HalfUp,
+    /// To the nearest value, ties towards zero.
+    HalfDown,
+    /// Away from zero.
+    Up,
+    /// Towards zero (truncation).
+    Down,
+    /// Towards positive infinity.
+    Ceiling,
+    /// Towards negative infinity.
+    Floor,
+}
+
+impl RoundingMode {
+    pub const NAMES: &'static [&'static str] = &["half_even", "half_up", "half_down", "up", "down", "ceiling", "floor"];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RoundingMode::HalfEven => "half_even",
+            RoundingMode::HalfUp => "half_up",
+            RoundingMode::HalfDown => "half_down",
+            RoundingMode::Up => "up",
+            RoundingMode::Down => "down",
+            RoundingMode::Ceiling => "ceiling",
+            RoundingMode::Floor => "floor",
+        }
+    }
+}
+
+impl FromStr for RoundingMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        match name {
+            "half_even" => Ok(RoundingMode::HalfEven),
+            "half_up" => Ok(RoundingMode::HalfUp),
+            "half_down" => Ok(RoundingMode::HalfDown),
+            "up" => Ok(RoundingMode::Up),
+            "down" => Ok(RoundingMode::Down),
+            "ceiling" => Ok(RoundingMode::Ceiling),
+            "floor" => Ok(RoundingMode::Floor),
+            _ => Err(format!("Unknown rounding mode '{}', expected one of: {}", name, Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// Settings for decimal operations that can't be exact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecimalContext {
+    /// Digits kept after the decimal point when a quotient doesn't terminate.
+    pub places: u32,
+    pub rounding: RoundingMode,
+}
+
+impl Default for DecimalContext {
+    fn default() -> Self {
+        DecimalContext { places: 28, rounding: RoundingMode::HalfEven }
+    }
+}
+
+/// An exact decimal number: `digits / 10^scale`.
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    digits: BigInt,
+    scale: u32,
+}
+
+fn power_of_ten(exponent: u32) -> BigInt {
+    num_traits::pow(BigInt::from(10), exponent as usize)
+}
+
+/// `numerator / denominator` rounded to an integer according to `mode`.
+fn divide_rounded(numerator: &BigInt, denominator: &BigInt, mode: RoundingMode) -> BigInt {
+    let (quotient, remainder) = numerator.div_rem(denominator);
+    if remainder.is_zero() {
+        return quotient;
+    }
+    // The sign of the exact result, and whether its fractional part is
+    // below, at or above one half
+    let negative = numerator.is_negative() != denominator.is_negative();
+    let half = (remainder.abs() * 2u32).cmp(&denominator.abs());
+    let away_from_zero = match mode {
+        RoundingMode::Up => true,
+        RoundingMode::Down => false,
+        RoundingMode::Ceiling => !negative,
+        RoundingMode::Floor => negative,
+        RoundingMode::HalfUp => half != Ordering::Less,
+        RoundingMode::HalfDown => half == Ordering::Greater,
+        RoundingMode::HalfEven => half == Ordering::Greater || (half == Ordering::Equal && quotient.is_odd()),
+    };
+    match (away_from_zero, negative) {
+        (false, _) => quotient,
+        (true, false) => quotient + 1,
+        (true, true) => quotient - 1,
+    }
+}
+
+impl Decimal {
+    pub fn new(digits: BigInt, scale: u32) -> Self {
+        Decimal { digits, scale }
+    }
+
+    /// The digits of the number without the decimal point.
+    pub fn digits(&self) -> &BigInt {
+        &self.digits
+    }
+
+    /// The number of digits after the decimal point.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    /// The decimal closest to `value` with the fewest digits, so that
+    /// `0.1` becomes `0.1` rather than its exact binary expansion.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        value.is_finite().then(|| value.to_string().parse().ok()).flatten()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.to_string().parse().unwrap_or(f64::NAN)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_zero()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        (&self.digits % power_of_ten(self.scale)).is_zero()
+    }
+
+    /// The integer part, rounding towards zero.
+    pub fn trunc(&self) -> BigInt {
+        divide_rounded(&self.digits, &power_of_ten(self.scale), RoundingMode::Down)
+    }
+
+    /// `self` with at least `scale` digits after the point.
+    fn rescaled(&self, scale: u32) -> BigInt {
+        if scale <= self.scale {
+            self.digits.clone()
+        } else {
+            &self.digits * power_of_ten(scale - self.scale)
+        }
+    }
+
+    /// Both operands' digits at their common scale.
+    fn aligned(&self, other: &Decimal) -> (BigInt, BigInt, u32) {
+        let scale = self.scale.max(other.scale);
+        (self.rescaled(scale), other.rescaled(scale), scale)
+    }
+
+    /// Rounds to `places` digits after the point.
+    pub fn round(&self, places: u32, mode: RoundingMode) -> Decimal {
+        if places >= self.scale {
+            return Decimal::new(self.rescaled(places), places);
+        }
+        let digits = divide_rounded(&self.digits, &power_of_ten(self.scale - places), mode);
+        Decimal::new(digits, places)
+    }
+
+    /// Drops trailing zeros after the point, keeping at least `min_scale` digits.
+    fn trimmed(mut self, min_scale: u32) -> Decimal {
+        let ten = BigInt::from(10);
+        while self.scale > min_scale {
+            let (quotient, remainder) = self.digits.div_rem(&ten);
+            if !remainder.is_zero() {
+                break;
+            }
+            self.digits = quotient;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    pub fn add(&self, other: &Decimal) -> Decimal {
+        let (a, b, scale) = self.aligned(other);
+        Decimal::new(a + b, scale)
+    }
+
+    pub fn sub(&self, other: &Decimal) -> Decimal {
+        let (a, b, scale) = self.aligned(other);
+        Decimal::new(a - b, scale)
+    }
+
+    pub fn mul(&self, other: &Decimal) -> Decimal {
+        Decimal::new(&self.digits * &other.digits, self.scale + other.scale)
+    }
+
+    /// `self / other` rounded to `context.places` digits after the point,
+    /// or `None` when dividing by zero. Exact quotients keep no more digits
+    /// than they need, but at least as many as the more precise operand.
+    pub fn div(&self, other: &Decimal, context: &DecimalContext) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let min_scale = self.scale.max(other.scale);
+        let scale = context.places.max(min_scale);
+        let numerator = &self.digits * power_of_ten(scale + other.scale - self.scale);
+        let digits = divide_rounded(&numerator, &other.digits, context.rounding);
+        Some(Decimal::new(digits, scale).trimmed(min_scale))
+    }
+
+    /// The quotient rounded towards negative infinity.
+    pub fn floor_div(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let (a, b, _) = self.aligned(other);
+        Some(Decimal::new(a.div_floor(&b), 0))
+    }
+
+    /// The remainder of truncating division, with the sign of `self`.
+    pub fn rem(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+        let (a, b, scale) = self.aligned(other);
+        Some(Decimal::new(a % b, scale))
+    }
+
+    pub fn pow(&self, exponent: u32) -> Decimal {
+        Decimal::new(num_traits::pow(self.digits.clone(), exponent as usize), self.scale * exponent)
+    }
+
+    pub fn neg(&self) -> Decimal {
+        Decimal::new(-&self.digits, self.scale)
+    }
+
+    pub fn abs(&self) -> Decimal {
+        Decimal::new(self.digits.abs(), self.scale)
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.is_integer() {
+            self.trunc().to_i64()
+        } else {
+            None
+        }
+    }
+}
+
+impl From<BigInt> for Decimal {
+    fn from(value: BigInt) -> Self {
+        Decimal::new(value, 0)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(value: i64) -> Self {
+        Decimal::new(BigInt::from(value), 0)
+    }
+}
+
+/// Parses `-12.34`, `1_000.5` or `6.02e23`.
+impl FromStr for Decimal {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid decimal: '{}'", text);
+        let cleaned = text.replace('_', "");
+        let (mantissa, exponent) = match cleaned.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().map_err(|_| invalid())?),
+            None => (cleaned.as_str(), 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let unsigned = whole.trim_start_matches(['-', '+']);
+        if (unsigned.is_empty() && fraction.is_empty())
+            || whole.len() - unsigned.len() > 1
+            || !unsigned.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let digits: BigInt = format!("{}{}", whole, fraction).parse().map_err(|_| invalid())?;
+        let scale = fraction.len() as i64 - exponent;
+        if scale >= 0 {
+            Ok(Decimal::new(digits, u32::try_from(scale).map_err(|_| invalid())?))
+        } else {
+            let shift = u32::try_from(-scale).map_err(|_| invalid())?;
+            Ok(Decimal::new(digits * power_of_ten(shift), 0))
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.digits.abs().to_string();
+        let sign = if self.digits.is_negative() { "-" } else { "" };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, _) = self.aligned(other);
+        a.cmp(&b)
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Decimals are equal when their values are, so `1.0d == 1.00d`.
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
@@ -0,0 +1,456 @@
+//! Public API for embedding Nova in a host Rust application.
+//!
+//! ```
+//! use nova_compiler::Interpreter;
+//!
+//! let mut nova = Interpreter::new();
+//! nova.register_function("double", 1, |args| {
+//!     let n: f64 = nova_compiler::FromNova::from_nova(&args[0])?;
+//!     Ok((n * 2.0).into())
+//! }).unwrap();
+//! nova.eval("fn rule(x) { double(x) + 1 }").unwrap();
+//! let result: f64 = nova.call_as("rule", &[20.0.into()]).unwrap();
+//! assert_eq!(result, 41.0);
+//! ```
+
+use crate::interpreter::{Interpreter, RuntimeError};
+use crate::lexer::Lexer;
+use crate::parser::{ParseError, Parser};
+use crate::value::{Environment, Value};
+use crate::value_serde::SerdeError;
+use indexmap::IndexMap;
+use num_bigint::BigInt;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+/// Errors surfaced to the host by the embedding API.
+#[derive(Debug)]
+pub enum NovaError {
+    Lex(String),
+    Parse(ParseError),
+    Runtime(RuntimeError),
+    /// A Nova value could not be converted to the requested Rust type.
+    Conversion { expected: &'static str, found: &'static str },
+    /// serde conversion failed; see `value_serde`.
+    Serde(SerdeError),
+    UndefinedGlobal(String),
+    NotCallable(String),
+    /// An error raised by a host function or host object.
+    Host(String),
+    /// A host function was registered under the name of a built-in.
+    ReservedName(String),
+}
+
+impl fmt::Display for NovaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NovaError::Lex(msg) => write!(f, "Lexer error: {}", msg),
+            NovaError::Parse(e) => write!(f, "Parse error: {}", e),
+            NovaError::Runtime(e) => write!(f, "Runtime error: {}", e),
+            NovaError::Conversion { expected, found } => {
+                write!(f, "Conversion error: expected {}, found {}", expected, found)
+            }
+            NovaError::Serde(e) => write!(f, "Conversion error: {}", e),
+            NovaError::UndefinedGlobal(name) => write!(f, "Undefined global: '{}'", name),
+            NovaError::NotCallable(name) => write!(f, "'{}' is not callable", name),
+            NovaError::Host(msg) => write!(f, "Host error: {}", msg),
+            NovaError::ReservedName(name) => write!(f, "'{}' is a built-in function and cannot be replaced", name),
+        }
+    }
+}
+
+impl std::error::Error for NovaError {}
+
+impl From<RuntimeError> for NovaError {
+    fn from(error: RuntimeError) -> Self {
+        NovaError::Runtime(error)
+    }
+}
+
+impl From<ParseError> for NovaError {
+    fn from(error: ParseError) -> Self {
+        NovaError::Parse(error)
+    }
+}
+
+impl From<SerdeError> for NovaError {
+    fn from(error: SerdeError) -> Self {
+        NovaError::Serde(error)
+    }
+}
+
+impl From<NovaError> for RuntimeError {
+    fn from(error: NovaError) -> Self {
+        match error {
+            NovaError::Runtime(e) => e,
+            other => RuntimeError::Host(other.to_string()),
+        }
+    }
+}
+
+pub type NovaResult<T> = Result<T, NovaError>;
+
+/// Conversion from a Nova value into a Rust type.
+pub trait FromNova: Sized {
+    fn from_nova(value: &Value) -> NovaResult<Self>;
+}
+
+/// Conversion from a Rust type into a Nova value.
+pub trait IntoNova {
+    fn into_nova(self) -> Value;
+}
+
+fn mismatch<T>(expected: &'static str, value: &Value) -> NovaResult<T> {
+    Err(NovaError::Conversion { expected, found: value.type_name() })
+}
+
+impl FromNova for Value {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromNova for f64 {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value.as_f64() {
+            Some(n) => Ok(n),
+            None => mismatch("number", value),
+        }
+    }
+}
+
+impl FromNova for f32 {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        f64::from_nova(value).map(|n| n as f32)
+    }
+}
+
+macro_rules! integer_from_nova {
+    ($($ty:ty),*) => {
+        $(
+            impl FromNova for $ty {
+                fn from_nova(value: &Value) -> NovaResult<Self> {
+                    let n = match value {
+                        Value::BigInt(n) => <$ty>::try_from(n).ok(),
+                        _ => value.as_i64().and_then(|n| <$ty>::try_from(n).ok()),
+                    };
+                    match n {
+                        Some(n) => Ok(n),
+                        None => mismatch(stringify!($ty), value),
+                    }
+                }
+            }
+
+            impl IntoNova for $ty {
+                fn into_nova(self) -> Value {
+                    // Only `u64` and `usize` values can exceed the integer range
+                    i64::try_from(self).map_or_else(|_| Value::BigInt(BigInt::from(self)), Value::Integer)
+                }
+            }
+        )*
+    };
+}
+
+integer_from_nova!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl FromNova for bool {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value {
+            Value::Boolean(b) => Ok(*b),
+            _ => mismatch("boolean", value),
+        }
+    }
+}
+
+impl FromNova for String {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value {
+            Value::String(s) => Ok(s.to_string()),
+            _ => mismatch("string", value),
+        }
+    }
+}
+
+impl FromNova for () {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value {
+            Value::Null => Ok(()),
+            _ => mismatch("null", value),
+        }
+    }
+}
+
+impl<T: FromNova> FromNova for Option<T> {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value {
+            Value::Null => Ok(None),
+            other => T::from_nova(other).map(Some),
+        }
+    }
+}
+
+impl<T: FromNova> FromNova for Vec<T> {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value {
+            Value::Array(items) => items.iter().map(T::from_nova).collect(),
+            _ => mismatch("array", value),
+        }
+    }
+}
+
+impl<T: FromNova> FromNova for HashMap<String, T> {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| T::from_nova(v).map(|v| (k.clone(), v)))
+                .collect(),
+            _ => mismatch("object", value),
+        }
+    }
+}
+
+impl<T: FromNova> FromNova for IndexMap<String, T> {
+    fn from_nova(value: &Value) -> NovaResult<Self> {
+        match value {
+            Value::Object(map) => map
+                .iter()
+                .map(|(k, v)| T::from_nova(v).map(|v| (k.clone(), v)))
+                .collect(),
+            _ => mismatch("object", value),
+        }
+    }
+}
+
+impl IntoNova for Value {
+    fn into_nova(self) -> Value {
+        self
+    }
+}
+
+impl IntoNova for f64 {
+    fn into_nova(self) -> Value {
+        Value::Number(self)
+    }
+}
+
+impl IntoNova for f32 {
+    fn into_nova(self) -> Value {
+        Value::Number(self as f64)
+    }
+}
+
+impl IntoNova for bool {
+    fn into_nova(self) -> Value {
+        Value::Boolean(self)
+    }
+}
+
+impl IntoNova for String {
+    fn into_nova(self) -> Value {
+        Value::String(self.into())
+    }
+}
+
+impl IntoNova for &str {
+    fn into_nova(self) -> Value {
+        Value::String(self.to_string().into())
+    }
+}
+
+impl IntoNova for () {
+    fn into_nova(self) -> Value {
+        Value::Null
+    }
+}
+
+impl<T: IntoNova> IntoNova for Option<T> {
+    fn into_nova(self) -> Value {
+        match self {
+            Some(value) => value.into_nova(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl<T: IntoNova> IntoNova for Vec<T> {
+    fn into_nova(self) -> Value {
+        Value::Array(self.into_iter().map(IntoNova::into_nova).collect())
+    }
+}
+
+impl<T: IntoNova> IntoNova for HashMap<String, T> {
+    fn into_nova(self) -> Value {
+        Value::Object(self.into_iter().map(|(k, v)| (k, v.into_nova())).collect())
+    }
+}
+
+impl<T: IntoNova> IntoNova for IndexMap<String, T> {
+    fn into_nova(self) -> Value {
+        Value::Object(self.into_iter().map(|(k, v)| (k, v.into_nova())).collect())
+    }
+}
+
+macro_rules! value_from {
+    ($($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    value.into_nova()
+                }
+            }
+        )*
+    };
+}
+
+value_from!(f64, i32, i64, usize, bool, String, &str);
+
+/// A Rust object exposed to scripts. Property reads and writes and method
+/// calls on the Nova side are forwarded to these methods.
+pub trait HostObject {
+    fn type_name(&self) -> &str;
+
+    fn get(&self, _property: &str) -> Option<Value> {
+        None
+    }
+
+    fn set(&mut self, property: &str, _value: Value) -> NovaResult<()> {
+        Err(NovaError::Host(format!("Cannot set property '{}' on {}", property, self.type_name())))
+    }
+
+    fn call_method(&mut self, method: &str, _args: &[Value]) -> NovaResult<Value> {
+        Err(NovaError::Host(format!("Method '{}' not found on {}", method, self.type_name())))
+    }
+}
+
+/// Shared handle to a registered host object.
+#[derive(Clone)]
+pub struct HostObjectRef(Rc<RefCell<dyn HostObject>>);
+
+impl HostObjectRef {
+    pub fn new<T: HostObject + 'static>(object: T) -> Self {
+        HostObjectRef(Rc::new(RefCell::new(object)))
+    }
+
+    pub fn borrow(&self) -> Ref<'_, dyn HostObject> {
+        self.0.borrow()
+    }
+
+    pub fn borrow_mut(&self) -> RefMut<'_, dyn HostObject> {
+        self.0.borrow_mut()
+    }
+}
+
+impl PartialEq for HostObjectRef {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl fmt::Debug for HostObjectRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HostObject({})", self.0.borrow().type_name())
+    }
+}
+
+pub type HostFunction = Rc<dyn Fn(&[Value]) -> NovaResult<Value>>;
+
+/// Whether `name` is one of the interpreter's own native functions.
+fn is_builtin(name: &str) -> bool {
+    static BUILTINS: OnceLock<HashSet<String>> = OnceLock::new();
+    BUILTINS
+        .get_or_init(|| {
+            let mut natives = Environment::new();
+            natives.define_natives();
+            let scope = natives.scope().borrow();
+            scope
+                .vars
+                .iter()
+                .filter(|(_, value)| matches!(value, Value::NativeFunction { .. }))
+                .map(|(name, _)| name.clone())
+                .collect()
+        })
+        .contains(name)
+}
+
+impl Interpreter {
+    /// Lexes, parses and runs `source` in the global scope, returning the
+    /// value of the last statement.
+    pub fn eval(&mut self, source: &str) -> NovaResult<Value> {
+        let (tokens, locations) = Lexer::new(source).tokenize_with_locations().map_err(NovaError::Lex)?;
+        let program = Parser::with_locations(tokens, locations).parse()?;
+        Ok(self.interpret(&program)?)
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals().get(name)
+    }
+
+    /// Reads a global and converts it to `T`.
+    pub fn get_global_as<T: FromNova>(&self, name: &str) -> NovaResult<T> {
+        let value = self
+            .get_global(name)
+            .ok_or_else(|| NovaError::UndefinedGlobal(name.to_string()))?;
+        T::from_nova(&value)
+    }
+
+    /// Defines or overwrites a global visible to all subsequently run code.
+    pub fn set_global<T: IntoNova>(&mut self, name: &str, value: T) {
+        self.globals().define(name.to_string(), value.into_nova());
+    }
+
+    /// Looks up a callable global, such as a function or class defined by a script.
+    pub fn get_function(&self, name: &str) -> NovaResult<Value> {
+        match self.get_global(name) {
+            Some(value) if value.is_callable() => Ok(value),
+            Some(_) => Err(NovaError::NotCallable(name.to_string())),
+            None => Err(NovaError::UndefinedGlobal(name.to_string())),
+        }
+    }
+
+    /// Calls the global function `name` with `args`.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> NovaResult<Value> {
+        let func = self.get_function(name)?;
+        self.call_value(&func, args)
+    }
+
+    /// Calls the global function `name` and converts its result to `T`.
+    pub fn call_as<T: FromNova>(&mut self, name: &str, args: &[Value]) -> NovaResult<T> {
+        let result = self.call(name, args)?;
+        T::from_nova(&result)
+    }
+
+    /// Calls any callable value, e.g. a callback a script handed back to the host.
+    pub fn call_value(&mut self, func: &Value, args: &[Value]) -> NovaResult<Value> {
+        Ok(self.run_limited(|interpreter| interpreter.call_function(func, args))?)
+    }
+
+    /// Registers a host function callable from scripts as `name(...)` with
+    /// exactly `arity` arguments. Built-in functions can't be replaced, as
+    /// method calls such as `s.len()` dispatch to them by name.
+    pub fn register_function<F>(&mut self, name: &str, arity: usize, function: F) -> NovaResult<()>
+    where
+        F: Fn(&[Value]) -> NovaResult<Value> + 'static,
+    {
+        if is_builtin(name) {
+            return Err(NovaError::ReservedName(name.to_string()));
+        }
+        self.host_functions.insert(name.to_string(), Rc::new(function));
+        self.globals().define(name.to_string(), Value::NativeFunction {
+            name: name.to_string(),
+            arity,
+        });
+        Ok(())
+    }
+
+    /// Exposes `object` to scripts as the global `name`. The returned handle
+    /// lets the host inspect the object after scripts have used it.
+    pub fn register_object<T: HostObject + 'static>(&mut self, name: &str, object: T) -> HostObjectRef {
+        let handle = HostObjectRef::new(object);
+        self.globals().define(name.to_string(), Value::HostObject(handle.clone()));
+        handle
+    }
+}
@@ -0,0 +1,62 @@
+//! Converting between strings and bytes in the text encodings `encode` and
+//! `decode` support: UTF-8, UTF-16 in either byte order and Latin-1.
+//!
+//! Names are matched case-insensitively and `utf8`, `utf-16`, `latin1` and
+//! `iso-8859-1` are accepted as aliases. `utf-16` means little-endian without
+//! a byte order mark. Text that can't be represented, and bytes that aren't
+//! valid in the encoding, are errors rather than being replaced.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+}
+
+impl Encoding {
+    const NAMES: &'static [&'static str] = &["utf-8", "utf-16le", "utf-16be", "latin-1"];
+
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Encoding::Utf8),
+            "utf-16" | "utf-16le" | "utf16" | "utf16le" => Ok(Encoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Encoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(Encoding::Latin1),
+            _ => Err(format!("Unknown encoding '{}', expected one of: {}", name, Self::NAMES.join(", "))),
+        }
+    }
+}
+
+/// `text` as bytes in the encoding called `name`.
+pub(crate) fn encode(text: &str, name: &str) -> Result<Vec<u8>, String> {
+    match Encoding::from_name(name)? {
+        Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+        Encoding::Utf16Le => Ok(text.encode_utf16().flat_map(u16::to_le_bytes).collect()),
+        Encoding::Utf16Be => Ok(text.encode_utf16().flat_map(u16::to_be_bytes).collect()),
+        Encoding::Latin1 => text
+            .chars()
+            .map(|c| u8::try_from(c).map_err(|_| format!("Cannot encode '{}' in latin-1", c)))
+            .collect(),
+    }
+}
+
+/// The text `bytes` represent in the encoding called `name`.
+pub(crate) fn decode(bytes: &[u8], name: &str) -> Result<String, String> {
+    let encoding = Encoding::from_name(name)?;
+    let units = |to_unit: fn([u8; 2]) -> u16| -> Result<Vec<u16>, String> {
+        if !bytes.len().is_multiple_of(2) {
+            return Err(format!("Invalid UTF-16: odd number of bytes ({})", bytes.len()));
+        }
+        Ok(bytes.chunks_exact(2).map(|pair| to_unit([pair[0], pair[1]])).collect())
+    };
+    let utf16 = |units: Vec<u16>| String::from_utf16(&units).map_err(|_| "Invalid UTF-16: unpaired surrogate".to_string());
+    match encoding {
+        Encoding::Utf8 => std::str::from_utf8(bytes)
+            .map(str::to_string)
+            .map_err(|e| format!("Invalid UTF-8 at byte {}", e.valid_up_to())),
+        Encoding::Utf16Le => utf16(units(u16::from_le_bytes)?),
+        Encoding::Utf16Be => utf16(units(u16::from_be_bytes)?),
+        Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+    }
+}
@@ -0,0 +1,157 @@
+//! The built-in `Error` class hierarchy.
+//!
+//! Runtime errors raised by the interpreter or by natives reach `catch`
+//! blocks as instances of these classes, so scripts can match them with
+//! typed catch clauses such as `catch (e: IOError)`. Scripts can throw them
+//! (`throw new TypeError("bad input", cause)`) or extend them like any
+//! other class. Every error instance has `message`, `name` and `cause` fields.
+//! A thrown string becomes the message of an `Error`, which also keeps the
+//! string itself as `value`, and `type` reads the same as `name`, so scripts
+//! written against the old catch object, which had `message` and `type`,
+//! keep working.
+
+use crate::gc;
+use crate::interpreter::{RuntimeError, RuntimeResult};
+use crate::text::NovaString;
+use crate::value::{Environment, Value};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+/// Name under which the shared `Error` constructor is stored in each class.
+/// It is handled by the interpreter itself rather than dispatched as a
+/// regular native, since it takes one or two arguments.
+pub const CONSTRUCTOR: &str = "Error.constructor";
+
+/// Reading this property of an error instance gives its `name`. The catch
+/// object used to have a `type` field instead.
+pub const LEGACY_TYPE_FIELD: &str = "type";
+
+/// Each built-in error class and the class it extends.
+const HIERARCHY: &[(&str, Option<&str>)] = &[
+    ("Error", None),
+    ("TypeError", Some("Error")),
+    ("ReferenceError", Some("Error")),
+    ("ZeroDivisionError", Some("Error")),
+    ("OverflowError", Some("Error")),
+    ("IOError", Some("Error")),
+    ("PermissionError", Some("Error")),
+    ("MemoryError", Some("Error")),
+    ("RecursionError", Some("Error")),
+];
+
+/// The built-in error classes, by name.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorClasses {
+    classes: HashMap<&'static str, Rc<Value>>,
+}
+
+impl ErrorClasses {
+    pub fn new() -> Self {
+        let mut classes: HashMap<&'static str, Rc<Value>> = HashMap::new();
+        for (name, parent) in HIERARCHY {
+            let superclass = parent.map(|parent| Box::new(classes[parent].as_ref().clone()));
+            let class = Value::Class {
+                name: name.to_string(),
+                superclass,
+                methods: HashMap::new(),
+                static_methods: HashMap::new(),
+                constructor: Some(Box::new(Value::NativeFunction {
+                    name: CONSTRUCTOR.to_string(),
+                    arity: 2,
+                })),
+                fields: Vec::new(),
+                static_fields: Rc::default(),
+                private_members: HashSet::new(),
+                traits: Vec::new(),
+            };
+            classes.insert(name, Rc::new(class));
+        }
+        ErrorClasses { classes }
+    }
+
+    /// Makes every error class available to scripts under its name.
+    pub fn define_in(&self, environment: &mut Environment) {
+        for (name, class) in &self.classes {
+            environment.define(name.to_string(), class.as_ref().clone());
+        }
+    }
+
+    /// Creates an instance of the built-in class `name`.
+    pub fn instantiate(&self, name: &str, message: impl Into<String>) -> Value {
+        let (name, class) = self
+            .classes
+            .get_key_value(name)
+            .unwrap_or_else(|| self.classes.get_key_value("Error").unwrap());
+        let fields = HashMap::from([
+            ("message".to_string(), Value::String(NovaString::from(message.into()))),
+            ("name".to_string(), Value::String(name.to_string().into())),
+            ("cause".to_string(), Value::Null),
+        ]);
+        let fields = Rc::new(RefCell::new(fields));
+        gc::track_fields(&fields);
+        Value::Instance {
+            class: class.clone(),
+            fields,
+        }
+    }
+
+    /// The `Error` a thrown string stands for. The string is its message and
+    /// is also kept, unchanged, as its `value`.
+    pub fn from_thrown_string(&self, thrown: NovaString) -> Value {
+        let error = self.instantiate("Error", thrown.to_string());
+        if let Value::Instance { fields, .. } = &error {
+            fields.borrow_mut().insert("value".to_string(), Value::String(thrown));
+        }
+        error
+    }
+
+    /// The value a `catch` block receives for `error`: whatever was thrown,
+    /// or an instance of the matching error class for errors raised by the
+    /// interpreter.
+    pub fn to_value(&self, error: &RuntimeError) -> Value {
+        let (class, message) = match error {
+            RuntimeError::Thrown(value) => return value.as_ref().clone(),
+            RuntimeError::UndefinedVariable(name) => ("ReferenceError", format!("Undefined variable: '{}'", name)),
+            RuntimeError::TypeError(message) => ("TypeError", message.clone()),
+            RuntimeError::DivisionByZero => ("ZeroDivisionError", "Division by zero".to_string()),
+            RuntimeError::Overflow(message) => ("OverflowError", message.clone()),
+            RuntimeError::Io(message) => ("IOError", message.clone()),
+            RuntimeError::PermissionDenied(what) => ("PermissionError", format!("Permission denied: {}", what)),
+            RuntimeError::OutOfMemory(error) => ("MemoryError", error.to_string()),
+            RuntimeError::RecursionLimitExceeded(limit) => {
+                ("RecursionError", format!("Maximum recursion depth of {} exceeded", limit))
+            }
+            RuntimeError::InvalidOperation(message) | RuntimeError::Host(message) => ("Error", message.clone()),
+            other => ("Error", other.to_string()),
+        };
+        self.instantiate(class, message)
+    }
+}
+
+/// The shared constructor of all error classes: `(message)` or `(message, cause)`.
+pub fn initialize(instance: &Value, args: &[Value]) -> RuntimeResult<()> {
+    let Value::Instance { class, fields } = instance else {
+        return Err(RuntimeError::TypeError("Error constructor called on a non-instance".to_string()));
+    };
+    if args.len() > 2 {
+        return Err(RuntimeError::InvalidOperation(
+            format!("Error constructor expects a message and an optional cause, got {} arguments", args.len())
+        ));
+    }
+    let name = match class.as_ref() {
+        Value::Class { name, .. } => name.clone(),
+        _ => "Error".to_string(),
+    };
+
+    let message = match args.first() {
+        Some(Value::String(message)) => message.clone(),
+        Some(Value::Null) | None => NovaString::default(),
+        Some(other) => other.to_string().into(),
+    };
+    let mut fields = fields.borrow_mut();
+    fields.insert("message".to_string(), Value::String(message));
+    fields.insert("name".to_string(), Value::String(name.into()));
+    fields.insert("cause".to_string(), args.get(1).cloned().unwrap_or(Value::Null));
+    Ok(())
+}
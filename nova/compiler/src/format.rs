@@ -0,0 +1,351 @@
+//! Format specifiers, as in `f"${price:.2f}"` and `format("{:>8}", n)`.
+//!
+//! A specifier is `[[fill]align][sign][#][0][width][grouping][.precision][type]`:
+//!
+//! | Part | Meaning |
+//! |---|---|
+//! | `<` `>` `^` | left, right or centre alignment, optionally after a fill character |
+//! | `+` `-` ` ` | show a sign on every number, only on negative ones (default), or a space for positive ones |
+//! | `#` | prefix binary, octal and hexadecimal numbers with `0b`, `0o` or `0x` |
+//! | `0` | pad numbers with zeros after the sign |
+//! | `,` `_` | group digits of the integer part in threes (fours in binary, octal and hex) |
+//! | `.N` | digits after the point, or at most N characters of a string |
+//! | `d` `x` `X` `o` `b` | an integer in decimal, hex, octal or binary |
+//! | `f` `e` `E` `%` | fixed point, scientific or percentage, 6 digits after the point by default |
+//! | `s` | the value as `str()` prints it |
+//!
+//! Numbers are right-aligned by default and everything else left-aligned.
+//! Widths are measured in terminal columns, so wide characters such as `日`
+//! count twice and combining accents not at all, and a string precision
+//! counts graphemes.
+//! A precision without a type formats numbers in fixed point. Decimals keep
+//! their exact digits and round with the decimal context's rounding mode.
+//! Widths and precisions above `MAX_WIDTH` are rejected, so a specifier
+//! can't ask for an arbitrarily large string.
+
+use crate::decimal::{Decimal, DecimalContext};
+use crate::interpreter::{RuntimeError, RuntimeResult};
+use crate::text;
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed};
+
+/// The largest width or precision a specifier may ask for.
+pub(crate) const MAX_WIDTH: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FormatSpec {
+    fill: char,
+    align: Option<Align>,
+    sign: char,
+    alternate: bool,
+    zero: bool,
+    width: usize,
+    grouping: Option<char>,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+fn align_of(c: char) -> Option<Align> {
+    match c {
+        '<' => Some(Align::Left),
+        '>' => Some(Align::Right),
+        '^' => Some(Align::Center),
+        _ => None,
+    }
+}
+
+impl FormatSpec {
+    pub(crate) fn parse(spec: &str) -> Result<FormatSpec, String> {
+        let invalid = |reason: String| format!("Invalid format specifier '{}': {}", spec, reason);
+        let chars: Vec<char> = spec.chars().collect();
+        let mut format = FormatSpec {
+            fill: ' ',
+            align: None,
+            sign: '-',
+            alternate: false,
+            zero: false,
+            width: 0,
+            grouping: None,
+            precision: None,
+            kind: None,
+        };
+        let mut i = 0;
+        let number = |i: &mut usize, what: &str| -> Result<Option<usize>, String> {
+            let start = *i;
+            while *i < chars.len() && chars[*i].is_ascii_digit() {
+                *i += 1;
+            }
+            if start == *i {
+                return Ok(None);
+            }
+            match chars[start..*i].iter().collect::<String>().parse() {
+                Ok(n) if n <= MAX_WIDTH => Ok(Some(n)),
+                _ => Err(invalid(format!("{} is larger than {}", what, MAX_WIDTH))),
+            }
+        };
+
+        if let Some(align) = chars.get(1).copied().and_then(align_of) {
+            format.fill = chars[0];
+            format.align = Some(align);
+            i = 2;
+        } else if let Some(align) = chars.first().copied().and_then(align_of) {
+            format.align = Some(align);
+            i = 1;
+        }
+        if let Some(&sign @ ('+' | '-' | ' ')) = chars.get(i) {
+            format.sign = sign;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'#') {
+            format.alternate = true;
+            i += 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            format.zero = true;
+            i += 1;
+        }
+        format.width = number(&mut i, "width")?.unwrap_or(0);
+        if let Some(&separator @ (',' | '_')) = chars.get(i) {
+            format.grouping = Some(separator);
+            i += 1;
+        }
+        if chars.get(i) == Some(&'.') {
+            i += 1;
+            format.precision = Some(number(&mut i, "precision")?.ok_or_else(|| invalid("expected digits after '.'".to_string()))?);
+        }
+        if let Some(&kind) = chars.get(i) {
+            if !"sdfeE%xXob".contains(kind) {
+                return Err(invalid(format!("unknown format type '{}'", kind)));
+            }
+            format.kind = Some(kind);
+            i += 1;
+        }
+        if let Some(extra) = chars.get(i) {
+            return Err(invalid(format!("unexpected '{}'", extra)));
+        }
+        Ok(format)
+    }
+
+    /// Formats `value`, whose `str()` is `text`.
+    pub(crate) fn apply(&self, value: &Value, text: &str, context: &DecimalContext) -> RuntimeResult<String> {
+        if value.is_number() && self.kind != Some('s') {
+            return Ok(self.pad(&self.number(value, text, context)?, Align::Right));
+        }
+        if let Some(kind) = self.kind.filter(|kind| *kind != 's') {
+            return Err(RuntimeError::TypeError(format!("Format type '{}' requires a number, got {}", kind, value.type_name())));
+        }
+        let body: String = match self.precision {
+            Some(precision) => text::split_graphemes(text).take(precision).collect(),
+            None => text.to_string(),
+        };
+        Ok(self.pad(&body, Align::Left))
+    }
+
+    fn number(&self, value: &Value, text: &str, context: &DecimalContext) -> RuntimeResult<String> {
+        let kind = match self.kind {
+            None if self.precision.is_some() => Some('f'),
+            kind => kind,
+        };
+        let (negative, digits) = match kind {
+            None => split_sign(text),
+            Some(kind @ ('d' | 'x' | 'X' | 'o' | 'b')) => {
+                let integer = integer_of(value).ok_or_else(|| {
+                    RuntimeError::TypeError(format!("Format type '{}' requires an integer, got {}", kind, value))
+                })?;
+                let digits = match kind {
+                    'd' => integer.abs().to_string(),
+                    'x' => integer.abs().to_str_radix(16),
+                    'X' => integer.abs().to_str_radix(16).to_uppercase(),
+                    'o' => integer.abs().to_str_radix(8),
+                    _ => integer.abs().to_str_radix(2),
+                };
+                (integer.is_negative(), digits)
+            }
+            Some('e' | 'E') => {
+                let n = value.as_f64().unwrap_or(f64::NAN);
+                let digits = scientific(n.abs(), self.precision.unwrap_or(6));
+                (n.is_sign_negative() && n != 0.0, if kind == Some('E') { digits.to_uppercase() } else { digits })
+            }
+            Some(kind) => {
+                let precision = self.precision.unwrap_or(6);
+                let text = fixed(value, precision, kind == '%', context);
+                let (negative, digits) = split_sign(&text);
+                (negative, if kind == '%' { format!("{}%", digits) } else { digits })
+            }
+        };
+
+        let radix_kind = matches!(kind, Some('x' | 'X' | 'o' | 'b'));
+        let digits = match self.grouping {
+            Some(separator) if radix_kind => group(&digits, digits.len(), separator, 4),
+            Some(separator) => {
+                let integer_len = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+                group(&digits, integer_len, separator, 3)
+            }
+            None => digits,
+        };
+        let sign = match (negative, self.sign) {
+            (true, _) => "-",
+            (false, '+') => "+",
+            (false, ' ') => " ",
+            _ => "",
+        };
+        let prefix = match kind {
+            Some('x' | 'X') if self.alternate => "0x",
+            Some('o') if self.alternate => "0o",
+            Some('b') if self.alternate => "0b",
+            _ => "",
+        };
+        let head = format!("{}{}", sign, prefix);
+        if self.zero && self.align.is_none() {
+            let padding = self.width.saturating_sub(head.chars().count() + digits.chars().count());
+            return Ok(format!("{}{}{}", head, "0".repeat(padding), digits));
+        }
+        Ok(format!("{}{}", head, digits))
+    }
+
+    fn pad(&self, body: &str, default: Align) -> String {
+        let padding = self.width.saturating_sub(text::display_width(body));
+        if padding == 0 {
+            return body.to_string();
+        }
+        let fill = |n: usize| self.fill.to_string().repeat(n);
+        match self.align.unwrap_or(default) {
+            Align::Left => format!("{}{}", body, fill(padding)),
+            Align::Right => format!("{}{}", fill(padding), body),
+            Align::Center => format!("{}{}{}", fill(padding / 2), body, fill(padding - padding / 2)),
+        }
+    }
+}
+
+fn split_sign(text: &str) -> (bool, String) {
+    match text.strip_prefix('-') {
+        Some(digits) => (true, digits.to_string()),
+        None => (false, text.to_string()),
+    }
+}
+
+/// The value of an integer, or of a float or decimal with no fractional part.
+fn integer_of(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Integer(n) => Some(BigInt::from(*n)),
+        Value::BigInt(n) => Some(n.clone()),
+        Value::Decimal(n) if n.is_integer() => Some(n.trunc()),
+        Value::Number(n) if n.fract() == 0.0 => BigInt::from_f64(*n),
+        _ => None,
+    }
+}
+
+/// `value` in fixed point with `precision` digits after the point, times 100
+/// if `percent`. Floats round as Rust's formatting does; everything else is
+/// exact up to the final rounding.
+fn fixed(value: &Value, precision: usize, percent: bool, context: &DecimalContext) -> String {
+    let exact = match value {
+        Value::Integer(n) => Some(Decimal::from(*n)),
+        Value::BigInt(n) => Some(Decimal::from(n.clone())),
+        Value::Decimal(n) => Some(n.clone()),
+        _ => None,
+    };
+    match exact {
+        Some(n) => {
+            let n = if percent { n.mul(&Decimal::from(100)) } else { n };
+            n.round(precision as u32, context.rounding).to_string()
+        }
+        None => {
+            let n = value.as_f64().unwrap_or(f64::NAN) * if percent { 100.0 } else { 1.0 };
+            format!("{:.*}", precision, n)
+        }
+    }
+}
+
+/// `n` in scientific notation with a signed, at least two-digit exponent: `6.02e+23`.
+fn scientific(n: f64, precision: usize) -> String {
+    if !n.is_finite() {
+        return n.to_string();
+    }
+    let formatted = format!("{:.*e}", precision, n);
+    let (mantissa, exponent) = formatted.split_once('e').unwrap_or((&formatted, "0"));
+    let (sign, exponent) = match exponent.strip_prefix('-') {
+        Some(exponent) => ('-', exponent),
+        None => ('+', exponent),
+    };
+    format!("{}e{}{:0>2}", mantissa, sign, exponent)
+}
+
+/// Inserts `separator` between groups of `size` digits in the first
+/// `integer_len` characters of `digits`, its integer part.
+fn group(digits: &str, integer_len: usize, separator: char, size: usize) -> String {
+    let (integer, rest) = digits.split_at(integer_len);
+    let mut grouped = String::new();
+    for (i, c) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % size == 0 {
+            grouped.push(separator);
+        }
+        grouped.push(c);
+    }
+    grouped + rest
+}
+
+/// A piece of a `format()` template: literal text or a `{index:spec}` field.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Piece {
+    Text(String),
+    Field { index: usize, spec: String },
+}
+
+/// Splits a `format()` template into text and fields. `{}` takes the next
+/// argument, `{2}` a specific one, and `{{`/`}}` stand for literal braces.
+pub(crate) fn parse_template(template: &str) -> Result<Vec<Piece>, String> {
+    let mut pieces = Vec::new();
+    let mut text = String::new();
+    let mut next_index = 0;
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                text.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                text.push('}');
+            }
+            '}' => return Err("Single '}' in format template; write '}}' for a literal brace".to_string()),
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => field.push(c),
+                        None => return Err("Unclosed '{' in format template".to_string()),
+                    }
+                }
+                let (index, spec) = field.split_once(':').unwrap_or((&field, ""));
+                let index = if index.is_empty() {
+                    next_index += 1;
+                    next_index - 1
+                } else {
+                    index.trim().parse().map_err(|_| format!("Invalid field '{{{}}}' in format template", field))?
+                };
+                FormatSpec::parse(spec)?;
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(std::mem::take(&mut text)));
+                }
+                pieces.push(Piece::Field { index, spec: spec.to_string() });
+            }
+            c => text.push(c),
+        }
+    }
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
@@ -0,0 +1,312 @@
+//! Frees reference cycles between scopes, closures and instances.
+//!
+//! A function holds on to the scope it was defined in, and that scope
+//! usually holds the function, so a scope that captured a closure is never
+//! freed by reference counting alone; neither is an instance whose fields
+//! refer back to it. Such scopes and field maps are tracked here, and once
+//! enough of them have piled up, the ones only reachable from each other are
+//! cleared, which breaks the cycles and lets reference counting free them.
+//!
+//! The collector uses trial deletion, as CPython's does: every reference
+//! found inside the graph reachable from tracked containers is subtracted
+//! from its target's reference count. Whatever still has references left is
+//! held from outside (by the interpreter, a Rust frame or the host) and is
+//! alive, along with everything it reaches. Roots therefore never need to be
+//! registered, and values held only by Rust code are safe.
+
+use crate::value::{Environment, Scope, Value};
+use std::cell::RefCell;
+use std::collections::hash_map::{self, HashMap};
+use std::rc::{Rc, Weak};
+
+type Fields = RefCell<HashMap<String, Value>>;
+
+/// A collection runs once this many containers are tracked, or twice as
+/// many as survived the previous one.
+const MIN_THRESHOLD: usize = 1_000;
+
+enum Tracked {
+    Scope(Weak<RefCell<Scope>>),
+    Fields(Weak<Fields>),
+}
+
+struct Registry {
+    tracked: HashMap<usize, Tracked>,
+    threshold: usize,
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry { tracked: HashMap::new(), threshold: MIN_THRESHOLD });
+}
+
+/// Tracks a scope that a function has captured. Returns `false` if it was
+/// already tracked.
+pub(crate) fn track_scope(environment: &Environment) -> bool {
+    let scope = environment.scope();
+    track(Rc::as_ptr(scope) as *const () as usize, || Tracked::Scope(Rc::downgrade(scope)))
+}
+
+/// Tracks the fields of an instance or the static fields of a class.
+pub(crate) fn track_fields(fields: &Rc<Fields>) {
+    track(Rc::as_ptr(fields) as *const () as usize, || Tracked::Fields(Rc::downgrade(fields)));
+}
+
+fn track(key: usize, tracked: impl FnOnce() -> Tracked) -> bool {
+    // The registry's weak reference keeps the allocation, so a key can't be
+    // reused by another container while it is registered
+    let (new, due) = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        let new = match registry.tracked.entry(key) {
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(tracked());
+                true
+            }
+            hash_map::Entry::Occupied(_) => false,
+        };
+        (new, registry.tracked.len() >= registry.threshold)
+    });
+    if due {
+        collect();
+    }
+    new
+}
+
+/// The number of tracked containers that are still alive.
+pub(crate) fn tracked() -> usize {
+    REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.tracked.retain(|_, tracked| tracked.is_alive());
+        registry.tracked.len()
+    })
+}
+
+/// Frees every tracked container that is only reachable from other garbage.
+pub(crate) fn collect() {
+    let candidates: Vec<Node> = REGISTRY.with(|registry| {
+        let mut registry = registry.borrow_mut();
+        registry.tracked.retain(|_, tracked| tracked.is_alive());
+        registry.tracked.values().filter_map(Tracked::upgrade).collect()
+    });
+
+    let mut graph = Graph::default();
+    for candidate in &candidates {
+        // Not counting the handle in `candidates`
+        graph.discover(candidate.as_ref(), 1);
+    }
+    drop(candidates);
+    graph.scan();
+    let garbage = graph.sweep();
+    drop(graph);
+    // Dropping what the garbage held frees the cycles
+    drop(garbage);
+
+    let survivors = tracked();
+    REGISTRY.with(|registry| registry.borrow_mut().threshold = MIN_THRESHOLD.max(2 * survivors));
+}
+
+impl Tracked {
+    fn is_alive(&self) -> bool {
+        match self {
+            Tracked::Scope(scope) => scope.strong_count() > 0,
+            Tracked::Fields(fields) => fields.strong_count() > 0,
+        }
+    }
+
+    fn upgrade(&self) -> Option<Node> {
+        match self {
+            Tracked::Scope(scope) => scope.upgrade().map(Node::Scope),
+            Tracked::Fields(fields) => fields.upgrade().map(Node::Fields),
+        }
+    }
+}
+
+/// A reference-counted allocation in the value graph. Classes can't be
+/// changed and so can't close a cycle on their own, but references to the
+/// functions and static fields in them have to be counted too.
+#[derive(Clone)]
+enum Node {
+    Scope(Rc<RefCell<Scope>>),
+    Fields(Rc<Fields>),
+    Class(Rc<Value>),
+}
+
+#[derive(Clone, Copy)]
+enum NodeRef<'a> {
+    Scope(&'a Rc<RefCell<Scope>>),
+    Fields(&'a Rc<Fields>),
+    Class(&'a Rc<Value>),
+}
+
+/// What a swept container held, dropped once the sweep is done.
+type Garbage = (HashMap<String, Value>, Option<Environment>);
+
+impl Node {
+    fn as_ref(&self) -> NodeRef<'_> {
+        match self {
+            Node::Scope(scope) => NodeRef::Scope(scope),
+            Node::Fields(fields) => NodeRef::Fields(fields),
+            Node::Class(class) => NodeRef::Class(class),
+        }
+    }
+
+    /// Calls `f` with every allocation this one holds a reference to.
+    /// Returns `false` if it is being modified and can't be looked into.
+    fn for_each_reference(&self, f: &mut impl FnMut(NodeRef)) -> bool {
+        match self {
+            Node::Scope(scope) => {
+                let Ok(scope) = scope.try_borrow() else {
+                    return false;
+                };
+                scope.vars.values().for_each(|value| references(value, f));
+                if let Some(parent) = &scope.parent {
+                    f(NodeRef::Scope(parent.scope()));
+                }
+            }
+            Node::Fields(fields) => {
+                let Ok(fields) = fields.try_borrow() else {
+                    return false;
+                };
+                fields.values().for_each(|value| references(value, f));
+            }
+            Node::Class(class) => references(class, f),
+        }
+        true
+    }
+
+    fn clear(&self) -> Option<Garbage> {
+        match self {
+            Node::Scope(scope) => {
+                let mut scope = scope.try_borrow_mut().ok()?;
+                Some((std::mem::take(&mut scope.vars), scope.parent.take()))
+            }
+            Node::Fields(fields) => Some((std::mem::take(&mut *fields.try_borrow_mut().ok()?), None)),
+            Node::Class(_) => None,
+        }
+    }
+}
+
+impl NodeRef<'_> {
+    fn key(self) -> usize {
+        match self {
+            NodeRef::Scope(scope) => Rc::as_ptr(scope) as *const () as usize,
+            NodeRef::Fields(fields) => Rc::as_ptr(fields) as *const () as usize,
+            NodeRef::Class(class) => Rc::as_ptr(class) as *const () as usize,
+        }
+    }
+
+    fn strong_count(self) -> usize {
+        match self {
+            NodeRef::Scope(scope) => Rc::strong_count(scope),
+            NodeRef::Fields(fields) => Rc::strong_count(fields),
+            NodeRef::Class(class) => Rc::strong_count(class),
+        }
+    }
+
+    fn to_node(self) -> Node {
+        match self {
+            NodeRef::Scope(scope) => Node::Scope(scope.clone()),
+            NodeRef::Fields(fields) => Node::Fields(fields.clone()),
+            NodeRef::Class(class) => Node::Class(class.clone()),
+        }
+    }
+}
+
+/// Calls `f` with the allocations `value` refers to directly, looking
+/// through arrays, objects and other values held by value.
+fn references(value: &Value, f: &mut impl FnMut(NodeRef)) {
+    match value {
+        Value::Array(items) => items.iter().for_each(|item| references(item, f)),
+        Value::Object(fields) => fields.values().for_each(|field| references(field, f)),
+        Value::Function { closure, .. } => f(NodeRef::Scope(closure.scope())),
+        Value::BoundMethod { receiver, method, args } => {
+            references(receiver, f);
+            references(method, f);
+            args.iter().for_each(|arg| references(arg, f));
+        }
+        Value::Class { superclass, methods, static_methods, constructor, fields, static_fields, traits, .. } => {
+            methods.values().chain(static_methods.values()).for_each(|method| references(method, f));
+            fields.iter().filter_map(|(_, initializer)| initializer.as_ref()).for_each(|initializer| references(initializer, f));
+            f(NodeRef::Fields(static_fields));
+            constructor.iter().chain(superclass).for_each(|value| references(value, f));
+            traits.iter().for_each(|implemented| references(implemented, f));
+        }
+        Value::Trait { methods, .. } => methods.values().for_each(|method| references(method, f)),
+        Value::Instance { class, fields } => {
+            f(NodeRef::Class(class));
+            f(NodeRef::Fields(fields));
+        }
+        _ => {}
+    }
+}
+
+struct Counts {
+    node: Node,
+    /// References held outside the collector's own handles.
+    count: usize,
+    /// References found inside the graph.
+    internal: usize,
+    /// Whether it was being modified, so its references are unknown.
+    opaque: bool,
+    reachable: bool,
+}
+
+#[derive(Default)]
+struct Graph {
+    entries: HashMap<usize, Counts>,
+    unscanned: Vec<usize>,
+}
+
+impl Graph {
+    /// Adds `node` if it is new; `handles` is how many references to it the
+    /// collector itself holds.
+    fn discover(&mut self, node: NodeRef, handles: usize) -> usize {
+        let key = node.key();
+        if let hash_map::Entry::Vacant(entry) = self.entries.entry(key) {
+            let count = node.strong_count() - handles;
+            entry.insert(Counts { node: node.to_node(), count, internal: 0, opaque: false, reachable: false });
+            self.unscanned.push(key);
+        }
+        key
+    }
+
+    /// Finds everything reachable from the tracked containers and counts the
+    /// references between them.
+    fn scan(&mut self) {
+        while let Some(key) = self.unscanned.pop() {
+            let node = self.entries[&key].node.clone();
+            let mut targets = Vec::new();
+            let scanned = node.for_each_reference(&mut |target| targets.push(self.discover(target, 0)));
+            drop(node);
+            for target in targets {
+                self.entries.get_mut(&target).expect("discovered").internal += 1;
+            }
+            self.entries.get_mut(&key).expect("discovered").opaque = !scanned;
+        }
+    }
+
+    /// Clears the containers that nothing outside the graph can reach, and
+    /// returns what they held.
+    fn sweep(&mut self) -> Vec<Garbage> {
+        let mut pending: Vec<usize> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.opaque || entry.count > entry.internal)
+            .map(|(key, _)| *key)
+            .collect();
+        while let Some(key) = pending.pop() {
+            let entry = self.entries.get_mut(&key).expect("discovered");
+            if entry.reachable {
+                continue;
+            }
+            entry.reachable = true;
+            let node = entry.node.clone();
+            node.for_each_reference(&mut |target| pending.push(target.key()));
+        }
+
+        self.entries
+            .values()
+            .filter(|entry| !entry.reachable)
+            .filter_map(|entry| entry.node.clear())
+            .collect()
+    }
+}
@@ -1,15 +1,49 @@
 use crate::ast::*;
-use crate::value::{Value, Environment};
-use std::collections::HashMap;
+use crate::decimal::{Decimal, DecimalContext, RoundingMode};
+use crate::embed::HostFunction;
+use crate::encoding;
+use crate::gc;
+use crate::format::{self, FormatSpec, Piece};
+use crate::errors::{self, ErrorClasses};
+use crate::limits::{self, Budget, CancelHandle, ExecutionLimits, LimitExceeded};
+use crate::memory::{self, MemoryUsage};
+use crate::methods;
+use crate::numeric;
+use crate::sandbox::{Capabilities, RedirectDenied};
+use crate::text;
+use crate::trace::{StackFrame, StackTrace};
+use crate::value::{Value, Environment, VARIADIC};
+use nova_runtime::{MemoryManager, OutOfMemory};
+use nova_stdlib::{Base64, Crypto, HashAlgorithm, Hex};
+use indexmap::IndexMap;
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, Signed, ToPrimitive};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
 #[derive(Debug)]
 pub enum RuntimeError {
     UndefinedVariable(String),
     TypeError(String),
     DivisionByZero,
+    /// Integer arithmetic whose result doesn't fit in 64 bits.
+    Overflow(String),
     InvalidOperation(String),
-    ReturnValue(Value),
-    UserThrown(String),
+    ReturnValue(Box<Value>),
+    /// A value raised by a script's `throw`.
+    Thrown(Box<Value>),
+    Io(String),
+    Host(String),
+    PermissionDenied(String),
+    OutOfFuel,
+    Timeout,
+    Cancelled,
+    CallDepthExceeded(usize),
+    /// Nesting calls deeper than the recursion limit. Unlike the call depth
+    /// limit this is a regular error that scripts can catch.
+    RecursionLimitExceeded(usize),
+    OutOfMemory(OutOfMemory),
     Break,
     Continue,
 }
@@ -20,40 +54,331 @@ impl std::fmt::Display for RuntimeError {
             RuntimeError::UndefinedVariable(name) => write!(f, "Undefined variable: '{}'", name),
             RuntimeError::TypeError(msg) => write!(f, "Type error: {}", msg),
             RuntimeError::DivisionByZero => write!(f, "Division by zero"),
+            RuntimeError::Overflow(msg) => write!(f, "Overflow error: {}", msg),
             RuntimeError::InvalidOperation(msg) => write!(f, "Invalid operation: {}", msg),
             RuntimeError::ReturnValue(_) => write!(f, "Return statement outside function"),
-            RuntimeError::UserThrown(msg) => write!(f, "Thrown error: {}", msg),
+            RuntimeError::Thrown(value) if value.is_error() => write!(f, "{}", value),
+            RuntimeError::Thrown(value) => write!(f, "Uncaught exception: {}", value),
+            RuntimeError::Io(msg) => write!(f, "I/O error: {}", msg),
+            RuntimeError::Host(msg) => write!(f, "Host error: {}", msg),
+            RuntimeError::PermissionDenied(what) => write!(f, "Permission denied: {}", what),
+            RuntimeError::OutOfFuel => write!(f, "Execution ran out of fuel"),
+            RuntimeError::Timeout => write!(f, "Execution timed out"),
+            RuntimeError::Cancelled => write!(f, "Execution was cancelled"),
+            RuntimeError::CallDepthExceeded(depth) => write!(f, "Maximum call depth of {} exceeded", depth),
+            RuntimeError::RecursionLimitExceeded(limit) => write!(f, "Maximum recursion depth of {} exceeded", limit),
+            RuntimeError::OutOfMemory(error) => write!(f, "Out of memory: {}", error),
             RuntimeError::Break => write!(f, "Break statement outside loop"),
             RuntimeError::Continue => write!(f, "Continue statement outside loop"),
         }
     }
 }
 
-type RuntimeResult<T> = Result<T, RuntimeError>;
+impl RuntimeError {
+    /// Whether this error comes from the fuel, time, cancellation or call
+    /// depth limits. These abort the whole run and are never handed to a
+    /// script's `catch` block. Running out of memory is catchable, since the
+    /// script can free memory and carry on.
+    pub fn is_limit_exceeded(&self) -> bool {
+        matches!(
+            self,
+            RuntimeError::OutOfFuel
+                | RuntimeError::Timeout
+                | RuntimeError::Cancelled
+                | RuntimeError::CallDepthExceeded(_)
+        )
+    }
+}
+
+impl RuntimeError {
+    /// Whether this is `return`, `break` or `continue` unwinding rather than an error.
+    pub fn is_control_flow(&self) -> bool {
+        matches!(self, RuntimeError::ReturnValue(_) | RuntimeError::Break | RuntimeError::Continue)
+    }
+}
+
+impl From<LimitExceeded> for RuntimeError {
+    fn from(reason: LimitExceeded) -> Self {
+        match reason {
+            LimitExceeded::Fuel => RuntimeError::OutOfFuel,
+            LimitExceeded::Timeout => RuntimeError::Timeout,
+            LimitExceeded::Cancelled => RuntimeError::Cancelled,
+        }
+    }
+}
+
+pub type RuntimeResult<T> = Result<T, RuntimeError>;
+
+/// Variable holding the class whose body the code is in. It is
+/// not a valid identifier, so scripts can't shadow or read it.
+const CLASS_SCOPE: &str = "<class>";
+
+/// How deeply calls may nest unless `set_recursion_limit` says otherwise.
+pub const DEFAULT_RECURSION_LIMIT: usize = 3_000;
+
+/// When less than this much stack is left at a call, the callee runs on a
+/// freshly allocated stack segment of `STACK_SEGMENT_SIZE` bytes. A single
+/// Nova call can take well over 100 KiB of Rust stack in debug builds.
+const STACK_RED_ZONE: usize = 1024 * 1024;
+const STACK_SEGMENT_SIZE: usize = 32 * 1024 * 1024;
+
+/// How a function body finished.
+enum Flow {
+    /// Its last expression produced a value.
+    Value(Value),
+    /// A `return` statement produced a value.
+    Return(Value),
+    /// It ended in a call that should run in place of the current function.
+    TailCall(Box<TailCall>),
+}
+
+/// A call in tail position whose callee and arguments have been evaluated.
+struct TailCall {
+    function: Value,
+    args: Vec<Value>,
+    this: Option<Value>,
+    frame: StackFrame,
+}
 
 pub struct Interpreter {
     environment: Environment,
+    globals: Environment,
+    pub(crate) host_functions: HashMap<String, HostFunction>,
+    capabilities: Capabilities,
+    error_classes: ErrorClasses,
+    limits: ExecutionLimits,
+    budget: Budget,
+    cancel: CancelHandle,
+    memory: MemoryManager,
+    recursion_limit: usize,
+    decimal_context: DecimalContext,
+    /// Scopes of callers that are waiting for a call to return. Together with
+    /// `environment` they are the roots for memory measurements.
+    suspended_environments: Vec<Environment>,
+    /// Calls in progress, outermost first.
+    call_stack: Vec<StackFrame>,
+    /// The stack captured when the error currently unwinding left its
+    /// innermost frame. After a run fails it describes the uncaught error.
+    error_trace: Option<StackTrace>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Interpreter {
+    fn drop(&mut self) {
+        // Functions defined at the top level hold on to the global scope that
+        // holds them, so only the collector can free it
+        self.environment = Environment::new();
+        self.globals = self.environment.clone();
+        self.suspended_environments.clear();
+        gc::collect();
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_capabilities(Capabilities::all())
+    }
+
+    /// Creates an interpreter whose scripts may only use the given capabilities.
+    pub fn with_capabilities(capabilities: Capabilities) -> Self {
         let mut env = Environment::new();
         env.define_natives();
+        let error_classes = ErrorClasses::new();
+        error_classes.define_in(&mut env);
         
-        Interpreter { environment: env }
+        Interpreter {
+            globals: env.clone(),
+            environment: env,
+            host_functions: HashMap::new(),
+            capabilities,
+            error_classes,
+            limits: ExecutionLimits::none(),
+            budget: Budget::default(),
+            cancel: CancelHandle::new(),
+            memory: MemoryManager::new(),
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            decimal_context: DecimalContext::default(),
+            suspended_environments: Vec::new(),
+            call_stack: Vec::new(),
+            error_trace: None,
+        }
+    }
+
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
+    /// The top-level scope shared by every script run on this interpreter.
+    pub fn globals(&self) -> Environment {
+        self.globals.clone()
+    }
+
+    /// Applies `limits` to every subsequent run.
+    pub fn set_limits(&mut self, limits: ExecutionLimits) {
+        self.memory.set_limit(limits.max_memory());
+        self.limits = limits;
+    }
+
+    pub fn limits(&self) -> &ExecutionLimits {
+        &self.limits
+    }
+
+    /// Sets how deeply calls may nest before a `RecursionError` is raised.
+    /// The evaluator grows its stack as needed, so the limit is bounded by
+    /// memory rather than by the size of the thread's stack.
+    pub fn set_recursion_limit(&mut self, limit: usize) {
+        self.recursion_limit = limit;
+    }
+
+    pub fn recursion_limit(&self) -> usize {
+        self.recursion_limit
+    }
+
+    /// Sets the places and rounding mode used when decimal division isn't exact.
+    pub fn set_decimal_context(&mut self, context: DecimalContext) {
+        self.decimal_context = context;
+    }
+
+    pub fn decimal_context(&self) -> DecimalContext {
+        self.decimal_context
+    }
+
+    /// Returns a handle that other threads can use to cancel running scripts.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
     }
 
     pub fn interpret(&mut self, program: &Program) -> RuntimeResult<Value> {
-        let mut last_value = Value::Null;
-        
-        for statement in &program.statements {
-            match self.execute_statement(statement)? {
-                Some(value) => last_value = value,
-                None => {}
+        self.run_limited(|interpreter| {
+            let mut last_value = Value::Null;
+
+            for statement in &program.statements {
+                if let Some(value) = interpreter.execute_statement(statement)? {
+                    last_value = value;
+                }
             }
+
+            Ok(last_value)
+        })
+    }
+
+    /// Measures the bytes used by values reachable from the interpreter's
+    /// scopes, per kind of value.
+    pub fn memory_usage(&mut self) -> MemoryUsage {
+        self.collect_memory();
+        self.memory.usage_by_category()
+    }
+
+    fn collect_memory(&mut self) {
+        // Cycles are only reachable from themselves and wouldn't be measured
+        gc::collect();
+        let roots = std::iter::once(&self.globals)
+            .chain(&self.suspended_environments)
+            .chain(std::iter::once(&self.environment));
+        self.memory.set_live(memory::measure_environments(roots));
+    }
+
+    /// Charges the bytes `value` owns itself to the memory manager.
+    fn charge(&mut self, value: &Value) -> RuntimeResult<()> {
+        if self.memory.limit().is_none() {
+            return Ok(());
         }
-        
-        Ok(last_value)
+        match memory::shallow_size(value) {
+            Some((category, bytes)) => self.allocate(category, bytes),
+            None => Ok(()),
+        }
+    }
+
+    /// Charges `value` and everything nested in it, for values built by natives.
+    fn charge_deep(&mut self, value: &Value) -> RuntimeResult<()> {
+        if self.memory.limit().is_none() {
+            return Ok(());
+        }
+        for (category, bytes) in memory::measure_value(value) {
+            self.allocate(&category, bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Tracks a scope a function is capturing, charging it the first time.
+    fn capture(&mut self, environment: &Environment) -> RuntimeResult<()> {
+        if gc::track_scope(environment) && self.memory.limit().is_some() {
+            self.allocate(memory::ENVIRONMENT, memory::scope_size(environment))?;
+        }
+        Ok(())
+    }
+
+    /// Charges the grapheme tables strings have built since the last call.
+    fn charge_string_tables(&mut self) -> RuntimeResult<()> {
+        let bytes = text::take_table_bytes();
+        if bytes == 0 || self.memory.limit().is_none() {
+            return Ok(());
+        }
+        self.allocate(memory::STRING, bytes)
+    }
+
+    fn allocate(&mut self, category: &str, bytes: usize) -> RuntimeResult<()> {
+        if self.memory.allocate(category, bytes).is_ok() {
+            return Ok(());
+        }
+        // Some of what was charged so far may be garbage by now
+        self.collect_memory();
+        self.memory.allocate(category, bytes).map_err(RuntimeError::OutOfMemory)
+    }
+
+    /// The call stack of the error that ended the most recent run, if it
+    /// failed inside a function call.
+    pub fn stack_trace(&self) -> Option<&StackTrace> {
+        self.error_trace.as_ref()
+    }
+
+    fn current_stack(&self) -> StackTrace {
+        StackTrace::new(self.call_stack.iter().rev().cloned().collect())
+    }
+
+    /// Runs `call` inside a new stack frame.
+    fn with_frame<T>(
+        &mut self,
+        function: String,
+        call_site: &SourceLocation,
+        call: impl FnOnce(&mut Self) -> RuntimeResult<T>,
+    ) -> RuntimeResult<T> {
+        self.call_stack.push(StackFrame { function, call_site: call_site.clone() });
+        let result = call(self);
+        self.leave_frame(&result);
+        result
+    }
+
+    /// Pops the innermost frame, first capturing the stack if an error is
+    /// unwinding out of it.
+    fn leave_frame<T>(&mut self, result: &RuntimeResult<T>) {
+        if let Err(error) = result {
+            if self.error_trace.is_none() && !error.is_control_flow() {
+                self.error_trace = Some(self.current_stack());
+            }
+        }
+        self.call_stack.pop();
+    }
+
+    /// Runs `run` with a fresh budget unless another run is already in
+    /// progress (e.g. `interpret` called by `import`), which keeps its own.
+    pub(crate) fn run_limited<T>(&mut self, run: impl FnOnce(&mut Self) -> RuntimeResult<T>) -> RuntimeResult<T> {
+        if self.budget.active_runs == 0 {
+            self.budget.refill(&self.limits);
+            // A cancellation that arrived after the last run ended is stale
+            self.cancel.take();
+            self.call_stack.clear();
+            self.error_trace = None;
+        }
+        self.budget.active_runs += 1;
+        let result = run(self);
+        self.budget.active_runs -= 1;
+        result
     }
 
     fn execute_statement(&mut self, stmt: &Stmt) -> RuntimeResult<Option<Value>> {
@@ -62,17 +387,19 @@ impl Interpreter {
                 let value = self.evaluate(expr)?;
                 Ok(Some(value))
             }
-            Stmt::Let { name, value } => {
+            Stmt::Let { name, value, .. } => {
                 let val = self.evaluate(value)?;
                 self.environment.define(name.clone(), val);
                 Ok(None)
             }
-            Stmt::Function { name, params, body } => {
+            Stmt::Function { name, params, body, .. } => {
                 let func = Value::Function {
                     params: params.clone(),
                     body: body.clone(),
                     closure: self.environment.clone(),
                 };
+                let environment = self.environment.clone();
+                self.capture(&environment)?;
                 self.environment.define(name.clone(), func);
                 Ok(None)
             }
@@ -81,13 +408,16 @@ impl Interpreter {
                     Some(e) => self.evaluate(e)?,
                     None => Value::Null,
                 };
-                Err(RuntimeError::ReturnValue(value))
+                Err(RuntimeError::ReturnValue(Box::new(value)))
             }
             Stmt::Import { module, alias } => {
                 self.import_module(module, alias.as_ref())
             }
-            Stmt::Class { name, superclass, methods } => {
-                self.define_class(name, superclass.as_ref(), methods)
+            Stmt::Class { name, superclass, methods, fields, private_methods, traits } => {
+                self.define_class(name, superclass.as_ref(), methods, fields, private_methods, traits)
+            }
+            Stmt::Trait { name, required, methods } => {
+                self.define_trait(name, required, methods)
             }
             Stmt::Break => {
                 Err(RuntimeError::Break)
@@ -99,6 +429,8 @@ impl Interpreter {
     }
 
     fn evaluate(&mut self, expr: &Expr) -> RuntimeResult<Value> {
+        self.budget.tick(&self.cancel)?;
+
         match expr {
             Expr::Literal(lit) => Ok(self.evaluate_literal(lit)),
             Expr::Identifier(name) => {
@@ -109,40 +441,64 @@ impl Interpreter {
             Expr::Binary { left, operator, right } => {
                 let left_val = self.evaluate(left)?;
                 let right_val = self.evaluate(right)?;
-                self.evaluate_binary(operator, &left_val, &right_val)
+                let result = match self.overloaded_binary(operator, &left_val, &right_val)? {
+                    Some(result) => result,
+                    None => self.evaluate_binary(operator, &left_val, &right_val)?,
+                };
+                self.charge(&result)?;
+                Ok(result)
             }
             Expr::Unary { operator, operand } => {
                 let operand_val = self.evaluate(operand)?;
+                if let UnaryOp::Minus = operator {
+                    if let Some(result) = self.call_protocol(&operand_val, "__neg__", &[])? {
+                        return Ok(result);
+                    }
+                }
                 self.evaluate_unary(operator, &operand_val)
             }
-            Expr::Call { callee, args } => {
+            Expr::Call { callee, args, location } => {
+                match callee.as_ref() {
+                    Expr::Super => return self.call_super(None, args, location),
+                    Expr::Property { object, property } if matches!(object.as_ref(), Expr::Super) => {
+                        return self.call_super(Some(property), args, location);
+                    }
+                    _ => {}
+                }
+
                 // Check if this is a method call (object.method())
                 if let Expr::Property { object, property } = callee.as_ref() {
                     let obj_val = self.evaluate(object)?;
-                    let arg_values: Result<Vec<_>, _> = args.iter()
-                        .map(|arg| self.evaluate(arg))
-                        .collect();
-                    let arg_values = arg_values?;
+                    self.check_access(object, &obj_val, property)?;
+                    let arg_values = self.evaluate_args(args)?;
                     
-                    self.call_method(&obj_val, property, &arg_values)
+                    self.with_frame(method_frame_name(&obj_val, property), location, |interpreter| {
+                        interpreter.call_method_on(object, &obj_val, property, &arg_values)
+                    })
                 } else {
                     let func = self.evaluate(callee)?;
-                    let arg_values: Result<Vec<_>, _> = args.iter()
-                        .map(|arg| self.evaluate(arg))
-                        .collect();
-                    let arg_values = arg_values?;
+                    let arg_values = self.evaluate_args(args)?;
                     
-                    self.call_function(&func, &arg_values)
+                    // Natives don't get frames of their own
+                    if let Value::NativeFunction { .. } = func {
+                        return self.call_function(&func, &arg_values);
+                    }
+                    self.with_frame(function_frame_name(callee), location, |interpreter| {
+                        interpreter.call_function(&func, &arg_values)
+                    })
                 }
             }
-            Expr::New { class, args } => {
+            Expr::New { class, args, location } => {
                 let class_value = self.evaluate(class)?;
-                let arg_values: Result<Vec<_>, _> = args.iter()
-                    .map(|arg| self.evaluate(arg))
-                    .collect();
-                let arg_values = arg_values?;
+                let arg_values = self.evaluate_args(args)?;
                 
-                self.instantiate_class(&class_value, &arg_values)
+                let frame_name = match &class_value {
+                    Value::Class { name, .. } => format!("new {}", name),
+                    _ => "new <anonymous>".to_string(),
+                };
+                self.with_frame(frame_name, location, |interpreter| {
+                    interpreter.instantiate_class(&class_value, &arg_values)
+                })
             }
             Expr::Block(statements) => {
                 // No separate scope for blocks - they share the same environment
@@ -152,14 +508,7 @@ impl Interpreter {
                     match self.execute_statement(statement) {
                         Ok(Some(value)) => result = value,
                         Ok(None) => {}
-                        Err(RuntimeError::ReturnValue(value)) => {
-                            return Ok(value);
-                        }
-                        Err(RuntimeError::Break) => return Err(RuntimeError::Break),
-                        Err(RuntimeError::Continue) => return Err(RuntimeError::Continue),
-                        Err(e) => {
-                            return Err(e);
-                        }
+                        Err(e) => return Err(e),
                     }
                 }
                 
@@ -180,31 +529,39 @@ impl Interpreter {
                 let values: Result<Vec<_>, _> = elements.iter()
                     .map(|elem| self.evaluate(elem))
                     .collect();
-                Ok(Value::Array(values?))
+                let array = Value::Array(values?);
+                self.charge(&array)?;
+                Ok(array)
             }
             Expr::Index { object, index } => {
                 let obj_val = self.evaluate(object)?;
                 let index_val = self.evaluate(index)?;
+                if let Some(result) = self.call_protocol(&obj_val, "__index__", std::slice::from_ref(&index_val))? {
+                    return Ok(result);
+                }
                 
                 match (obj_val, index_val) {
-                    (Value::Array(arr), Value::Number(idx)) => {
-                        let idx = idx as usize;
-                        if idx < arr.len() {
-                            Ok(arr[idx].clone())
-                        } else {
-                            Err(RuntimeError::InvalidOperation("Array index out of bounds".to_string()))
+                    (Value::Array(arr), idx) if idx.is_number() => {
+                        match numeric::index(&idx, arr.len())? {
+                            Some(idx) => Ok(arr[idx].clone()),
+                            None => Err(RuntimeError::InvalidOperation("Array index out of bounds".to_string())),
                         }
                     }
                     (Value::Object(obj), Value::String(key)) => {
-                        Ok(obj.get(&key).cloned().unwrap_or(Value::Null))
+                        Ok(obj.get(key.as_str()).cloned().unwrap_or(Value::Null))
                     }
-                    (Value::String(s), Value::Number(idx)) => {
-                        let idx = idx as usize;
-                        let chars: Vec<char> = s.chars().collect();
-                        if idx < chars.len() {
-                            Ok(Value::String(chars[idx].to_string()))
-                        } else {
-                            Err(RuntimeError::InvalidOperation("String index out of bounds".to_string()))
+                    (Value::String(s), idx) if idx.is_number() => {
+                        let grapheme = numeric::index(&idx, s.grapheme_count())?.and_then(|idx| s.grapheme(idx));
+                        self.charge_string_tables()?;
+                        match grapheme {
+                            Some(grapheme) => Ok(Value::String(grapheme.into())),
+                            None => Err(RuntimeError::InvalidOperation("String index out of bounds".to_string())),
+                        }
+                    }
+                    (Value::Bytes(bytes), idx) if idx.is_number() => {
+                        match numeric::index(&idx, bytes.len())? {
+                            Some(idx) => Ok(Value::Integer(bytes[idx] as i64)),
+                            None => Err(RuntimeError::InvalidOperation("Bytes index out of bounds".to_string())),
                         }
                     }
                     _ => Err(RuntimeError::TypeError("Invalid indexing operation".to_string()))
@@ -262,8 +619,8 @@ impl Interpreter {
                     Value::String(s) => {
                         let previous_var = self.environment.get(variable);
                         
-                        for ch in s.chars() {
-                            self.environment.define(variable.clone(), Value::String(ch.to_string()));
+                        for grapheme in text::split_graphemes(&s) {
+                            self.environment.define(variable.clone(), Value::String(grapheme.into()));
                             match self.evaluate(body) {
                                 Ok(value) => result = value,
                                 Err(RuntimeError::Break) => break,
@@ -272,140 +629,150 @@ impl Interpreter {
                             }
                         }
                         
-                        match previous_var {
-                            Some(val) => self.environment.define(variable.clone(), val),
-                            None => {}
+                        if let Some(val) = previous_var {
+                            self.environment.define(variable.clone(), val);
                         }
                     }
-                    _ => return Err(RuntimeError::TypeError("Can only iterate over arrays and strings".to_string()))
+                    Value::Bytes(bytes) => {
+                        let previous_var = self.environment.get(variable);
+
+                        for byte in bytes {
+                            self.environment.define(variable.clone(), Value::Integer(byte as i64));
+                            match self.evaluate(body) {
+                                Ok(value) => result = value,
+                                Err(RuntimeError::Break) => break,
+                                Err(RuntimeError::Continue) => continue,
+                                Err(e) => return Err(e),
+                            }
+                        }
+
+                        if let Some(val) = previous_var {
+                            self.environment.define(variable.clone(), val);
+                        }
+                    }
+                    Value::Object(fields) => {
+                        let previous_var = self.environment.get(variable);
+
+                        for key in fields.into_keys() {
+                            self.environment.define(variable.clone(), Value::String(key.into()));
+                            match self.evaluate(body) {
+                                Ok(value) => result = value,
+                                Err(RuntimeError::Break) => break,
+                                Err(RuntimeError::Continue) => continue,
+                                Err(e) => return Err(e),
+                            }
+                        }
+
+                        if let Some(val) = previous_var {
+                            self.environment.define(variable.clone(), val);
+                        }
+                    }
+                    _ => return Err(RuntimeError::TypeError("Can only iterate over arrays, strings, bytes and objects".to_string()))
                 }
                 
                 Ok(result)
             }
             Expr::Object(pairs) => {
-                let mut object = std::collections::HashMap::new();
+                let mut object = IndexMap::new();
                 for (key, value_expr) in pairs {
                     let value = self.evaluate(value_expr)?;
                     object.insert(key.clone(), value);
                 }
-                Ok(Value::Object(object))
+                let object = Value::Object(object);
+                self.charge(&object)?;
+                Ok(object)
             }
             Expr::Property { object, property } => {
                 let obj_val = self.evaluate(object)?;
+                self.check_access(object, &obj_val, property)?;
+                if let Some(value) = self.call_getter(&obj_val, property)? {
+                    return Ok(value);
+                }
                 match obj_val {
                     Value::Object(ref obj) => {
-                        Ok(obj.get(property).cloned().unwrap_or(Value::Null))
+                        Ok(obj.get(property).cloned()
+                            .or_else(|| methods::builtin_method(&obj_val, property).map(|method| methods::bind_builtin(&obj_val, method)))
+                            .unwrap_or(Value::Null))
                     }
                     Value::Instance { ref fields, ref class } => {
                         // First check instance fields
-                        if let Some(value) = fields.get(property) {
+                        if let Some(value) = fields.borrow().get(property) {
                             Ok(value.clone())
+                        } else if property == errors::LEGACY_TYPE_FIELD && obj_val.is_error() {
+                            Ok(obj_val.get_field("name").unwrap_or(Value::Null))
                         } else {
-                            // Then check methods from class
-                            if let Value::Class { methods, .. } = class.as_ref() {
-                                Ok(methods.get(property).cloned().unwrap_or(Value::Null))
-                            } else {
-                                Ok(Value::Null)
-                            }
+                            // Then check methods from the class and its superclasses
+                            Ok(class.find_method(property)
+                                .map_or(Value::Null, |method| bind_method(&obj_val, method)))
                         }
                     }
-                    _ => Err(RuntimeError::TypeError(format!("Cannot access property '{}' on {}", property, obj_val.type_name())))
-                }
-            }
-            Expr::Assignment { target, value } => {
-                let val = self.evaluate(value)?;
-                
-                match target.as_ref() {
-                    Expr::Identifier(name) => {
-                        self.environment.set(name, val.clone())
-                            .map_err(|e| RuntimeError::UndefinedVariable(e))?;
-                        Ok(val)
-                    }
-                    Expr::Index { object, index } => {
-                        // TODO: Implement array/object assignment by index
-                        Err(RuntimeError::InvalidOperation("Index assignment not yet implemented".to_string()))
-                    }
-                    Expr::Property { object, property } => {
-                        // For now, only support 'this.property = value' in constructors/methods
-                        if let Expr::This = object.as_ref() {
-                            if let Some(mut this_value) = self.environment.get("this") {
-                                match &mut this_value {
-                                    Value::Instance { fields, .. } => {
-                                        fields.insert(property.clone(), val.clone());
-                                        // Use define instead of set to ensure it works
-                                        self.environment.define("this".to_string(), this_value);
-                                        Ok(val)
-                                    }
-                                    _ => Err(RuntimeError::TypeError("'this' is not an instance".to_string()))
-                                }
-                            } else {
-                                Err(RuntimeError::InvalidOperation("'this' used outside class method".to_string()))
-                            }
-                        } else {
-                            Err(RuntimeError::InvalidOperation("Property assignment only supported on 'this' for now".to_string()))
+                    Value::Class { .. } => {
+                        if let Some(value) = obj_val.get_static_field(property) {
+                            return Ok(value);
                         }
+                        Ok(obj_val.find_static_method(property)
+                            .map_or(Value::Null, |method| bind_method(&obj_val, method)))
+                    }
+                    Value::HostObject(ref host) => {
+                        Ok(host.borrow().get(property).unwrap_or(Value::Null))
+                    }
+                    _ => match methods::builtin_method(&obj_val, property) {
+                        Some(method) => Ok(methods::bind_builtin(&obj_val, method)),
+                        None => Err(RuntimeError::TypeError(format!("Cannot access property '{}' on {}", property, obj_val.type_name()))),
                     }
-                    _ => Err(RuntimeError::InvalidOperation("Invalid assignment target".to_string()))
                 }
             }
+            Expr::Assignment { target, value, .. } => {
+                let val = self.evaluate(value)?;
+                self.assign(target, val)
+            }
             Expr::StringInterpolation(parts) => {
                 let mut result = String::new();
                 for part in parts {
                     let value = self.evaluate(part)?;
-                    result.push_str(&value.to_string());
+                    result.push_str(&self.stringify(&value)?);
                 }
-                Ok(Value::String(result))
+                let result = Value::String(result.into());
+                self.charge(&result)?;
+                Ok(result)
             }
-            Expr::Try { body, catch, finally } => {
-                let try_result = self.evaluate(body);
-                
-                let catch_result = match (try_result, catch) {
-                    (Ok(value), _) => Ok(value),
-                    (Err(error), Some((catch_var, catch_block))) => {
-                        // Create error object with message
-                        let error_value = Value::Object({
-                            let mut map = std::collections::HashMap::new();
-                            map.insert("message".to_string(), Value::String(format!("{:?}", error)));
-                            map.insert("type".to_string(), Value::String("RuntimeError".to_string()));
-                            map
-                        });
-                        
-                        // Store previous value of catch variable if it exists
-                        let previous_var = self.environment.get(catch_var);
-                        
-                        // Define catch variable with error object
-                        self.environment.define(catch_var.clone(), error_value);
-                        
-                        // Execute catch block
-                        let result = self.evaluate(catch_block);
-                        
-                        // Restore previous value of catch variable
-                        match previous_var {
-                            Some(val) => self.environment.define(catch_var.clone(), val),
-                            None => {} // Variable didn't exist before, leave it defined
-                        }
-                        
-                        result
+            Expr::Format { value, spec } => {
+                let value = self.evaluate(value)?;
+                let text = self.stringify(&value)?;
+                let spec = FormatSpec::parse(spec).map_err(RuntimeError::InvalidOperation)?;
+                let result = Value::String((spec.apply(&value, &text, &self.decimal_context)?).into());
+                self.charge(&result)?;
+                Ok(result)
+            }
+            Expr::Try { body, catches, finally } => {
+                let result = match self.evaluate(body) {
+                    Err(error) if !error.is_control_flow() && !error.is_limit_exceeded() => {
+                        self.catch_error(error, catches)
                     }
-                    (Err(error), None) => Err(error), // Re-throw if no catch block
+                    other => other,
                 };
                 
                 // Execute finally block if present (ignores result)
                 if let Some(finally_block) = finally {
+                    let error_trace = self.error_trace.take();
                     let _ = self.evaluate(finally_block);
+                    self.error_trace = error_trace;
                 }
                 
-                catch_result
+                result
             }
             Expr::Throw(expr) => {
-                let error_value = self.evaluate(expr)?;
-                let error_message = match error_value {
-                    Value::String(msg) => msg,
-                    _ => error_value.to_string(),
+                let value = match self.evaluate(expr)? {
+                    // Scripts written before the error classes throw strings and
+                    // read `message` in the catch block
+                    Value::String(thrown) => self.error_classes.from_thrown_string(thrown),
+                    value => value,
                 };
-                Err(RuntimeError::UserThrown(error_message))
+                Err(RuntimeError::Thrown(Box::new(value)))
             }
             Expr::Lambda { params, body } => {
+                let environment = self.environment.clone();
+                self.capture(&environment)?;
                 Ok(Value::Function {
                     params: params.clone(),
                     body: *body.clone(),
@@ -425,10 +792,62 @@ impl Interpreter {
         }
     }
 
+    /// Runs the first catch clause matching `error`, or passes the error on
+    /// unchanged if none does.
+    fn catch_error(&mut self, error: RuntimeError, catches: &[CatchClause]) -> RuntimeResult<Value> {
+        let error_value = self.error_classes.to_value(&error);
+        if !matches!(error, RuntimeError::Thrown(_)) {
+            let trace = self.error_trace.clone().unwrap_or_else(|| self.current_stack());
+            error_value.set_field("stack".to_string(), trace.to_value()).ok();
+        }
+
+        for clause in catches {
+            if let Some(class_name) = &clause.class {
+                let class = self.environment
+                    .get(class_name)
+                    .ok_or_else(|| RuntimeError::UndefinedVariable(class_name.clone()))?;
+                let Value::Class { name, .. } = &class else {
+                    return Err(RuntimeError::TypeError(
+                        format!("Catch clause type '{}' is not a class", class_name)
+                    ));
+                };
+                let matches = match &error_value {
+                    Value::Instance { class, .. } => class.extends(name),
+                    _ => false,
+                };
+                if !matches {
+                    continue;
+                }
+            }
+
+            // The error is handled; the next one starts a new trace
+            self.error_trace = None;
+
+            // Store previous value of catch variable if it exists
+            let previous_var = self.environment.get(&clause.variable);
+            self.environment.define(clause.variable.clone(), error_value);
+
+            let result = self.evaluate(&clause.body);
+
+            // Restore previous value of catch variable
+            // Variable didn't exist before, leave it defined
+            if let Some(val) = previous_var {
+                self.environment.define(clause.variable.clone(), val);
+            }
+            return result;
+        }
+
+        Err(error)
+    }
+
     fn evaluate_literal(&self, lit: &Literal) -> Value {
         match lit {
+            Literal::Integer(n) => Value::Integer(*n),
+            Literal::BigInt(n) => Value::BigInt(n.clone()),
+            Literal::Decimal(n) => Value::Decimal(n.clone()),
             Literal::Number(n) => Value::Number(*n),
-            Literal::String(s) => Value::String(s.clone()),
+            Literal::String(s) => Value::String(s.clone().into()),
+            Literal::Bytes(b) => Value::Bytes(b.clone()),
             Literal::Boolean(b) => Value::Boolean(*b),
             Literal::Array(arr) => {
                 let values: Vec<Value> = arr.iter().map(|lit| self.evaluate_literal(lit)).collect();
@@ -444,67 +863,34 @@ impl Interpreter {
     }
 
     fn evaluate_binary(&self, op: &BinaryOp, left: &Value, right: &Value) -> RuntimeResult<Value> {
+        if let BinaryOp::InstanceOf = op {
+            return match right {
+                Value::Class { .. } | Value::Trait { .. } => Ok(Value::Boolean(left.is_instance_of(right))),
+                _ => Err(RuntimeError::TypeError(
+                    format!("Right-hand side of 'instanceof' must be a class or trait, got {}", right.type_name())
+                )),
+            };
+        }
+
+        if let Some(result) = numeric::binary(op, left, right, &self.decimal_context) {
+            return result;
+        }
+
         match (left, right) {
-            (Value::Number(l), Value::Number(r)) => {
+            (Value::String(l), Value::String(r)) => {
                 match op {
-                    BinaryOp::Add => Ok(Value::Number(l + r)),
-                    BinaryOp::Subtract => Ok(Value::Number(l - r)),
-                    BinaryOp::Multiply => Ok(Value::Number(l * r)),
-                    BinaryOp::Divide => {
-                        if *r == 0.0 {
-                            Err(RuntimeError::DivisionByZero)
-                        } else {
-                            Ok(Value::Number(l / r))
-                        }
-                    }
-                    BinaryOp::Modulo => {
-                        if *r == 0.0 {
-                            Err(RuntimeError::DivisionByZero)
-                        } else {
-                            Ok(Value::Number(l % r))
-                        }
-                    }
-                    BinaryOp::Power => Ok(Value::Number(l.powf(*r))),
+                    BinaryOp::Add => Ok(Value::String(format!("{}{}", l, r).into())),
                     BinaryOp::Equal => Ok(Value::Boolean(l == r)),
                     BinaryOp::NotEqual => Ok(Value::Boolean(l != r)),
-                    BinaryOp::Less => Ok(Value::Boolean(l < r)),
-                    BinaryOp::Greater => Ok(Value::Boolean(l > r)),
-                    BinaryOp::LessEqual => Ok(Value::Boolean(l <= r)),
-                    BinaryOp::GreaterEqual => Ok(Value::Boolean(l >= r)),
-                    BinaryOp::BitwiseAnd => {
-                        let l_int = *l as i64;
-                        let r_int = *r as i64;
-                        Ok(Value::Number((l_int & r_int) as f64))
-                    }
-                    BinaryOp::BitwiseOr => {
-                        let l_int = *l as i64;
-                        let r_int = *r as i64;
-                        Ok(Value::Number((l_int | r_int) as f64))
-                    }
-                    BinaryOp::BitwiseXor => {
-                        let l_int = *l as i64;
-                        let r_int = *r as i64;
-                        Ok(Value::Number((l_int ^ r_int) as f64))
-                    }
-                    BinaryOp::LeftShift => {
-                        let l_int = *l as i64;
-                        let r_int = *r as i64;
-                        Ok(Value::Number((l_int << r_int) as f64))
-                    }
-                    BinaryOp::RightShift => {
-                        let l_int = *l as i64;
-                        let r_int = *r as i64;
-                        Ok(Value::Number((l_int >> r_int) as f64))
-                    }
-                    _ => Err(RuntimeError::InvalidOperation(format!("Cannot apply {:?} to numbers", op))),
+                    _ => Err(RuntimeError::InvalidOperation(format!("Cannot apply {:?} to strings", op))),
                 }
             }
-            (Value::String(l), Value::String(r)) => {
+            (Value::Bytes(l), Value::Bytes(r)) => {
                 match op {
-                    BinaryOp::Add => Ok(Value::String(format!("{}{}", l, r))),
+                    BinaryOp::Add => Ok(Value::Bytes([l.as_slice(), r.as_slice()].concat())),
                     BinaryOp::Equal => Ok(Value::Boolean(l == r)),
                     BinaryOp::NotEqual => Ok(Value::Boolean(l != r)),
-                    _ => Err(RuntimeError::InvalidOperation(format!("Cannot apply {:?} to strings", op))),
+                    _ => Err(RuntimeError::InvalidOperation(format!("Cannot apply {:?} to bytes", op))),
                 }
             }
             (Value::Boolean(l), Value::Boolean(r)) => {
@@ -520,7 +906,7 @@ impl Interpreter {
                 match op {
                     BinaryOp::Add => {
                         // Auto-convert values to string for concatenation
-                        Ok(Value::String(format!("{}{}", left, right)))
+                        Ok(Value::String(format!("{}{}", left, right).into()))
                     }
                     BinaryOp::Equal => Ok(Value::Boolean(left == right)),
                     BinaryOp::NotEqual => Ok(Value::Boolean(left != right)),
@@ -539,28 +925,25 @@ impl Interpreter {
         match op {
             UnaryOp::Not => Ok(Value::Boolean(!operand.is_truthy())),
             UnaryOp::Minus => {
-                match operand {
-                    Value::Number(n) => Ok(Value::Number(-n)),
-                    _ => Err(RuntimeError::TypeError(
+                match numeric::negate(operand) {
+                    Some(result) => result,
+                    None => Err(RuntimeError::TypeError(
                         format!("Cannot apply unary minus to {}", operand.type_name())
                     )),
                 }
             }
             UnaryOp::Plus => {
                 match operand {
-                    Value::Number(n) => Ok(Value::Number(*n)),
+                    Value::Integer(_) | Value::Number(_) => Ok(operand.clone()),
                     _ => Err(RuntimeError::TypeError(
                         format!("Cannot apply unary plus to {}", operand.type_name())
                     )),
                 }
             }
             UnaryOp::BitwiseNot => {
-                match operand {
-                    Value::Number(n) => {
-                        let int_val = *n as i64;
-                        Ok(Value::Number((!int_val) as f64))
-                    }
-                    _ => Err(RuntimeError::TypeError(
+                match numeric::bitwise_not(operand) {
+                    Some(result) => result,
+                    None => Err(RuntimeError::TypeError(
                         format!("Cannot apply bitwise not to {}", operand.type_name())
                     )),
                 }
@@ -568,48 +951,30 @@ impl Interpreter {
         }
     }
 
-    fn call_function(&mut self, func: &Value, args: &[Value]) -> RuntimeResult<Value> {
+    pub(crate) fn call_function(&mut self, func: &Value, args: &[Value]) -> RuntimeResult<Value> {
         match func {
             Value::NativeFunction { name, arity } => {
-                if args.len() != *arity {
+                if *arity != VARIADIC && args.len() != *arity {
                     return Err(RuntimeError::InvalidOperation(
                         format!("Function '{}' expects {} arguments, got {}", name, arity, args.len())
                     ));
                 }
-                self.call_native_function(name, args)
+                let result = self.call_native_function(name, args)?;
+                self.charge_string_tables()?;
+                self.charge_deep(&result)?;
+                Ok(result)
             }
-            Value::Function { params, body, closure } => {
-                if args.len() != params.len() {
-                    return Err(RuntimeError::InvalidOperation(
-                        format!("Function expects {} arguments, got {}", params.len(), args.len())
-                    ));
-                }
-
-                let previous_env = self.environment.clone();
-                
-                // Check if 'this' is already in environment (for constructor/method calls)
-                let preserve_this = self.environment.get("this");
-                
-                self.environment = Environment::with_parent(closure.clone());
-
-                // Restore 'this' if it was present
-                if let Some(this_value) = preserve_this {
-                    self.environment.define("this".to_string(), this_value);
-                }
-
-                for (param, arg) in params.iter().zip(args.iter()) {
-                    self.environment.define(param.clone(), arg.clone());
-                }
-
-                let result = match self.evaluate(body) {
-                    Ok(value) => Ok(value),
-                    Err(RuntimeError::ReturnValue(value)) => Ok(value),
-                    Err(e) => Err(e),
-                };
-
-                self.environment = previous_env;
-                result
+            Value::Function { .. } => self.call_closure(func, args, None),
+            Value::BoundMethod { receiver, method, args: bound_args } => {
+                let args: Vec<Value> = bound_args.iter().chain(args).cloned().collect();
+                self.call_closure(method, &args, Some(receiver.as_ref().clone()))
             }
+            Value::Instance { .. } => match self.call_protocol(func, "__call__", args)? {
+                Some(result) => Ok(result),
+                None => Err(RuntimeError::TypeError(
+                    format!("{} is not callable; define __call__ to call it", func)
+                )),
+            },
             Value::Class { .. } => {
                 // Calling a class directly instantiates it
                 self.instantiate_class(func, args)
@@ -620,33 +985,220 @@ impl Interpreter {
         }
     }
     
+    fn evaluate_args(&mut self, args: &[Expr]) -> RuntimeResult<Vec<Value>> {
+        args.iter().map(|arg| self.evaluate(arg)).collect()
+    }
+
+    /// Calls a script function with `this` bound to `this`, or to the
+    /// caller's `this` if `None`. Calls the function makes in tail position
+    /// run here, one after the other, so they don't grow the stack.
+    pub(crate) fn call_closure(&mut self, func: &Value, args: &[Value], this: Option<Value>) -> RuntimeResult<Value> {
+        if let Some(max_depth) = self.limits.max_call_depth() {
+            if self.budget.call_depth >= max_depth {
+                return Err(RuntimeError::CallDepthExceeded(max_depth));
+            }
+        }
+        if self.budget.call_depth >= self.recursion_limit {
+            return Err(RuntimeError::RecursionLimitExceeded(self.recursion_limit));
+        }
+
+        self.budget.call_depth += 1;
+        let mut flow = self.run_body(func, args, this);
+        // Tail calls share one frame on top of the frame of the original call
+        let mut in_tail_frame = false;
+        let result = loop {
+            match flow {
+                Ok(Flow::Value(value)) | Ok(Flow::Return(value)) => break Ok(value),
+                Ok(Flow::TailCall(call)) => {
+                    let TailCall { function, args, this, frame } = *call;
+                    if in_tail_frame {
+                        self.call_stack.pop();
+                    }
+                    self.call_stack.push(frame);
+                    in_tail_frame = true;
+                    flow = self.run_body(&function, &args, this);
+                }
+                Err(error) => break Err(error),
+            }
+        };
+        if in_tail_frame {
+            self.leave_frame(&result);
+        }
+        self.budget.call_depth -= 1;
+        result
+    }
+
+    /// Binds the arguments of a script function and evaluates its body.
+    fn run_body(&mut self, func: &Value, args: &[Value], this: Option<Value>) -> RuntimeResult<Flow> {
+        let Value::Function { params, body, closure } = func else {
+            return self.call_function(func, args).map(Flow::Value);
+        };
+        if args.len() != params.len() {
+            return Err(RuntimeError::InvalidOperation(
+                format!("Function expects {} arguments, got {}", params.len(), args.len())
+            ));
+        }
+
+        let previous_env = self.environment.clone();
+        self.suspended_environments.push(previous_env.clone());
+
+        // Plain calls keep the caller's 'this' (e.g. helpers called from methods)
+        let this = this.or_else(|| self.environment.get("this"));
+
+        self.environment = Environment::with_parent(closure.clone());
+        if let Some(this_value) = this {
+            self.environment.define("this".to_string(), this_value);
+        }
+        for (param, arg) in params.iter().zip(args.iter()) {
+            self.environment.define(param.clone(), arg.clone());
+        }
+
+        let result = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || self.evaluate_body(body, true));
+        let result = match result {
+            Err(RuntimeError::ReturnValue(value)) => Ok(Flow::Return(*value)),
+            other => other,
+        };
+
+        self.suspended_environments.pop();
+        self.environment = previous_env;
+        result
+    }
+
+    /// Evaluates part of a function body. When `tail` is set the value of
+    /// `expr` is the function's result, so a call there is handed back as a
+    /// `Flow::TailCall` instead of being made. `return` always ends the
+    /// function, so its operand is in tail position wherever it appears.
+    fn evaluate_body(&mut self, expr: &Expr, tail: bool) -> RuntimeResult<Flow> {
+        match expr {
+            Expr::Block(statements) => {
+                self.budget.tick(&self.cancel)?;
+                let mut result = Value::Null;
+                for (i, statement) in statements.iter().enumerate() {
+                    let last = tail && i + 1 == statements.len();
+                    match statement {
+                        Stmt::Return(Some(value)) => {
+                            return Ok(match self.evaluate_body(value, true)? {
+                                Flow::Value(value) => Flow::Return(value),
+                                flow => flow,
+                            });
+                        }
+                        Stmt::Expression(expr) => match self.evaluate_body(expr, last)? {
+                            Flow::Value(value) => result = value,
+                            flow => return Ok(flow),
+                        },
+                        _ => {
+                            if let Some(value) = self.execute_statement(statement)? {
+                                result = value;
+                            }
+                        }
+                    }
+                }
+                Ok(Flow::Value(result))
+            }
+            Expr::If { condition, then_branch, else_branch } => {
+                self.budget.tick(&self.cancel)?;
+                if self.evaluate(condition)?.is_truthy() {
+                    self.evaluate_body(then_branch, tail)
+                } else if let Some(else_expr) = else_branch {
+                    self.evaluate_body(else_expr, tail)
+                } else {
+                    Ok(Flow::Value(Value::Null))
+                }
+            }
+            Expr::Call { callee, args, location } if tail && !is_super_call(callee) => {
+                self.budget.tick(&self.cancel)?;
+                self.tail_call(callee, args, location)
+            }
+            _ => self.evaluate(expr).map(Flow::Value),
+        }
+    }
+
+    /// Evaluates a call in tail position. Calls to script functions and
+    /// methods are returned as a `Flow::TailCall`; anything else is called
+    /// right away.
+    fn tail_call(&mut self, callee: &Expr, args: &[Expr], location: &SourceLocation) -> RuntimeResult<Flow> {
+        let (function, this, frame_name) = match callee {
+            Expr::Property { object: object_expr, property } => {
+                let object = self.evaluate(object_expr)?;
+                self.check_access(object_expr, &object, property)?;
+                let frame_name = method_frame_name(&object, property);
+                match self.find_method(&object, property) {
+                    Some(method) => (method, Some(object), frame_name),
+                    None => {
+                        let args = self.evaluate_args(args)?;
+                        return self
+                            .with_frame(frame_name, location, |interpreter| interpreter.call_method_on(object_expr, &object, property, &args))
+                            .map(Flow::Value);
+                    }
+                }
+            }
+            _ => (self.evaluate(callee)?, self.environment.get("this"), function_frame_name(callee)),
+        };
+        let args = self.evaluate_args(args)?;
+
+        match function {
+            Value::Function { .. } => Ok(Flow::TailCall(Box::new(TailCall {
+                function,
+                args,
+                this,
+                frame: StackFrame { function: frame_name, call_site: location.clone() },
+            }))),
+            Value::NativeFunction { .. } => self.call_function(&function, &args).map(Flow::Value),
+            _ => self
+                .with_frame(frame_name, location, |interpreter| interpreter.call_function(&function, &args))
+                .map(Flow::Value),
+        }
+    }
+
+    /// The client for `http_get` and `http_post`, which checks every
+    /// redirect against the sandbox.
+    fn http_client(&self) -> RuntimeResult<reqwest::blocking::Client> {
+        reqwest::blocking::Client::builder()
+            .redirect(self.capabilities.redirect_policy())
+            .build()
+            .map_err(|e| RuntimeError::Io(format!("HTTP client failed: {}", e)))
+    }
+
     fn call_native_function(&mut self, name: &str, args: &[Value]) -> RuntimeResult<Value> {
+        if let Some(host_function) = self.host_functions.get(name).cloned() {
+            return host_function(args).map_err(RuntimeError::from);
+        }
+
         match name {
             "print" => {
-                println!("{}", args[0]);
+                println!("{}", self.stringify(&args[0])?);
                 Ok(Value::Null)
             }
             "println" => {
-                println!("{}", args[0]);
+                println!("{}", self.stringify(&args[0])?);
                 Ok(Value::Null)
             }
             "input" => {
+                self.capabilities.check_stdin().map_err(RuntimeError::PermissionDenied)?;
                 use std::io::{self, Write};
                 print!("{}", args[0]);
                 io::stdout().flush().unwrap();
                 
                 let mut input = String::new();
                 io::stdin().read_line(&mut input).unwrap();
-                Ok(Value::String(input.trim().to_string()))
+                Ok(Value::String(input.trim().to_string().into()))
             }
             "len" => {
                 if args.len() != 1 {
                     return Err(RuntimeError::InvalidOperation("len expects exactly one argument".to_string()));
                 }
                 match &args[0] {
-                    Value::String(s) => Ok(Value::Number(s.len() as f64)),
-                    Value::Array(arr) => Ok(Value::Number(arr.len() as f64)),
-                    _ => Err(RuntimeError::TypeError("len can only be applied to strings and arrays".to_string()))
+                    Value::String(s) => Ok(Value::Integer(s.grapheme_count() as i64)),
+                    Value::Bytes(b) => Ok(Value::Integer(b.len() as i64)),
+                    Value::Array(arr) => Ok(Value::Integer(arr.len() as i64)),
+                    instance @ Value::Instance { .. } => match self.call_protocol(instance, "__len__", &[])? {
+                        Some(n) if n.is_number() => Ok(n),
+                        Some(other) => Err(RuntimeError::TypeError(
+                            format!("__len__ must return a number, got {}", other.type_name())
+                        )),
+                        None => Err(RuntimeError::TypeError("len can only be applied to strings, arrays and instances defining __len__".to_string())),
+                    },
+                    _ => Err(RuntimeError::TypeError("len can only be applied to strings, bytes and arrays".to_string()))
                 }
             }
             "push" => {
@@ -679,46 +1231,231 @@ impl Interpreter {
                     _ => Err(RuntimeError::TypeError("pop can only be applied to arrays".to_string()))
                 }
             }
+            "keys" | "values" | "entries" => {
+                match &args[0] {
+                    Value::Object(fields) => Ok(Value::Array(fields.iter()
+                        .map(|(key, value)| match name {
+                            "keys" => Value::String(key.as_str().into()),
+                            "values" => value.clone(),
+                            _ => Value::Array(vec![Value::String(key.as_str().into()), value.clone()]),
+                        })
+                        .collect())),
+                    other => Err(RuntimeError::TypeError(format!("{}() requires an object, got {}", name, other.type_name())))
+                }
+            }
+            "has" | "delete" => {
+                match (&args[0], &args[1]) {
+                    (Value::Object(fields), Value::String(key)) if name == "has" => Ok(Value::Boolean(fields.contains_key(key.as_str()))),
+                    (Value::Object(fields), Value::String(key)) => {
+                        // Like `push`, returns a new object; the rest keep their order
+                        let mut fields = fields.clone();
+                        fields.shift_remove(key.as_str());
+                        Ok(Value::Object(fields))
+                    }
+                    (Value::Object(_), other) => Err(RuntimeError::TypeError(format!("{}() requires a string key, got {}", name, other.type_name()))),
+                    (other, _) => Err(RuntimeError::TypeError(format!("{}() requires an object, got {}", name, other.type_name()))),
+                }
+            }
+            "merge" => {
+                if args.is_empty() {
+                    return Err(RuntimeError::InvalidOperation("merge() requires at least one object".to_string()));
+                }
+                // Later objects win; keys keep the position they first appeared at
+                let mut merged = IndexMap::new();
+                for arg in args {
+                    match arg {
+                        Value::Object(fields) => merged.extend(fields.iter().map(|(key, value)| (key.clone(), value.clone()))),
+                        other => return Err(RuntimeError::TypeError(format!("merge() requires objects, got {}", other.type_name()))),
+                    }
+                }
+                Ok(Value::Object(merged))
+            }
             "type" => {
                 if args.len() != 1 {
                     return Err(RuntimeError::InvalidOperation("type expects exactly one argument".to_string()));
                 }
-                Ok(Value::String(args[0].type_name().to_string()))
+                Ok(Value::String(args[0].type_name().to_string().into()))
             }
             "str" => {
                 if args.len() != 1 {
                     return Err(RuntimeError::InvalidOperation("str expects exactly one argument".to_string()));
                 }
-                Ok(Value::String(args[0].to_string()))
+                Ok(Value::String((self.stringify(&args[0])?).into()))
             }
             "num" => {
-                if args.len() != 1 {
-                    return Err(RuntimeError::InvalidOperation("num expects exactly one argument".to_string()));
+                if args.is_empty() || args.len() > 2 {
+                    return Err(RuntimeError::InvalidOperation("num expects a value and an optional radix".to_string()));
+                }
+                if let Some(radix) = args.get(1) {
+                    let radix = radix.as_i64().and_then(|radix| u32::try_from(radix).ok()).filter(|radix| (2..=36).contains(radix));
+                    return match (&args[0], radix) {
+                        (_, None) => Err(RuntimeError::InvalidOperation(format!("Radix must be between 2 and 36, got {}", args[1]))),
+                        (Value::String(s), Some(radix)) => numeric::parse_integer(s, radix).ok_or_else(|| {
+                            RuntimeError::InvalidOperation(format!("Cannot parse '{}' as a base-{} integer", s, radix))
+                        }),
+                        (other, _) => Err(RuntimeError::TypeError(format!("num() with a radix requires a string, got {}", other.type_name()))),
+                    };
+                }
+                match &args[0] {
+                    n if n.is_number() => Ok(n.clone()),
+                    Value::String(s) => {
+                        if let Some(radix) = numeric::literal_radix(s) {
+                            return numeric::parse_integer(s, radix).ok_or_else(|| {
+                                RuntimeError::InvalidOperation(format!("Cannot convert '{}' to a number", s))
+                            });
+                        }
+                        if let Ok(n) = s.parse::<i64>() {
+                            return Ok(Value::Integer(n));
+                        }
+                        if let Ok(n) = s.parse::<BigInt>() {
+                            return Ok(Value::BigInt(n));
+                        }
+                        match s.parse::<f64>() {
+                            Ok(n) => Ok(Value::Number(n)),
+                            Err(_) => Err(RuntimeError::InvalidOperation("Cannot convert string to number".to_string()))
+                        }
+                    }
+                    Value::Boolean(b) => Ok(Value::Integer(*b as i64)),
+                    _ => Err(RuntimeError::TypeError("Cannot convert value to number".to_string()))
                 }
+            }
+            "to_base" => {
+                let radix = args[1].as_i64().and_then(|radix| u32::try_from(radix).ok()).unwrap_or(0);
+                numeric::to_base(&args[0], radix).map(|digits| Value::String(digits.into()))
+            }
+            "int" => {
+                match &args[0] {
+                    Value::Integer(n) => Ok(Value::Integer(*n)),
+                    Value::BigInt(n) => n
+                        .to_i64()
+                        .map(Value::Integer)
+                        .ok_or_else(|| RuntimeError::Overflow(format!("{} does not fit in a 64-bit integer", n))),
+                    Value::Decimal(n) => n
+                        .trunc()
+                        .to_i64()
+                        .map(Value::Integer)
+                        .ok_or_else(|| RuntimeError::Overflow(format!("{} does not fit in a 64-bit integer", n))),
+                    Value::Number(n) if n.is_nan() || n.is_infinite() => {
+                        Err(RuntimeError::InvalidOperation(format!("Cannot convert {} to an integer", n)))
+                    }
+                    // Truncates towards zero
+                    Value::Number(n) => numeric::float_to_i64(n.trunc())
+                        .map(Value::Integer)
+                        .ok_or_else(|| RuntimeError::Overflow(format!("{} does not fit in a 64-bit integer", n))),
+                    Value::String(s) => s.trim().parse::<i64>().map(Value::Integer).map_err(|_| {
+                        RuntimeError::InvalidOperation(format!("Cannot convert '{}' to an integer", s))
+                    }),
+                    Value::Boolean(b) => Ok(Value::Integer(*b as i64)),
+                    other => Err(RuntimeError::TypeError(format!("Cannot convert {} to an integer", other.type_name()))),
+                }
+            }
+            "float" => {
+                match &args[0] {
+                    Value::Integer(n) => Ok(Value::Number(*n as f64)),
+                    Value::Number(n) => Ok(Value::Number(*n)),
+                    n @ (Value::BigInt(_) | Value::Decimal(_)) => match n.as_f64() {
+                        Some(float) if float.is_finite() => Ok(Value::Number(float)),
+                        _ => Err(RuntimeError::Overflow(format!("{} is too large for a float", n))),
+                    },
+                    Value::String(s) => s.trim().parse::<f64>().map(Value::Number).map_err(|_| {
+                        RuntimeError::InvalidOperation(format!("Cannot convert '{}' to a float", s))
+                    }),
+                    Value::Boolean(b) => Ok(Value::Number(*b as i64 as f64)),
+                    other => Err(RuntimeError::TypeError(format!("Cannot convert {} to a float", other.type_name()))),
+                }
+            }
+            "bigint" => {
+                match &args[0] {
+                    Value::Integer(n) => Ok(Value::BigInt(BigInt::from(*n))),
+                    Value::BigInt(n) => Ok(Value::BigInt(n.clone())),
+                    Value::Decimal(n) => Ok(Value::BigInt(n.trunc())),
+                    // Truncates towards zero
+                    Value::Number(n) => BigInt::from_f64(n.trunc()).map(Value::BigInt).ok_or_else(|| {
+                        RuntimeError::InvalidOperation(format!("Cannot convert {} to an integer", n))
+                    }),
+                    Value::String(s) => s.trim().replace('_', "").parse::<BigInt>().map(Value::BigInt).map_err(|_| {
+                        RuntimeError::InvalidOperation(format!("Cannot convert '{}' to an integer", s))
+                    }),
+                    Value::Boolean(b) => Ok(Value::BigInt(BigInt::from(*b as i64))),
+                    other => Err(RuntimeError::TypeError(format!("Cannot convert {} to an integer", other.type_name()))),
+                }
+            }
+            "decimal" => {
+                match &args[0] {
+                    Value::Integer(n) => Ok(Value::Decimal(Decimal::from(*n))),
+                    Value::BigInt(n) => Ok(Value::Decimal(Decimal::from(n.clone()))),
+                    Value::Decimal(n) => Ok(Value::Decimal(n.clone())),
+                    // The shortest decimal that reads back as the same float
+                    Value::Number(n) => Decimal::from_f64(*n).map(Value::Decimal).ok_or_else(|| {
+                        RuntimeError::InvalidOperation(format!("Cannot convert {} to a decimal", n))
+                    }),
+                    Value::String(s) => s.trim().parse::<Decimal>().map(Value::Decimal).map_err(RuntimeError::InvalidOperation),
+                    other => Err(RuntimeError::TypeError(format!("Cannot convert {} to a decimal", other.type_name()))),
+                }
+            }
+            "round" => {
+                if args.is_empty() || args.len() > 3 {
+                    return Err(RuntimeError::InvalidOperation(
+                        "round expects a number, optional places and an optional rounding mode".to_string()
+                    ));
+                }
+                let places = match args.get(1) {
+                    None | Some(Value::Null) => 0,
+                    Some(places) => places.as_i64().and_then(|places| u32::try_from(places).ok()).ok_or_else(|| {
+                        RuntimeError::TypeError(format!("round() places must be a non-negative integer, got {}", places))
+                    })?,
+                };
+                let mode = match args.get(2) {
+                    None => self.decimal_context.rounding,
+                    Some(Value::String(name)) => name.parse::<RoundingMode>().map_err(RuntimeError::InvalidOperation)?,
+                    Some(other) => {
+                        return Err(RuntimeError::TypeError(format!("round() mode must be a string, got {}", other.type_name())))
+                    }
+                };
                 match &args[0] {
-                    Value::Number(n) => Ok(Value::Number(*n)),
-                    Value::String(s) => {
-                        match s.parse::<f64>() {
-                            Ok(n) => Ok(Value::Number(n)),
-                            Err(_) => Err(RuntimeError::InvalidOperation("Cannot convert string to number".to_string()))
-                        }
+                    Value::Integer(_) | Value::BigInt(_) => Ok(args[0].clone()),
+                    Value::Decimal(n) => Ok(Value::Decimal(n.round(places, mode))),
+                    Value::Number(n) => match Decimal::from_f64(*n) {
+                        Some(decimal) => Ok(Value::Number(decimal.round(places, mode).to_f64())),
+                        None => Ok(Value::Number(*n)),
+                    },
+                    other => Err(RuntimeError::TypeError(format!("round() requires a number, got {}", other.type_name()))),
+                }
+            }
+            "decimal_context" => {
+                let context = IndexMap::from([
+                    ("places".to_string(), Value::Integer(self.decimal_context.places as i64)),
+                    ("rounding".to_string(), Value::String(self.decimal_context.rounding.name().to_string().into())),
+                ]);
+                Ok(Value::Object(context))
+            }
+            "set_decimal_context" => {
+                let places = args[0].as_i64().and_then(|places| u32::try_from(places).ok()).ok_or_else(|| {
+                    RuntimeError::TypeError(format!("Decimal places must be a non-negative integer, got {}", args[0]))
+                })?;
+                let rounding = match &args[1] {
+                    Value::String(name) => name.parse::<RoundingMode>().map_err(RuntimeError::InvalidOperation)?,
+                    other => {
+                        return Err(RuntimeError::TypeError(format!("Rounding mode must be a string, got {}", other.type_name())))
                     }
-                    Value::Boolean(true) => Ok(Value::Number(1.0)),
-                    Value::Boolean(false) => Ok(Value::Number(0.0)),
-                    _ => Err(RuntimeError::TypeError("Cannot convert value to number".to_string()))
-                }
+                };
+                self.decimal_context = DecimalContext { places, rounding };
+                Ok(Value::Null)
             }
             // Math functions
             "abs" => {
                 match &args[0] {
+                    Value::Integer(n) => Ok(n.checked_abs().map_or_else(|| Value::BigInt(BigInt::from(*n).abs()), Value::Integer)),
+                    Value::BigInt(n) => Ok(Value::BigInt(n.abs())),
+                    Value::Decimal(n) => Ok(Value::Decimal(n.abs())),
                     Value::Number(n) => Ok(Value::Number(n.abs())),
                     _ => Err(RuntimeError::TypeError("abs() requires a number".to_string()))
                 }
             }
             "sqrt" => {
-                match &args[0] {
-                    Value::Number(n) => {
-                        if *n >= 0.0 {
+                match args[0].as_f64() {
+                    Some(n) => {
+                        if n >= 0.0 {
                             Ok(Value::Number(n.sqrt()))
                         } else {
                             Err(RuntimeError::InvalidOperation("sqrt() of negative number".to_string()))
@@ -728,31 +1465,30 @@ impl Interpreter {
                 }
             }
             "pow" => {
-                match (&args[0], &args[1]) {
-                    (Value::Number(base), Value::Number(exp)) => Ok(Value::Number(base.powf(*exp))),
+                match (args[0].as_f64(), args[1].as_f64()) {
+                    (Some(base), Some(exp)) => Ok(Value::Number(base.powf(exp))),
                     _ => Err(RuntimeError::TypeError("pow() requires two numbers".to_string()))
                 }
             }
             "sin" => {
-                match &args[0] {
-                    Value::Number(n) => Ok(Value::Number(n.sin())),
+                match args[0].as_f64() {
+                    Some(n) => Ok(Value::Number(n.sin())),
                     _ => Err(RuntimeError::TypeError("sin() requires a number".to_string()))
                 }
             }
             "cos" => {
-                match &args[0] {
-                    Value::Number(n) => Ok(Value::Number(n.cos())),
+                match args[0].as_f64() {
+                    Some(n) => Ok(Value::Number(n.cos())),
                     _ => Err(RuntimeError::TypeError("cos() requires a number".to_string()))
                 }
             }
             "random" => {
-                use std::collections::hash_map::DefaultHasher;
-                use std::hash::{Hash, Hasher};
-                use std::time::{SystemTime, UNIX_EPOCH};
+                use std::collections::hash_map::RandomState;
+                use std::hash::{BuildHasher, Hasher};
                 
-                let mut hasher = DefaultHasher::new();
-                SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos().hash(&mut hasher);
-                let hash = hasher.finish();
+                // Each RandomState gets fresh keys seeded by the OS, so this
+                // doesn't read the clock, which the sandbox may deny
+                let hash = RandomState::new().build_hasher().finish();
                 let random_val = (hash as f64) / (u64::MAX as f64);
                 Ok(Value::Number(random_val))
             }
@@ -761,48 +1497,101 @@ impl Interpreter {
                 if args.len() != 3 {
                     return Err(RuntimeError::InvalidOperation("substr() requires 3 arguments: string, start, length".to_string()));
                 }
-                match (&args[0], &args[1], &args[2]) {
-                    (Value::String(s), Value::Number(start), Value::Number(len)) => {
-                        let start = *start as usize;
-                        let len = *len as usize;
-                        let chars: Vec<char> = s.chars().collect();
-                        if start >= chars.len() {
-                            Ok(Value::String("".to_string()))
-                        } else {
-                            let end = std::cmp::min(start + len, chars.len());
-                            let substring: String = chars[start..end].iter().collect();
-                            Ok(Value::String(substring))
-                        }
+                match (&args[0], args[1].as_i64(), args[2].as_i64()) {
+                    (Value::String(s), Some(start), Some(len)) => {
+                        let start = usize::try_from(start).unwrap_or(0);
+                        let len = usize::try_from(len).unwrap_or(0);
+                        let count = s.grapheme_count();
+                        let start = std::cmp::min(start, count);
+                        let end = std::cmp::min(start.saturating_add(len), count);
+                        Ok(Value::String(s.graphemes(start, end).into()))
                     }
                     _ => Err(RuntimeError::TypeError("substr() requires string, number, number".to_string()))
                 }
             }
+            "slice" => {
+                if args.len() < 2 || args.len() > 3 {
+                    return Err(RuntimeError::InvalidOperation("slice() requires a value, a start and an optional end".to_string()));
+                }
+                match &args[0] {
+                    Value::Array(items) => {
+                        let (start, end) = numeric::slice_bounds(&args[1], args.get(2), items.len())?;
+                        Ok(Value::Array(items[start..end].to_vec()))
+                    }
+                    Value::Bytes(bytes) => {
+                        let (start, end) = numeric::slice_bounds(&args[1], args.get(2), bytes.len())?;
+                        Ok(Value::Bytes(bytes[start..end].to_vec()))
+                    }
+                    Value::String(s) => {
+                        let (start, end) = numeric::slice_bounds(&args[1], args.get(2), s.grapheme_count())?;
+                        Ok(Value::String(s.graphemes(start, end).into()))
+                    }
+                    other => Err(RuntimeError::TypeError(format!("slice() requires an array, string or bytes, got {}", other.type_name())))
+                }
+            }
             "upper" => {
                 match &args[0] {
-                    Value::String(s) => Ok(Value::String(s.to_uppercase())),
+                    Value::String(s) => Ok(Value::String(s.to_uppercase().into())),
                     _ => Err(RuntimeError::TypeError("upper() requires a string".to_string()))
                 }
             }
             "lower" => {
                 match &args[0] {
-                    Value::String(s) => Ok(Value::String(s.to_lowercase())),
+                    Value::String(s) => Ok(Value::String(s.to_lowercase().into())),
                     _ => Err(RuntimeError::TypeError("lower() requires a string".to_string()))
                 }
             }
             "trim" => {
                 match &args[0] {
-                    Value::String(s) => Ok(Value::String(s.trim().to_string())),
+                    Value::String(s) => Ok(Value::String(s.trim().to_string().into())),
                     _ => Err(RuntimeError::TypeError("trim() requires a string".to_string()))
                 }
             }
+            "chars" => {
+                match &args[0] {
+                    Value::String(s) => Ok(Value::Array(s.chars().map(|c| Value::String(c.into())).collect())),
+                    other => Err(RuntimeError::TypeError(format!("chars() requires a string, got {}", other.type_name())))
+                }
+            }
+            "casefold" => {
+                match &args[0] {
+                    Value::String(s) => Ok(Value::String(text::casefold(s).into())),
+                    other => Err(RuntimeError::TypeError(format!("casefold() requires a string, got {}", other.type_name())))
+                }
+            }
+            "normalize" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(RuntimeError::InvalidOperation("normalize() requires a string and an optional form".to_string()));
+                }
+                let form = match args.get(1) {
+                    None => "NFC",
+                    Some(Value::String(form)) => form.as_str(),
+                    Some(other) => return Err(RuntimeError::TypeError(format!("Normalization form must be a string, got {}", other.type_name()))),
+                };
+                match &args[0] {
+                    Value::String(s) => text::normalize(s, form)
+                        .map(|normalized| Value::String(normalized.into()))
+                        .map_err(RuntimeError::InvalidOperation),
+                    other => Err(RuntimeError::TypeError(format!("normalize() requires a string, got {}", other.type_name())))
+                }
+            }
+            "width" => {
+                match &args[0] {
+                    Value::String(s) => Ok(Value::Integer(s.width() as i64)),
+                    other => Err(RuntimeError::TypeError(format!("width() requires a string, got {}", other.type_name())))
+                }
+            }
             "split" => {
                 if args.len() != 2 {
                     return Err(RuntimeError::InvalidOperation("split() requires 2 arguments: string, delimiter".to_string()));
                 }
                 match (&args[0], &args[1]) {
+                    (Value::String(s), Value::String(delimiter)) if delimiter.is_empty() => {
+                        Ok(Value::Array(text::split_graphemes(s).map(|grapheme| Value::String(grapheme.into())).collect()))
+                    }
                     (Value::String(s), Value::String(delimiter)) => {
-                        let parts: Vec<Value> = s.split(delimiter)
-                            .map(|part| Value::String(part.to_string()))
+                        let parts: Vec<Value> = s.split(delimiter.as_str())
+                            .map(|part| Value::String(part.into()))
                             .collect();
                         Ok(Value::Array(parts))
                     }
@@ -818,7 +1607,7 @@ impl Interpreter {
                         let strings: Vec<String> = arr.iter()
                             .map(|val| val.to_string())
                             .collect();
-                        Ok(Value::String(strings.join(sep)))
+                        Ok(Value::String(strings.join(sep).into()))
                     }
                     _ => Err(RuntimeError::TypeError("join() requires array and string".to_string()))
                 }
@@ -831,25 +1620,12 @@ impl Interpreter {
                         reversed.reverse();
                         Ok(Value::Array(reversed))
                     }
-                    _ => Err(RuntimeError::TypeError("reverse() requires an array".to_string()))
-                }
-            }
-            "sort" => {
-                match &args[0] {
-                    Value::Array(arr) => {
-                        let mut sorted = arr.clone();
-                        sorted.sort_by(|a, b| {
-                            match (a, b) {
-                                (Value::Number(n1), Value::Number(n2)) => n1.partial_cmp(n2).unwrap_or(std::cmp::Ordering::Equal),
-                                (Value::String(s1), Value::String(s2)) => s1.cmp(s2),
-                                _ => std::cmp::Ordering::Equal,
-                            }
-                        });
-                        Ok(Value::Array(sorted))
-                    }
-                    _ => Err(RuntimeError::TypeError("sort() requires an array".to_string()))
+                    Value::String(s) => Ok(Value::String(text::reverse_graphemes(s).into())),
+                    _ => Err(RuntimeError::TypeError("reverse() requires an array or a string".to_string()))
                 }
             }
+            "map" | "filter" | "reduce" | "find" | "any" | "all" | "flat_map" | "zip" | "chunk" | "window" | "unique"
+            | "group_by" | "partition" | "sort" | "sort_by" | "sort_with" => self.call_array_function(name, args),
             "contains" => {
                 if args.len() != 2 {
                     return Err(RuntimeError::InvalidOperation("contains() requires 2 arguments".to_string()));
@@ -859,36 +1635,113 @@ impl Interpreter {
                         Ok(Value::Boolean(arr.contains(value)))
                     }
                     (Value::String(s), Value::String(substr)) => {
-                        Ok(Value::Boolean(s.contains(substr)))
+                        Ok(Value::Boolean(s.contains(substr.as_str())))
                     }
                     _ => Err(RuntimeError::TypeError("contains() requires array/value or string/string".to_string()))
                 }
             }
             // Time functions
             "now" => {
+                self.capabilities.check_clock().map_err(RuntimeError::PermissionDenied)?;
                 use std::time::{SystemTime, UNIX_EPOCH};
                 let timestamp = SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .unwrap()
-                    .as_secs() as f64;
-                Ok(Value::Number(timestamp))
+                    .as_secs() as i64;
+                Ok(Value::Integer(timestamp))
             }
             "sleep" => {
-                match &args[0] {
-                    Value::Number(seconds) => {
-                        std::thread::sleep(std::time::Duration::from_millis((*seconds * 1000.0) as u64));
-                        Ok(Value::Null)
+                self.capabilities.check_clock().map_err(RuntimeError::PermissionDenied)?;
+                match args[0].as_f64() {
+                    Some(seconds) => {
+                        let wake = std::time::Instant::now() + std::time::Duration::from_millis((seconds * 1000.0) as u64);
+                        // Sleep in short slices, never past the run's deadline,
+                        // so that a timeout or cancellation stops the wait
+                        loop {
+                            self.budget.check_interrupts(&self.cancel)?;
+                            let left = wake.saturating_duration_since(std::time::Instant::now());
+                            if left.is_zero() {
+                                break Ok(Value::Null);
+                            }
+                            let deadline = self.budget.time_left().unwrap_or(left);
+                            std::thread::sleep(left.min(limits::SLEEP_SLICE).min(deadline));
+                        }
                     }
                     _ => Err(RuntimeError::TypeError("sleep() requires a number (seconds)".to_string()))
                 }
             }
+            // Runtime introspection
+            "class_of" => {
+                match &args[0] {
+                    Value::Instance { class, .. } => Ok(class.as_ref().clone()),
+                    _ => Ok(Value::Null),
+                }
+            }
+            "superclass_of" => {
+                match &args[0] {
+                    Value::Class { superclass, .. } => {
+                        Ok(superclass.as_ref().map_or(Value::Null, |superclass| superclass.as_ref().clone()))
+                    }
+                    other => Err(RuntimeError::TypeError(format!("superclass_of() requires a class, got {}", other.type_name()))),
+                }
+            }
+            "bind" => {
+                if args.len() < 2 || !args[0].is_callable() {
+                    return Err(RuntimeError::TypeError("bind expects a function, a receiver and optional arguments".to_string()));
+                }
+                Ok(Value::BoundMethod {
+                    receiver: Box::new(args[1].clone()),
+                    method: Box::new(args[0].clone()),
+                    args: args[2..].to_vec(),
+                })
+            }
+            "call" => {
+                if args.len() < 2 || !args[0].is_callable() {
+                    return Err(RuntimeError::TypeError("call expects a function, a receiver and optional arguments".to_string()));
+                }
+                self.call_closure(&args[0], &args[2..], Some(args[1].clone()))
+            }
+            "apply" => {
+                match (&args[0], &args[2]) {
+                    (function, Value::Array(arguments)) if function.is_callable() => {
+                        self.call_closure(function, arguments, Some(args[1].clone()))
+                    }
+                    (function, Value::Null) if function.is_callable() => {
+                        self.call_closure(function, &[], Some(args[1].clone()))
+                    }
+                    _ => Err(RuntimeError::TypeError("apply expects a function, a receiver and an array of arguments".to_string())),
+                }
+            }
+            "implements" => {
+                let Value::Trait { name: trait_name, .. } = &args[1] else {
+                    return Err(RuntimeError::TypeError(format!("implements() requires a trait, got {}", args[1].type_name())));
+                };
+                match &args[0] {
+                    Value::Instance { class, .. } => Ok(Value::Boolean(class.implements(trait_name))),
+                    class @ Value::Class { .. } => Ok(Value::Boolean(class.implements(trait_name))),
+                    _ => Ok(Value::Boolean(false)),
+                }
+            }
+            "memory_usage" => {
+                let usage = self.memory_usage();
+                let mut report: IndexMap<String, Value> = [memory::STRING, memory::BYTES, memory::ARRAY, memory::OBJECT, memory::INSTANCE, memory::NUMBER, memory::ENVIRONMENT]
+                    .iter()
+                    .map(|category| (category.to_string(), Value::Integer(0)))
+                    .collect();
+                for (category, bytes) in &usage {
+                    report.insert(category.clone(), Value::Integer(*bytes as i64));
+                }
+                report.insert("total".to_string(), Value::Integer(usage.values().sum::<usize>() as i64));
+                Ok(Value::Object(report))
+            }
             // File I/O functions
             "read_file" => {
                 match &args[0] {
                     Value::String(filename) => {
+                        self.capabilities.check_read(filename).map_err(RuntimeError::PermissionDenied)?;
                         match std::fs::read_to_string(filename) {
-                            Ok(content) => Ok(Value::String(content)),
-                            Err(e) => Err(RuntimeError::InvalidOperation(format!("Cannot read file '{}': {}", filename, e)))
+                            Ok(content) => Ok(Value::String(content.into())),
+                            Err(e) => Err(RuntimeError::Io(format!("Cannot read file '{}': {}", filename, e)))
                         }
                     }
                     _ => Err(RuntimeError::TypeError("read_file() requires a filename string".to_string()))
@@ -900,17 +1753,45 @@ impl Interpreter {
                 }
                 match (&args[0], &args[1]) {
                     (Value::String(filename), Value::String(content)) => {
+                        self.capabilities.check_write(filename).map_err(RuntimeError::PermissionDenied)?;
                         match std::fs::write(filename, content) {
                             Ok(()) => Ok(Value::Null),
-                            Err(e) => Err(RuntimeError::InvalidOperation(format!("Cannot write file '{}': {}", filename, e)))
+                            Err(e) => Err(RuntimeError::Io(format!("Cannot write file '{}': {}", filename, e)))
                         }
                     }
                     _ => Err(RuntimeError::TypeError("write_file() requires filename and content strings".to_string()))
                 }
             }
+            "read_file_bytes" => {
+                match &args[0] {
+                    Value::String(filename) => {
+                        self.capabilities.check_read(filename).map_err(RuntimeError::PermissionDenied)?;
+                        match std::fs::read(filename) {
+                            Ok(content) => Ok(Value::Bytes(content)),
+                            Err(e) => Err(RuntimeError::Io(format!("Cannot read file '{}': {}", filename, e)))
+                        }
+                    }
+                    _ => Err(RuntimeError::TypeError("read_file_bytes() requires a filename string".to_string()))
+                }
+            }
+            "write_file_bytes" => {
+                match (&args[0], &args[1]) {
+                    (Value::String(filename), Value::Bytes(content)) => {
+                        self.capabilities.check_write(filename).map_err(RuntimeError::PermissionDenied)?;
+                        match std::fs::write(filename, content) {
+                            Ok(()) => Ok(Value::Null),
+                            Err(e) => Err(RuntimeError::Io(format!("Cannot write file '{}': {}", filename, e)))
+                        }
+                    }
+                    _ => Err(RuntimeError::TypeError("write_file_bytes() requires a filename string and bytes".to_string()))
+                }
+            }
             "exists" => {
                 match &args[0] {
-                    Value::String(path) => Ok(Value::Boolean(std::path::Path::new(path).exists())),
+                    Value::String(path) => {
+                        self.capabilities.check_read(path).map_err(RuntimeError::PermissionDenied)?;
+                        Ok(Value::Boolean(std::path::Path::new(path.as_str()).exists()))
+                    }
                     _ => Err(RuntimeError::TypeError("exists() requires a path string".to_string()))
                 }
             }
@@ -918,14 +1799,15 @@ impl Interpreter {
             "http_get" => {
                 match &args[0] {
                     Value::String(url) => {
-                        match reqwest::blocking::get(url) {
+                        self.capabilities.check_net(url).map_err(RuntimeError::PermissionDenied)?;
+                        match self.http_client()?.get(url.as_str()).send() {
                             Ok(response) => {
                                 match response.text() {
-                                    Ok(body) => Ok(Value::String(body)),
-                                    Err(e) => Err(RuntimeError::InvalidOperation(format!("HTTP GET failed: {}", e)))
+                                    Ok(body) => Ok(Value::String(body.into())),
+                                    Err(e) => Err(RuntimeError::Io(format!("HTTP GET failed: {}", e)))
                                 }
                             }
-                            Err(e) => Err(RuntimeError::InvalidOperation(format!("HTTP GET failed: {}", e)))
+                            Err(e) => Err(http_error("GET", e))
                         }
                     }
                     _ => Err(RuntimeError::TypeError("http_get() requires a URL string".to_string()))
@@ -937,15 +1819,15 @@ impl Interpreter {
                 }
                 match (&args[0], &args[1]) {
                     (Value::String(url), Value::String(data)) => {
-                        let client = reqwest::blocking::Client::new();
-                        match client.post(url).body(data.clone()).send() {
+                        self.capabilities.check_net(url).map_err(RuntimeError::PermissionDenied)?;
+                        match self.http_client()?.post(url.as_str()).body(data.to_string()).send() {
                             Ok(response) => {
                                 match response.text() {
-                                    Ok(body) => Ok(Value::String(body)),
-                                    Err(e) => Err(RuntimeError::InvalidOperation(format!("HTTP POST failed: {}", e)))
+                                    Ok(body) => Ok(Value::String(body.into())),
+                                    Err(e) => Err(RuntimeError::Io(format!("HTTP POST failed: {}", e)))
                                 }
                             }
-                            Err(e) => Err(RuntimeError::InvalidOperation(format!("HTTP POST failed: {}", e)))
+                            Err(e) => Err(http_error("POST", e))
                         }
                     }
                     _ => Err(RuntimeError::TypeError("http_post() requires URL and data strings".to_string()))
@@ -966,7 +1848,7 @@ impl Interpreter {
             "json_stringify" => {
                 let json_value = args[0].to_json();
                 match serde_json::to_string(&json_value) {
-                    Ok(json_str) => Ok(Value::String(json_str)),
+                    Ok(json_str) => Ok(Value::String(json_str.into())),
                     Err(e) => Err(RuntimeError::InvalidOperation(format!("JSON stringify error: {}", e)))
                 }
             }
@@ -995,10 +1877,10 @@ impl Interpreter {
                             Ok(re) => {
                                 match re.find(text) {
                                     Some(mat) => {
-                                        let mut result = std::collections::HashMap::new();
-                                        result.insert("match".to_string(), Value::String(mat.as_str().to_string()));
-                                        result.insert("start".to_string(), Value::Number(mat.start() as f64));
-                                        result.insert("end".to_string(), Value::Number(mat.end() as f64));
+                                        let mut result = IndexMap::new();
+                                        result.insert("match".to_string(), Value::String(mat.as_str().to_string().into()));
+                                        result.insert("start".to_string(), Value::Integer(mat.start() as i64));
+                                        result.insert("end".to_string(), Value::Integer(mat.end() as i64));
                                         Ok(Value::Object(result))
                                     }
                                     None => Ok(Value::Null)
@@ -1017,7 +1899,7 @@ impl Interpreter {
                 match (&args[0], &args[1], &args[2]) {
                     (Value::String(pattern), Value::String(text), Value::String(replacement)) => {
                         match regex::Regex::new(pattern) {
-                            Ok(re) => Ok(Value::String(re.replace_all(text, replacement.as_str()).to_string())),
+                            Ok(re) => Ok(Value::String(re.replace_all(text, replacement.as_str()).to_string().into())),
                             Err(e) => Err(RuntimeError::InvalidOperation(format!("Invalid regex pattern: {}", e)))
                         }
                     }
@@ -1033,7 +1915,7 @@ impl Interpreter {
                         match regex::Regex::new(pattern) {
                             Ok(re) => {
                                 let parts: Vec<Value> = re.split(text)
-                                    .map(|part| Value::String(part.to_string()))
+                                    .map(|part| Value::String(part.to_string().into()))
                                     .collect();
                                 Ok(Value::Array(parts))
                             }
@@ -1053,10 +1935,10 @@ impl Interpreter {
                             Ok(re) => {
                                 let matches: Vec<Value> = re.find_iter(text)
                                     .map(|mat| {
-                                        let mut result = std::collections::HashMap::new();
-                                        result.insert("match".to_string(), Value::String(mat.as_str().to_string()));
-                                        result.insert("start".to_string(), Value::Number(mat.start() as f64));
-                                        result.insert("end".to_string(), Value::Number(mat.end() as f64));
+                                        let mut result = IndexMap::new();
+                                        result.insert("match".to_string(), Value::String(mat.as_str().to_string().into()));
+                                        result.insert("start".to_string(), Value::Integer(mat.start() as i64));
+                                        result.insert("end".to_string(), Value::Integer(mat.end() as i64));
                                         Value::Object(result)
                                     })
                                     .collect();
@@ -1068,6 +1950,81 @@ impl Interpreter {
                     _ => Err(RuntimeError::TypeError("regex_find_all() requires two strings".to_string()))
                 }
             }
+            "format" => {
+                let template = match args.first() {
+                    Some(Value::String(template)) => template,
+                    _ => return Err(RuntimeError::TypeError("format() requires a template string".to_string())),
+                };
+                let values = &args[1..];
+                let mut result = String::new();
+                for piece in format::parse_template(template).map_err(RuntimeError::InvalidOperation)? {
+                    match piece {
+                        Piece::Text(text) => result.push_str(&text),
+                        Piece::Field { index, spec } => {
+                            let value = values.get(index).ok_or_else(|| RuntimeError::InvalidOperation(format!(
+                                "No argument {} for the format() template ({} given)", index, values.len()
+                            )))?;
+                            let text = self.stringify(value)?;
+                            let spec = FormatSpec::parse(&spec).map_err(RuntimeError::InvalidOperation)?;
+                            result.push_str(&spec.apply(value, &text, &self.decimal_context)?);
+                        }
+                    }
+                }
+                Ok(Value::String(result.into()))
+            }
+            // Binary data
+            "bytes" => {
+                match &args[0] {
+                    Value::Bytes(bytes) => Ok(Value::Bytes(bytes.clone())),
+                    Value::String(s) => Ok(Value::Bytes(s.as_bytes().to_vec())),
+                    Value::Array(items) => items.iter()
+                        .map(|item| item.as_i64().and_then(|n| u8::try_from(n).ok()).ok_or_else(|| {
+                            RuntimeError::InvalidOperation(format!("Bytes must be integers from 0 to 255, got {}", item))
+                        }))
+                        .collect::<RuntimeResult<Vec<u8>>>()
+                        .map(Value::Bytes),
+                    other => Err(RuntimeError::TypeError(format!("bytes() requires a string or an array of integers, got {}", other.type_name())))
+                }
+            }
+            "encode" | "decode" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(RuntimeError::InvalidOperation(format!("{}() requires a value and an optional encoding", name)));
+                }
+                let encoding_name = match args.get(1) {
+                    None => "utf-8",
+                    Some(Value::String(encoding_name)) => encoding_name.as_str(),
+                    Some(other) => return Err(RuntimeError::TypeError(format!("Encoding must be a string, got {}", other.type_name()))),
+                };
+                match (name, &args[0]) {
+                    ("encode", Value::String(s)) => encoding::encode(s, encoding_name).map(Value::Bytes),
+                    ("decode", Value::Bytes(bytes)) => encoding::decode(bytes, encoding_name).map(|text| Value::String(text.into())),
+                    ("encode", other) => return Err(RuntimeError::TypeError(format!("encode() requires a string, got {}", other.type_name()))),
+                    (_, other) => return Err(RuntimeError::TypeError(format!("decode() requires bytes, got {}", other.type_name()))),
+                }
+                .map_err(RuntimeError::InvalidOperation)
+            }
+            "base64_encode" => Ok(Value::String(Base64::encode(byte_data(&args[0], name)?).into())),
+            "hex_encode" => Ok(Value::String(Hex::encode(byte_data(&args[0], name)?).into())),
+            "base64_decode" | "hex_decode" => {
+                match &args[0] {
+                    Value::String(text) if name == "base64_decode" => Base64::decode(text),
+                    Value::String(text) => Hex::decode(text),
+                    other => return Err(RuntimeError::TypeError(format!("{}() requires a string, got {}", name, other.type_name()))),
+                }
+                .map(Value::Bytes)
+                .map_err(RuntimeError::InvalidOperation)
+            }
+            "hash" => {
+                if args.is_empty() || args.len() > 2 {
+                    return Err(RuntimeError::InvalidOperation("hash() requires data and an optional algorithm".to_string()));
+                }
+                let algorithm = match args.get(1) {
+                    None => HashAlgorithm::Sha256,
+                    Some(Value::String(algorithm)) => hash_algorithm(algorithm)?,
+                    Some(other) => return Err(RuntimeError::TypeError(format!("Hash algorithm must be a string, got {}", other.type_name()))),
+                };
+                Ok(Value::String(Crypto::hash_bytes(algorithm, byte_data(&args[0], name)?).into()))
+            }
             _ => Err(RuntimeError::InvalidOperation(format!("Unknown built-in function: {}", name)))
         }
     }
@@ -1080,52 +2037,68 @@ impl Interpreter {
             format!("{}.nova", module_path)
         };
 
-        // Check if file exists
-        if !std::path::Path::new(&module_file).exists() {
-            // Try standard library path
-            let std_path = format!("std/{}", module_file);
-            if !std::path::Path::new(&std_path).exists() {
-                return Err(RuntimeError::InvalidOperation(format!("Module not found: {}", module_path)));
+        // Look next to the script first, then in the standard library path.
+        // Only paths the script may read are probed, so a failed import
+        // can't reveal whether a file outside its roots exists.
+        let std_path = format!("std/{}", module_file);
+        let mut denied = None;
+        let mut readable = false;
+        let mut found = None;
+        for candidate in [module_file, std_path] {
+            if let Err(e) = self.capabilities.check_read(&candidate) {
+                denied.get_or_insert(e);
+            } else if std::path::Path::new(&candidate).exists() {
+                found = Some(candidate);
+                break;
+            } else {
+                readable = true;
             }
         }
+        let path = match (found, denied) {
+            (Some(path), _) => path,
+            (None, Some(e)) if !readable => return Err(RuntimeError::PermissionDenied(e)),
+            _ => return Err(RuntimeError::InvalidOperation(format!("Module not found: {}", module_path))),
+        };
 
         // Read and execute module
-        let module_content = match std::fs::read_to_string(&module_file) {
+        let module_content = match std::fs::read_to_string(&path) {
             Ok(content) => content,
-            Err(_) => {
-                // Try std path
-                let std_path = format!("std/{}", module_file);
-                match std::fs::read_to_string(&std_path) {
-                    Ok(content) => content,
-                    Err(e) => return Err(RuntimeError::InvalidOperation(format!("Cannot read module '{}': {}", module_path, e))),
-                }
-            }
+            Err(e) => return Err(RuntimeError::Io(format!("Cannot read module '{}': {}", module_path, e))),
         };
 
         // Parse and execute module
-        let mut lexer = crate::lexer::Lexer::new(&module_content);
-        let tokens = match lexer.tokenize() {
+        let mut lexer = crate::lexer::Lexer::new_with_file(&module_content, Some(path.clone()));
+        let (tokens, locations) = match lexer.tokenize_with_locations() {
             Ok(tokens) => tokens,
             Err(e) => return Err(RuntimeError::InvalidOperation(format!("Module parse error: {}", e))),
         };
 
-        let mut parser = crate::parser::Parser::new(tokens);
+        let mut parser = crate::parser::Parser::with_locations(tokens, locations);
         let program = match parser.parse() {
             Ok(program) => program,
             Err(e) => return Err(RuntimeError::InvalidOperation(format!("Module parse error: {:?}", e))),
         };
 
-        // Create new environment for module
+        // Create new environment for module. Natives, error classes and host
+        // functions live in a parent scope, so they aren't exported.
         let previous_env = self.environment.clone();
-        let mut module_env = crate::value::Environment::new();
-        module_env.define_natives();
-        self.environment = module_env;
+        self.suspended_environments.push(previous_env.clone());
+        let mut prelude = crate::value::Environment::new();
+        prelude.define_natives();
+        self.error_classes.define_in(&mut prelude);
+        for name in self.host_functions.keys() {
+            if let Some(function @ Value::NativeFunction { .. }) = self.globals.get(name) {
+                prelude.define(name.clone(), function);
+            }
+        }
+        self.environment = crate::value::Environment::with_parent(prelude);
 
         // Execute module
         let module_result = self.interpret(&program);
         let module_exports = self.environment.clone();
         
         // Restore previous environment
+        self.suspended_environments.pop();
         self.environment = previous_env;
 
         match module_result {
@@ -1133,12 +2106,14 @@ impl Interpreter {
                 // Import module exports into current environment
                 let module_name = match alias {
                     Some(alias_name) => alias_name.clone(),
-                    None => module_path.replace("/", "_").replace(".", "_"),
+                    None => module_path.replace(['/', '.'], "_"),
                 };
                 
                 // Create module object with all exported functions/variables
-                let mut module_obj = std::collections::HashMap::new();
-                for (name, value) in module_exports.get_all_variables() {
+                let mut module_obj = IndexMap::new();
+                let mut exports: Vec<(String, Value)> = module_exports.get_all_variables().into_iter().collect();
+                exports.sort_by(|a, b| a.0.cmp(&b.0));
+                for (name, value) in exports {
                     // Skip native functions, only import user-defined ones
                     match value {
                         crate::value::Value::NativeFunction { .. } => continue,
@@ -1155,7 +2130,15 @@ impl Interpreter {
         }
     }
 
-    fn define_class(&mut self, name: &str, superclass: Option<&String>, methods: &[Stmt]) -> RuntimeResult<Option<Value>> {
+    fn define_class(
+        &mut self,
+        name: &str,
+        superclass: Option<&String>,
+        methods: &[Stmt],
+        fields: &[FieldDecl],
+        private_methods: &[String],
+        traits: &[String],
+    ) -> RuntimeResult<Option<Value>> {
         // Handle superclass
         let superclass_value = if let Some(superclass_name) = superclass {
             match self.environment.get(superclass_name) {
@@ -1167,6 +2150,46 @@ impl Interpreter {
             None
         };
 
+        // Methods and initializers run in the class scope, where methods of
+        // derived classes see their superclass as `super`
+        let mut closure = Environment::with_parent(self.environment.clone());
+        if let Some(superclass) = &superclass_value {
+            closure.define("super".to_string(), superclass.as_ref().clone());
+        }
+
+        // Static fields are initialized once, now; instance fields for every new instance
+        let previous_env = std::mem::replace(&mut self.environment, closure.clone());
+        let static_fields: RuntimeResult<HashMap<String, Value>> = fields.iter()
+            .filter(|field| field.is_static)
+            .map(|field| {
+                let value = match &field.initializer {
+                    Some(initializer) => self.evaluate(initializer)?,
+                    None => Value::Null,
+                };
+                Ok((field.name.clone(), value))
+            })
+            .collect();
+        self.environment = previous_env;
+        let static_fields = static_fields?;
+
+        let instance_fields = fields.iter()
+            .filter(|field| !field.is_static)
+            .map(|field| {
+                let initializer = field.initializer.as_ref().map(|initializer| Value::Function {
+                    params: Vec::new(),
+                    body: initializer.clone(),
+                    closure: closure.clone(),
+                });
+                (field.name.clone(), initializer)
+            })
+            .collect();
+
+        let private_members: HashSet<String> = fields.iter()
+            .filter(|field| field.is_private)
+            .map(|field| field.name.clone())
+            .chain(private_methods.iter().cloned())
+            .collect();
+
         // Process methods
         let mut class_methods = HashMap::new();
         let mut static_methods = HashMap::new();
@@ -1174,11 +2197,11 @@ impl Interpreter {
 
         for method in methods {
             match method {
-                Stmt::Function { name: method_name, params, body } => {
+                Stmt::Function { name: method_name, params, body, .. } => {
                     let method_value = Value::Function {
                         params: params.clone(),
                         body: body.clone(),
-                        closure: self.environment.clone(),
+                        closure: closure.clone(),
                     };
 
                     if method_name.starts_with("static_") {
@@ -1194,52 +2217,114 @@ impl Interpreter {
             }
         }
 
+        let traits = traits.iter()
+            .map(|trait_name| match self.environment.get(trait_name) {
+                Some(value @ Value::Trait { .. }) => Ok(value),
+                Some(other) => Err(RuntimeError::TypeError(format!("{} is a {}, not a trait", trait_name, other.type_name()))),
+                None => Err(RuntimeError::UndefinedVariable(trait_name.clone())),
+            })
+            .collect::<RuntimeResult<Vec<_>>>()?;
+        self.apply_traits(name, superclass_value.as_deref(), &mut class_methods, &traits)?;
+
+        let static_fields = Rc::new(RefCell::new(static_fields));
+        gc::track_fields(&static_fields);
         let class = Value::Class {
             name: name.to_string(),
             superclass: superclass_value,
             methods: class_methods,
             static_methods,
             constructor,
+            fields: instance_fields,
+            static_fields,
+            private_members,
+            traits,
         };
 
+        closure.define(CLASS_SCOPE.to_string(), class.clone());
+        self.capture(&closure)?;
         self.environment.define(name.to_string(), class);
         Ok(None)
     }
 
+    fn define_trait(&mut self, name: &str, required: &[String], methods: &[Stmt]) -> RuntimeResult<Option<Value>> {
+        let environment = self.environment.clone();
+        self.capture(&environment)?;
+        let mut trait_methods = HashMap::new();
+        for method in methods {
+            if let Stmt::Function { name: method_name, params, body, .. } = method {
+                let method_value = Value::Function {
+                    params: params.clone(),
+                    body: body.clone(),
+                    closure: self.environment.clone(),
+                };
+                trait_methods.insert(method_name.clone(), method_value);
+            }
+        }
+
+        let value = Value::Trait {
+            name: name.to_string(),
+            required: required.to_vec(),
+            methods: trait_methods,
+        };
+        self.environment.define(name.to_string(), value);
+        Ok(None)
+    }
+
+    /// Copies the default methods of `traits` into `methods` where the class
+    /// neither defines nor inherits them, then checks that every required
+    /// method is there.
+    fn apply_traits(
+        &self,
+        class_name: &str,
+        superclass: Option<&Value>,
+        methods: &mut HashMap<String, Value>,
+        traits: &[Value],
+    ) -> RuntimeResult<()> {
+        let inherited = |method: &str| superclass.is_some_and(|parent| parent.find_method(method).is_some());
+        let own_methods: HashSet<String> = methods.keys().cloned().collect();
+
+        let mut provided_by: HashMap<&str, &str> = HashMap::new();
+        for implemented in traits {
+            let Value::Trait { name: trait_name, methods: defaults, .. } = implemented else { continue };
+            for (method, default) in defaults {
+                if own_methods.contains(method) || inherited(method) {
+                    continue;
+                }
+                if let Some(other) = provided_by.insert(method, trait_name) {
+                    return Err(RuntimeError::TypeError(format!(
+                        "Class {} gets conflicting default method '{}' from traits {} and {}; define it in the class",
+                        class_name, method, other, trait_name
+                    )));
+                }
+                methods.insert(method.clone(), default.clone());
+            }
+        }
+
+        for implemented in traits {
+            let Value::Trait { name: trait_name, required, .. } = implemented else { continue };
+            if let Some(missing) = required.iter().find(|method| !methods.contains_key(*method) && !inherited(method)) {
+                return Err(RuntimeError::TypeError(format!(
+                    "Class {} must implement '{}' required by trait {}",
+                    class_name, missing, trait_name
+                )));
+            }
+        }
+        Ok(())
+    }
+
     fn instantiate_class(&mut self, class: &Value, args: &[Value]) -> RuntimeResult<Value> {
         match class {
-            Value::Class { name, constructor, methods, .. } => {
-                // Create new instance
-                let mut instance = Value::Instance {
-                    class: Box::new(class.clone()),
-                    fields: HashMap::new(),
+            Value::Class { .. } => {
+                let fields = Rc::new(RefCell::new(HashMap::new()));
+                gc::track_fields(&fields);
+                let instance = Value::Instance {
+                    class: Rc::new(class.clone()),
+                    fields,
                 };
 
-                // Call constructor if it exists
-                if let Some(constructor_func) = constructor {
-                    // Set up constructor environment with 'this' binding
-                    let prev_env = self.environment.clone();
-                    let mut constructor_env = Environment::with_parent(self.environment.clone());
-                    constructor_env.define("this".to_string(), instance.clone());
-                    self.environment = constructor_env;
-                    
-                    // Call constructor
-                    let result = self.call_function(constructor_func.as_ref(), args);
-                    
-                    // Get updated instance from 'this' if it was modified
-                    if let Some(updated_this) = self.environment.get("this") {
-                        instance = updated_this;
-                    }
-                    
-                    // Restore environment
-                    self.environment = prev_env;
-                    
-                    // Check constructor result
-                    match result {
-                        Ok(_) => {}
-                        Err(RuntimeError::ReturnValue(_)) => {} // Constructor returns are ignored
-                        Err(e) => return Err(e),
-                    }
+                self.initialize_fields(&instance, class)?;
+                if let Some(constructor) = class.find_constructor() {
+                    self.run_constructor(&instance, &constructor, args)?;
                 }
 
                 Ok(instance)
@@ -1248,32 +2333,315 @@ impl Interpreter {
         }
     }
 
+    /// Sets the declared fields of `class` and its superclasses on a new
+    /// instance, superclass fields first.
+    fn initialize_fields(&mut self, instance: &Value, class: &Value) -> RuntimeResult<()> {
+        let Value::Class { superclass, fields, .. } = class else {
+            return Ok(());
+        };
+        if let Some(superclass) = superclass {
+            self.initialize_fields(instance, superclass)?;
+        }
+        for (name, initializer) in fields {
+            let value = match initializer {
+                Some(initializer) => self.call_closure(initializer, &[], Some(instance.clone()))?,
+                None => Value::Null,
+            };
+            instance.set_field(name.clone(), value).ok();
+            self.allocate(memory::INSTANCE, memory::field_size(name))?;
+        }
+        Ok(())
+    }
+
+    /// The getter and setter declared for `property` on an instance's class
+    /// or, for static accessors, on a class.
+    fn find_accessors(target: &Value, property: &str) -> (Option<Value>, Option<Value>) {
+        match target {
+            Value::Instance { class, .. } => {
+                (class.find_method(&getter_key(property)), class.find_method(&setter_key(property)))
+            }
+            Value::Class { .. } => {
+                (target.find_static_method(&getter_key(property)), target.find_static_method(&setter_key(property)))
+            }
+            _ => (None, None),
+        }
+    }
+
+    /// Reads `property` through its getter, if `target` has one.
+    fn call_getter(&mut self, target: &Value, property: &str) -> RuntimeResult<Option<Value>> {
+        match Self::find_accessors(target, property) {
+            (Some(getter), _) => self.call_closure(&getter, &[], Some(target.clone())).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Assigns `property` through its setter, if `target` has one, and
+    /// returns whether it did. Properties with only a getter are read-only.
+    fn call_setter(&mut self, target: &Value, property: &str, value: &Value) -> RuntimeResult<bool> {
+        match Self::find_accessors(target, property) {
+            (_, Some(setter)) => {
+                self.call_closure(&setter, std::slice::from_ref(value), Some(target.clone()))?;
+                Ok(true)
+            }
+            (Some(_), None) => Err(RuntimeError::TypeError(format!(
+                "Cannot assign to read-only property '{}'", property
+            ))),
+            (None, None) => Ok(false),
+        }
+    }
+
+    /// Private members are only accessible from inside the body of a class,
+    /// through `this` or through the class itself, where the target is that
+    /// class or one of its subclasses.
+    fn check_access(&self, object: &Expr, target: &Value, member: &str) -> RuntimeResult<()> {
+        let class = match target {
+            Value::Instance { class, .. } => class.as_ref(),
+            Value::Class { .. } => target,
+            _ => return Ok(()),
+        };
+        if !class.is_private_member(member) {
+            return Ok(());
+        }
+
+        let allowed = match self.environment.get(CLASS_SCOPE) {
+            Some(current) => {
+                (matches!(object, Expr::This) || matches!(target, Value::Class { .. })) && class.is_or_extends(&current)
+            }
+            None => false,
+        };
+        if allowed {
+            return Ok(());
+        }
+        let class_name = match class {
+            Value::Class { name, .. } => name.as_str(),
+            _ => "class",
+        };
+        Err(RuntimeError::TypeError(format!(
+            "Cannot access private member '{}' of {} outside the class", member, class_name
+        )))
+    }
+
+    /// Runs `constructor` with `this` bound to `instance`.
+    fn run_constructor(&mut self, instance: &Value, constructor: &Value, args: &[Value]) -> RuntimeResult<()> {
+        if let Value::NativeFunction { name, .. } = constructor {
+            if name == errors::CONSTRUCTOR {
+                errors::initialize(instance, args)?;
+                // Leave out the frames of the constructors creating the error
+                let stack = self.current_stack().frames().iter()
+                    .skip_while(|frame| frame.function.starts_with("new ") || frame.function.ends_with(".constructor"))
+                    .cloned()
+                    .collect();
+                instance.set_field("stack".to_string(), StackTrace::new(stack).to_value()).ok();
+                return self.charge(instance);
+            }
+        }
+
+        // Constructor return values are ignored
+        self.call_closure(constructor, args, Some(instance.clone()))?;
+        Ok(())
+    }
+
+    /// The method `name` of an instance's class or its superclasses.
+    fn find_method(&self, instance: &Value, name: &str) -> Option<Value> {
+        match instance {
+            Value::Instance { .. } => instance.get_method(name),
+            _ => None,
+        }
+    }
+
+    /// Calls the superclass constructor (`super(args)`, with `method` set to
+    /// `None`) or a superclass method (`super.method(args)`) on `this`.
+    /// `super` is bound in the scope of every method of a derived class.
+    fn call_super(&mut self, method: Option<&str>, args: &[Expr], location: &SourceLocation) -> RuntimeResult<Value> {
+        let outside_method = || RuntimeError::InvalidOperation("'super' used outside derived class method".to_string());
+        let superclass = self.environment.get("super").ok_or_else(outside_method)?;
+        let this = self.environment.get("this").ok_or_else(outside_method)?;
+        let Value::Class { name: class_name, .. } = &superclass else {
+            return Err(RuntimeError::TypeError("Superclass must be a class".to_string()));
+        };
+        let args = self.evaluate_args(args)?;
+
+        match method {
+            Some(method) => {
+                let function = superclass.find_method(method).ok_or_else(|| {
+                    RuntimeError::InvalidOperation(format!("Method '{}' not found in superclass {}", method, class_name))
+                })?;
+                self.with_frame(format!("{}.{}", class_name, method), location, |interpreter| {
+                    interpreter.call_closure(&function, &args, Some(this))
+                })
+            }
+            None => {
+                if let Some(constructor) = superclass.find_constructor() {
+                    self.with_frame(format!("{}.constructor", class_name), location, |interpreter| {
+                        interpreter.run_constructor(&this, &constructor, &args)
+                    })?;
+                }
+                Ok(Value::Null)
+            }
+        }
+    }
+
+    /// Stores `val` in the variable or property `target`.
+    fn assign(&mut self, target: &Expr, val: Value) -> RuntimeResult<Value> {
+        match target {
+            Expr::Identifier(name) => {
+                self.environment.set(name, val.clone())
+                    .map_err(RuntimeError::UndefinedVariable)?;
+                Ok(val)
+            }
+            Expr::Index { .. } => {
+                // TODO: Implement array/object assignment by index
+                Err(RuntimeError::InvalidOperation("Index assignment not yet implemented".to_string()))
+            }
+            Expr::Property { object, property } => {
+                let target_val = self.evaluate(object)?;
+                self.check_access(object, &target_val, property)?;
+                if self.call_setter(&target_val, property, &val)? {
+                    return Ok(val);
+                }
+                match target_val {
+                    Value::Instance { fields, .. } => {
+                        let previous = fields.borrow_mut().insert(property.clone(), val.clone());
+                        if previous.is_none() {
+                            self.allocate(memory::INSTANCE, memory::field_size(property))?;
+                        }
+                        Ok(val)
+                    }
+                    Value::Class { .. } => {
+                        if target_val.set_static_field(property, val.clone()) {
+                            self.allocate(memory::OBJECT, memory::field_size(property))?;
+                        }
+                        Ok(val)
+                    }
+                    Value::HostObject(host) => {
+                        host.borrow_mut().set(property, val.clone())?;
+                        Ok(val)
+                    }
+                    other => Err(RuntimeError::TypeError(
+                        format!("Cannot set property '{}' on {}", property, other.type_name())
+                    )),
+                }
+            }
+            _ => Err(RuntimeError::InvalidOperation("Invalid assignment target".to_string()))
+        }
+    }
+
+    /// Calls `object.method_name(args)`, where `object` is the value of
+    /// `object_expr`, and stores the receiver back if the method changed it.
+    fn call_method_on(&mut self, object_expr: &Expr, object: &Value, method_name: &str, args: &[Value]) -> RuntimeResult<Value> {
+        let result = self.call_method(object, method_name, args)?;
+        if matches!(object_expr, Expr::Identifier(_) | Expr::Property { .. }) {
+            if let Some(receiver) = methods::updated_receiver(object, method_name, &result) {
+                self.assign(object_expr, receiver)?;
+            }
+        }
+        Ok(result)
+    }
+
     fn call_method(&mut self, instance: &Value, method_name: &str, args: &[Value]) -> RuntimeResult<Value> {
         match instance {
             Value::Instance { class, .. } => {
-                if let Value::Class { methods, .. } = class.as_ref() {
-                    if let Some(method) = methods.get(method_name) {
-                        // Set up method environment with 'this' binding
-                        let prev_env = self.environment.clone();
-                        let mut method_env = Environment::with_parent(self.environment.clone());
-                        method_env.define("this".to_string(), instance.clone());
-                        self.environment = method_env;
-                        
-                        // Call method
-                        let result = self.call_function(method, args);
-                        
-                        // Restore environment
-                        self.environment = prev_env;
-                        
-                        result
-                    } else {
-                        Err(RuntimeError::InvalidOperation(format!("Method '{}' not found", method_name)))
-                    }
-                } else {
-                    Err(RuntimeError::TypeError("Invalid class structure".to_string()))
+                if !matches!(class.as_ref(), Value::Class { .. }) {
+                    return Err(RuntimeError::TypeError("Invalid class structure".to_string()));
+                }
+                match self.find_method(instance, method_name) {
+                    Some(method) => self.call_closure(&method, args, Some(instance.clone())),
+                    None => Err(RuntimeError::InvalidOperation(format!("Method '{}' not found", method_name))),
+                }
+            }
+            Value::Class { name, .. } => {
+                // Static methods run with `this` bound to the class
+                match instance.find_static_method(method_name) {
+                    Some(method) => self.call_closure(&method, args, Some(instance.clone())),
+                    None => Err(RuntimeError::InvalidOperation(
+                        format!("Static method '{}' not found on class {}", method_name, name)
+                    )),
                 }
             }
-            _ => Err(RuntimeError::TypeError(format!("Cannot call method '{}' on {}", method_name, instance.type_name()))),
+            Value::HostObject(host) => {
+                Ok(host.borrow_mut().call_method(method_name, args)?)
+            }
+            Value::Object(fields) if fields.contains_key(method_name) => {
+                self.call_function(&fields[method_name], args)
+            }
+            _ => match methods::builtin_method(instance, method_name) {
+                Some(method) => {
+                    let args: Vec<Value> = std::iter::once(instance.clone()).chain(args.iter().cloned()).collect();
+                    self.call_function(&method, &args)
+                }
+                None => Err(RuntimeError::TypeError(format!("Cannot call method '{}' on {}", method_name, instance.type_name()))),
+            },
+        }
+    }
+}
+
+/// The error for a failed HTTP `method` request: `PermissionDenied` if the
+/// sandbox refused a redirect, an I/O error otherwise.
+fn http_error(method: &str, error: reqwest::Error) -> RuntimeError {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(&error);
+    while let Some(cause) = source {
+        if let Some(RedirectDenied(denied)) = cause.downcast_ref::<RedirectDenied>() {
+            return RuntimeError::PermissionDenied(denied.clone());
         }
+        source = cause.source();
+    }
+    RuntimeError::Io(format!("HTTP {} failed: {}", method, error))
+}
+
+/// The bytes of a string or bytes argument to `function`.
+fn byte_data<'a>(value: &'a Value, function: &str) -> RuntimeResult<&'a [u8]> {
+    match value {
+        Value::Bytes(bytes) => Ok(bytes),
+        Value::String(s) => Ok(s.as_bytes()),
+        other => Err(RuntimeError::TypeError(format!("{}() requires bytes or a string, got {}", function, other.type_name()))),
     }
-}
\ No newline at end of file
+}
+
+fn hash_algorithm(name: &str) -> RuntimeResult<HashAlgorithm> {
+    match name {
+        "md5" => Ok(HashAlgorithm::Md5),
+        "sha1" => Ok(HashAlgorithm::Sha1),
+        "sha256" => Ok(HashAlgorithm::Sha256),
+        "sha512" => Ok(HashAlgorithm::Sha512),
+        _ => Err(RuntimeError::InvalidOperation(format!("Unknown hash algorithm '{}', expected one of: md5, sha1, sha256, sha512", name))),
+    }
+}
+
+/// The name a method call appears under in stack traces.
+fn method_frame_name(object: &Value, method: &str) -> String {
+    let class = match object {
+        Value::Instance { class, .. } => class.as_ref(),
+        other => other,
+    };
+    match class {
+        Value::Class { name, .. } => format!("{}.{}", name, method),
+        _ => method.to_string(),
+    }
+}
+
+/// Binds `method`, read off `receiver`, so calling it later still sees
+/// `receiver` as `this`.
+fn bind_method(receiver: &Value, method: Value) -> Value {
+    Value::BoundMethod {
+        receiver: Box::new(receiver.clone()),
+        method: Box::new(method),
+        args: Vec::new(),
+    }
+}
+
+/// The name a call of `callee` appears under in stack traces.
+fn function_frame_name(callee: &Expr) -> String {
+    match callee {
+        Expr::Identifier(name) => name.clone(),
+        _ => "<anonymous>".to_string(),
+    }
+}
+
+/// Whether `callee` is `super` or `super.method`.
+fn is_super_call(callee: &Expr) -> bool {
+    match callee {
+        Expr::Super => true,
+        Expr::Property { object, .. } => matches!(object.as_ref(), Expr::Super),
+        _ => false,
+    }
+}
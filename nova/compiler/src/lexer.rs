@@ -9,12 +9,12 @@ pub struct Lexer {
     line: usize,
     column: usize,
     file: Option<String>,
-    interpolation_stack: VecDeque<InterpolationContext>,
-}
-
-#[derive(Debug, Clone)]
-struct InterpolationContext {
-    brace_count: usize,
+    /// Where the token most recently returned by `next_token` starts.
+    token_start: SourceLocation,
+    /// Tokens already read, with their locations, waiting to be returned by
+    /// `next_token`. An interpolated string is read in one go but returned
+    /// one token at a time.
+    pending: VecDeque<(Token, SourceLocation)>,
 }
 
 impl Lexer {
@@ -32,8 +32,9 @@ impl Lexer {
             current_char,
             line: 1,
             column: 1,
+            token_start: SourceLocation::new(1, 1, file.clone()),
             file,
-            interpolation_stack: VecDeque::new(),
+            pending: VecDeque::new(),
         }
     }
 
@@ -123,83 +124,266 @@ impl Lexer {
         Ok(())
     }
 
-    fn read_number(&mut self) -> Result<f64, String> {
+    /// Reads a numeric literal: an integer unless it has a fraction or an
+    /// exponent, a big integer with an `n` suffix or a decimal with a `d`
+    /// suffix. Integers may be written in hexadecimal (`0xFF`), octal
+    /// (`0o755`) or binary (`0b1010`), and any run of digits may be split
+    /// with single underscores (`1_000_000`). Integers too large for 64 bits
+    /// become big integers.
+    fn read_number(&mut self) -> Result<Token, String> {
+        let radix = match (self.current_char, self.peek()) {
+            (Some('0'), Some('x' | 'X')) => Some((16, "hexadecimal")),
+            (Some('0'), Some('o' | 'O')) => Some((8, "octal")),
+            (Some('0'), Some('b' | 'B')) => Some((2, "binary")),
+            _ => None,
+        };
+        if let Some((radix, name)) = radix {
+            return self.read_radix_number(radix, name);
+        }
+
+        // `text` is the literal as written, for error messages
+        let mut text = String::new();
         let mut number = String::new();
-        let mut has_dot = false;
-        
-        while let Some(ch) = self.current_char {
-            if ch.is_ascii_digit() {
-                number.push(ch);
-                self.advance();
-            } else if ch == '.' && !has_dot && 
-                      self.peek().map_or(false, |c| c.is_ascii_digit()) {
-                has_dot = true;
-                number.push(ch);
-                self.advance();
-            } else if ch == '_' {
-                // Allow underscores in numbers for readability
+        self.read_digits(10, &mut text, &mut number)?;
+        let mut is_float = false;
+        if self.current_char == Some('.') && self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            is_float = true;
+            self.take_char(&mut text, &mut number);
+            self.read_digits(10, &mut text, &mut number)?;
+        }
+        if let Some('e' | 'E') = self.current_char {
+            is_float = true;
+            self.take_char(&mut text, &mut number);
+            if let Some('+' | '-') = self.current_char {
+                self.take_char(&mut text, &mut number);
+            }
+            if !self.current_char.is_some_and(|c| c.is_ascii_digit()) {
+                return Err(format!("Invalid number literal '{}': expected digits in the exponent", text));
+            }
+            self.read_digits(10, &mut text, &mut number)?;
+        }
+
+        let token = match self.current_char {
+            Some('n') if is_float => {
+                return Err(format!("Invalid number literal '{}n': big integers can't have a fraction or exponent", text))
+            }
+            Some('n') => {
+                self.take_char(&mut text, &mut String::new());
+                number.parse().map(Token::BigInt).map_err(|_| format!("Invalid number literal '{}'", text))?
+            }
+            Some('d') => {
+                self.take_char(&mut text, &mut String::new());
+                number.parse().map(Token::Decimal).map_err(|_| format!("Invalid number literal '{}'", text))?
+            }
+            _ if is_float => number.parse().map(Token::Number).map_err(|_| format!("Invalid number literal '{}'", text))?,
+            _ => match number.parse() {
+                Ok(n) => Token::Integer(n),
+                Err(_) => number.parse().map(Token::BigInt).map_err(|_| format!("Invalid number literal '{}'", text))?,
+            },
+        };
+        self.end_number(&text)?;
+        Ok(token)
+    }
+
+    /// Reads an integer literal after its `0x`, `0o` or `0b` prefix.
+    fn read_radix_number(&mut self, radix: u32, name: &str) -> Result<Token, String> {
+        let mut text = String::new();
+        self.take_char(&mut text, &mut String::new());
+        self.take_char(&mut text, &mut String::new());
+        if !self.current_char.is_some_and(|c| c.is_digit(radix)) {
+            return Err(format!("Invalid {} literal '{}': expected digits after the prefix", name, text));
+        }
+        let mut digits = String::new();
+        self.read_digits(radix, &mut text, &mut digits)?;
+        let suffix = self.current_char == Some('n');
+        if suffix {
+            self.take_char(&mut text, &mut String::new());
+        }
+        if let Some(c) = self.current_char.filter(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("Invalid digit '{}' in {} literal '{}'", c, name, text));
+        }
+        let value = num_bigint::BigInt::parse_bytes(digits.as_bytes(), radix)
+            .ok_or_else(|| format!("Invalid {} literal '{}'", name, text))?;
+        match i64::try_from(&value) {
+            Ok(n) if !suffix => Ok(Token::Integer(n)),
+            _ => Ok(Token::BigInt(value)),
+        }
+    }
+
+    /// Reads a run of digits in `radix`, separated by single underscores,
+    /// appending them to `text` as written and to `digits` without the
+    /// underscores.
+    fn read_digits(&mut self, radix: u32, text: &mut String, digits: &mut String) -> Result<(), String> {
+        while let Some(c) = self.current_char {
+            if c.is_digit(radix) {
+                self.take_char(text, digits);
+            } else if c == '_' {
+                text.push(c);
                 self.advance();
+                if !self.current_char.is_some_and(|c| c.is_digit(radix)) {
+                    return Err(format!(
+                        "Invalid number literal '{}{}': underscores must separate digits", text, self.rest_of_word()
+                    ));
+                }
             } else {
                 break;
             }
         }
-        
-        number.parse().map_err(|_| format!("Invalid number: {}", number))
+        Ok(())
+    }
+
+    /// The letters, digits and underscores from the current character on,
+    /// without consuming them.
+    fn rest_of_word(&self) -> String {
+        (0..)
+            .map_while(|offset| self.peek_ahead(offset))
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .collect()
+    }
+
+    fn take_char(&mut self, text: &mut String, digits: &mut String) {
+        if let Some(c) = self.current_char {
+            text.push(c);
+            digits.push(c);
+            self.advance();
+        }
+    }
+
+    /// Rejects letters and digits run into the end of a number, as in `12px`.
+    fn end_number(&self, text: &str) -> Result<(), String> {
+        match self.current_char {
+            Some(c) if c.is_alphanumeric() || c == '_' => {
+                Err(format!("Invalid character '{}' after number literal '{}'", c, text))
+            }
+            _ => Ok(()),
+        }
     }
 
     fn read_string(&mut self, quote: char) -> Result<String, String> {
-        let mut string = String::new();
         self.advance(); // Skip opening quote
-        
+        let mut body = String::new();
+
         while let Some(ch) = self.current_char {
             if ch == quote {
                 self.advance(); // Skip closing quote
+                return unescape(&body);
+            } else if ch == '\n' {
+                break;
+            }
+            body.push(ch);
+            self.advance();
+            if ch == '\\' {
+                // The escaped character can't end the string
+                match self.current_char {
+                    Some(c) => {
+                        body.push(c);
+                        self.advance();
+                    }
+                    None => break,
+                }
+            }
+        }
+
+        Err("Unterminated string literal".to_string())
+    }
+
+    /// Reads an `r"..."` string, in which backslashes have no special meaning.
+    fn read_raw_string(&mut self) -> Result<String, String> {
+        self.advance(); // Skip 'r'
+        let quote = self.current_char.unwrap_or('"');
+        self.advance(); // Skip opening quote
+        let mut string = String::new();
+
+        while let Some(ch) = self.current_char {
+            if ch == quote {
+                self.advance();
                 return Ok(string);
+            } else if ch == '\n' {
+                break;
+            }
+            string.push(ch);
+            self.advance();
+        }
+
+        Err("Unterminated raw string literal".to_string())
+    }
+
+    fn at_triple_quote(&self) -> bool {
+        self.current_char == Some('"') && self.peek() == Some('"') && self.peek_ahead(2) == Some('"')
+    }
+
+    /// Reads a `"""` string, which may span lines. Its layout is stripped
+    /// with `dedent` before escapes are processed, unless `raw`.
+    fn read_triple_quoted(&mut self, raw: bool) -> Result<String, String> {
+        for _ in 0..3 {
+            self.advance();
+        }
+        let mut body = String::new();
+
+        while let Some(ch) = self.current_char {
+            if self.at_triple_quote() {
+                for _ in 0..3 {
+                    self.advance();
+                }
+                let text = dedent(&body);
+                return if raw { Ok(text) } else { unescape(&text) };
+            }
+            body.push(ch);
+            self.advance();
+            if ch == '\\' && !raw {
+                if let Some(c) = self.current_char {
+                    body.push(c);
+                    self.advance();
+                }
+            }
+        }
+
+        Err("Unterminated triple-quoted string".to_string())
+    }
+
+    /// Reads a `b"..."` literal. Only ASCII may appear literally; other bytes
+    /// are written with `\\xNN` escapes.
+    fn read_bytes(&mut self) -> Result<Vec<u8>, String> {
+        self.advance(); // Skip 'b'
+        let quote = self.current_char.unwrap_or('"');
+        self.advance(); // Skip opening quote
+        let mut bytes = Vec::new();
+
+        while let Some(ch) = self.current_char {
+            if ch == quote {
+                self.advance();
+                return Ok(bytes);
             } else if ch == '\\' {
                 self.advance();
-                match self.current_char {
-                    Some('n') => string.push('\n'),
-                    Some('t') => string.push('\t'),
-                    Some('r') => string.push('\r'),
-                    Some('\\') => string.push('\\'),
-                    Some('"') => string.push('"'),
-                    Some('\'') => string.push('\''),
-                    Some('0') => string.push('\0'),
+                let byte = match self.current_char {
+                    Some('n') => b'\n',
+                    Some('t') => b'\t',
+                    Some('r') => b'\r',
+                    Some('\\') => b'\\',
+                    Some('"') => b'"',
+                    Some('\'') => b'\'',
+                    Some('0') => 0,
                     Some('x') => {
-                        // Hexadecimal escape sequence
-                        self.advance();
-                        let hex = self.read_hex_escape(2)?;
-                        if let Some(ch) = char::from_u32(hex) {
-                            string.push(ch);
-                        } else {
-                            return Err(format!("Invalid hex escape: \\x{:02x}", hex));
-                        }
-                        continue;
-                    }
-                    Some('u') => {
-                        // Unicode escape sequence
                         self.advance();
-                        let unicode = self.read_hex_escape(4)?;
-                        if let Some(ch) = char::from_u32(unicode) {
-                            string.push(ch);
-                        } else {
-                            return Err(format!("Invalid unicode escape: \\u{:04x}", unicode));
-                        }
+                        bytes.push(self.read_hex_escape(2)? as u8);
                         continue;
                     }
-                    Some(c) => string.push(c),
-                    None => return Err("Unterminated string literal".to_string()),
-                }
+                    Some(c) => return Err(format!("Invalid escape '\\{}' in bytes literal", c)),
+                    None => return Err("Unterminated bytes literal".to_string()),
+                };
+                bytes.push(byte);
                 self.advance();
             } else if ch == '\n' {
-                return Err("Unterminated string literal".to_string());
-            } else {
-                string.push(ch);
+                return Err("Unterminated bytes literal".to_string());
+            } else if ch.is_ascii() {
+                bytes.push(ch as u8);
                 self.advance();
+            } else {
+                return Err(format!("Non-ASCII character '{}' in bytes literal; use \\x escapes", ch));
             }
         }
-        
-        Err("Unterminated string literal".to_string())
+
+        Err("Unterminated bytes literal".to_string())
     }
 
     fn read_hex_escape(&mut self, digits: usize) -> Result<u32, String> {
@@ -216,82 +400,106 @@ impl Lexer {
         Ok(value)
     }
 
-    fn read_interpolated_string(&mut self) -> Result<Vec<Token>, String> {
-        let mut tokens = Vec::new();
-        let mut current_string = String::new();
-        
+    /// Reads an `f"..."` string into the tokens the parser expects:
+    /// `StringStart`, text as `StringMiddle`, each `${...}` as
+    /// `InterpolationStart`, the expression's tokens and `InterpolationEnd`,
+    /// and finally `StringEnd`.
+    fn read_interpolated_string(&mut self) -> Result<Vec<(Token, SourceLocation)>, String> {
+        let mut tokens = vec![(Token::StringStart, self.current_location())];
+        let mut text = String::new();
+        let mut text_start = self.current_location();
+
         // Skip 'f"'
         self.advance();
         self.advance();
-        
-        while let Some(ch) = self.current_char {
-            if ch == '"' {
-                // End of interpolated string
-                if !current_string.is_empty() {
-                    tokens.push(Token::StringEnd(current_string.clone()));
+
+        loop {
+            match self.current_char {
+                None | Some('\n') => return Err("Unterminated string literal".to_string()),
+                Some('"') => {
+                    self.advance();
+                    tokens.push((Token::StringEnd(unescape(&text)?), text_start));
+                    return Ok(tokens);
                 }
-                self.advance();
-                break;
-            } else if ch == '$' && self.peek() == Some('{') {
-                // Start of interpolation
-                if !current_string.is_empty() {
-                    tokens.push(Token::StringMiddle(current_string.clone()));
-                    current_string.clear();
-                }
-                
-                tokens.push(Token::InterpolationStart);
-                self.advance(); // Skip '$'
-                self.advance(); // Skip '{'
-                
-                // Parse the expression inside ${}
-                let mut brace_count = 1;
-                let mut expr_tokens = Vec::new();
-                
-                while brace_count > 0 && self.current_char.is_some() {
-                    let token = self.next_token()?;
-                    match &token {
-                        Token::LeftBrace => brace_count += 1,
-                        Token::RightBrace => {
-                            brace_count -= 1;
-                            if brace_count == 0 {
-                                tokens.push(Token::InterpolationEnd);
-                                continue;
-                            }
-                        }
-                        _ => {}
+                Some('$') if self.peek() == Some('{') => {
+                    if !text.is_empty() {
+                        tokens.push((Token::StringMiddle(unescape(&std::mem::take(&mut text))?), text_start));
                     }
-                    expr_tokens.push(token);
-                }
-                
-                tokens.extend(expr_tokens);
-            } else if ch == '\\' {
-                // Escape sequences
-                self.advance();
-                match self.current_char {
-                    Some('n') => current_string.push('\n'),
-                    Some('t') => current_string.push('\t'),
-                    Some('r') => current_string.push('\r'),
-                    Some('\\') => current_string.push('\\'),
-                    Some('"') => current_string.push('"'),
-                    Some('$') => current_string.push('$'),
-                    Some(c) => {
-                        current_string.push('\\');
-                        current_string.push(c);
+                    tokens.push((Token::InterpolationStart, self.current_location()));
+                    self.advance(); // Skip '$'
+                    self.advance(); // Skip '{'
+                    self.read_interpolation(&mut tokens)?;
+                    text_start = self.current_location();
+                }
+                Some('\\') => {
+                    // Kept for `unescape`; `\$` stops `${` starting an interpolation
+                    text.push('\\');
+                    self.advance();
+                    if let Some(c) = self.current_char {
+                        text.push(c);
+                        self.advance();
                     }
-                    None => return Err("Unterminated string literal".to_string()),
                 }
-                self.advance();
-            } else {
-                current_string.push(ch);
-                self.advance();
+                Some(ch) => {
+                    text.push(ch);
+                    self.advance();
+                }
             }
         }
-        
-        if tokens.is_empty() {
-            tokens.push(Token::StringEnd(current_string));
+    }
+
+    /// Reads the tokens of one interpolation, after its `${`, up to and
+    /// including the closing `InterpolationEnd`. A `:` outside any brackets
+    /// starts a format specifier, which runs to the closing `}`.
+    fn read_interpolation(&mut self, tokens: &mut Vec<(Token, SourceLocation)>) -> Result<(), String> {
+        // Brackets opened in the expression, and interpolated strings nested in it
+        let mut depth = 0usize;
+        let mut nested_strings = 0usize;
+
+        loop {
+            let token = self.next_token()?;
+            let location = self.token_start.clone();
+            match &token {
+                Token::Eof => return Err("Unterminated interpolation in string literal".to_string()),
+                Token::StringStart => nested_strings += 1,
+                Token::StringEnd(_) => nested_strings -= 1,
+                _ if nested_strings > 0 => {}
+                Token::LeftBrace | Token::LeftParen | Token::LeftBracket => depth += 1,
+                Token::RightBrace if depth == 0 => {
+                    tokens.push((Token::InterpolationEnd, location));
+                    return Ok(());
+                }
+                Token::RightBrace | Token::RightParen | Token::RightBracket => depth = depth.saturating_sub(1),
+                Token::Colon if depth == 0 => {
+                    let spec = self.read_format_spec()?;
+                    tokens.push((Token::FormatSpec(spec), location));
+                    tokens.push((Token::InterpolationEnd, self.token_start.clone()));
+                    return Ok(());
+                }
+                _ => {}
+            }
+            tokens.push((token, location));
+        }
+    }
+
+    /// Reads a format specifier up to and including the `}` that closes
+    /// its interpolation. Leaves `token_start` at the `}`.
+    fn read_format_spec(&mut self) -> Result<String, String> {
+        let mut spec = String::new();
+        loop {
+            match self.current_char {
+                Some('}') => {
+                    self.token_start = self.current_location();
+                    self.advance();
+                    return Ok(spec);
+                }
+                None | Some('\n') | Some('"') => return Err("Unterminated format specifier in string literal".to_string()),
+                Some(ch) => {
+                    spec.push(ch);
+                    self.advance();
+                }
+            }
         }
-        
-        Ok(tokens)
     }
 
     fn read_identifier(&mut self) -> String {
@@ -310,7 +518,12 @@ impl Lexer {
     }
 
     pub fn next_token(&mut self) -> Result<Token, String> {
+        if let Some((token, location)) = self.pending.pop_front() {
+            self.token_start = location;
+            return Ok(token);
+        }
         loop {
+            self.token_start = self.current_location();
             match self.current_char {
                 None => return Ok(Token::Eof),
                 Some(' ') | Some('\t') | Some('\r') => {
@@ -408,6 +621,10 @@ impl Lexer {
                 }
                 Some('~') => {
                     self.advance();
+                    if self.current_char == Some('/') {
+                        self.advance();
+                        return Ok(Token::TildeSlash);
+                    }
                     return Ok(Token::Tilde);
                 }
                 Some('<') => {
@@ -524,6 +741,9 @@ impl Lexer {
                     self.advance();
                     return Ok(Token::Dot);
                 }
+                Some('"') if self.at_triple_quote() => {
+                    return Ok(Token::String(self.read_triple_quoted(false)?));
+                }
                 Some('"') => {
                     let string = self.read_string('"')?;
                     return Ok(Token::String(string));
@@ -532,24 +752,30 @@ impl Lexer {
                     let string = self.read_string('\'')?;
                     return Ok(Token::String(string));
                 }
+                Some('b') if matches!(self.peek(), Some('"' | '\'')) => {
+                    return Ok(Token::Bytes(self.read_bytes()?));
+                }
+                Some('r') if self.peek() == Some('"') && self.peek_ahead(2) == Some('"') && self.peek_ahead(3) == Some('"') => {
+                    self.advance(); // Skip 'r'
+                    return Ok(Token::String(self.read_triple_quoted(true)?));
+                }
+                Some('r') if matches!(self.peek(), Some('"' | '\'')) => {
+                    return Ok(Token::String(self.read_raw_string()?));
+                }
                 Some('f') if self.peek() == Some('"') => {
-                    // Interpolated string
-                    let tokens = self.read_interpolated_string()?;
-                    // For now, return the first token and handle the rest later
-                    if let Some(token) = tokens.first() {
-                        return Ok(token.clone());
-                    } else {
-                        return Ok(Token::String(String::new()));
-                    }
+                    let mut tokens = self.read_interpolated_string()?.into_iter();
+                    let (token, location) = tokens.next().expect("interpolated strings start with StringStart");
+                    self.pending.extend(tokens);
+                    self.token_start = location;
+                    return Ok(token);
                 }
                 Some(ch) if ch.is_ascii_digit() => {
-                    let number = self.read_number()?;
-                    return Ok(Token::Number(number));
+                    return self.read_number();
                 }
                 Some(ch) if ch.is_alphabetic() || ch == '_' => {
                     let identifier = self.read_identifier();
                     return Ok(Token::is_keyword(&identifier)
-                        .unwrap_or_else(|| Token::Identifier(identifier)));
+                        .unwrap_or(Token::Identifier(identifier)));
                 }
                 Some(ch) => {
                     return Err(format!("Unexpected character: '{}'", ch));
@@ -559,20 +785,99 @@ impl Lexer {
     }
 
     pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
+        self.tokenize_with_locations().map(|(tokens, _)| tokens)
+    }
+
+    /// Like `tokenize`, but also returns where each token starts, for
+    /// `Parser::with_locations`.
+    pub fn tokenize_with_locations(&mut self) -> Result<(Vec<Token>, Vec<SourceLocation>), String> {
         let mut tokens = Vec::new();
+        let mut locations = Vec::new();
         
         loop {
             let token = self.next_token()?;
             if token == Token::Eof {
                 tokens.push(token);
+                locations.push(self.token_start.clone());
                 break;
             }
             // Skip newlines in tokenize mode for simplicity
             if !matches!(token, Token::Newline) {
                 tokens.push(token);
+                locations.push(self.token_start.clone());
             }
         }
         
-        Ok(tokens)
+        Ok((tokens, locations))
+    }
+}
+
+/// Replaces the escape sequences in the body of a string literal.
+fn unescape(text: &str) -> Result<String, String> {
+    let mut string = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            string.push(ch);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => string.push('\n'),
+            Some('t') => string.push('\t'),
+            Some('r') => string.push('\r'),
+            Some('0') => string.push('\0'),
+            Some('x') => {
+                let hex = hex_escape(&mut chars, 2)?;
+                string.push(char::from_u32(hex).ok_or_else(|| format!("Invalid hex escape: \\x{:02x}", hex))?);
+            }
+            Some('u') => {
+                let unicode = hex_escape(&mut chars, 4)?;
+                string.push(char::from_u32(unicode).ok_or_else(|| format!("Invalid unicode escape: \\u{:04x}", unicode))?);
+            }
+            // `\\`, `\"`, `\'`, `\$` and any other character stand for themselves
+            Some(c) => string.push(c),
+            None => return Err("Unterminated string literal".to_string()),
+        }
+    }
+
+    Ok(string)
+}
+
+fn hex_escape(chars: &mut std::str::Chars, digits: usize) -> Result<u32, String> {
+    let mut value = 0;
+    for _ in 0..digits {
+        match chars.next().and_then(|c| c.to_digit(16)) {
+            Some(digit) => value = value * 16 + digit,
+            None => return Err("Invalid hex escape sequence".to_string()),
+        }
     }
-}
\ No newline at end of file
+    Ok(value)
+}
+
+/// Strips the layout of a triple-quoted string: a line break straight after
+/// the opening quotes, the line holding the closing quotes if nothing else
+/// is on it, and the indentation shared by the closing line and every
+/// non-blank line.
+fn dedent(body: &str) -> String {
+    let body = body.strip_prefix("\r\n").or_else(|| body.strip_prefix('\n')).unwrap_or(body);
+    let is_blank = |line: &str| line.trim_matches([' ', '\t', '\r']).is_empty();
+    let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+
+    let mut lines: Vec<&str> = body.split('\n').collect();
+    let closing_indent = match lines.last() {
+        Some(last) if lines.len() > 1 && is_blank(last) => lines.pop().map(indentation),
+        _ => None,
+    };
+    let indent = lines.iter()
+        .filter(|line| !is_blank(line))
+        .map(|line| indentation(line))
+        .chain(closing_indent)
+        .min()
+        .unwrap_or(0);
+
+    lines.iter()
+        .map(|line| line.get(indent..).unwrap_or("").trim_end_matches('\r'))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
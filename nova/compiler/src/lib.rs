@@ -3,8 +3,25 @@ pub mod token;
 pub mod lexer;
 pub mod parser;
 pub mod value;
+pub mod decimal;
+pub mod text;
 pub mod interpreter;
+pub mod errors;
+pub mod trace;
+pub mod embed;
+pub mod value_serde;
+pub mod sandbox;
+pub mod limits;
+pub mod memory;
+mod protocols;
+mod numeric;
+mod encoding;
+mod format;
+mod methods;
+mod arrays;
+mod gc;
 pub mod repl;
+pub mod checker;
 
 #[cfg(test)]
 mod tests;
@@ -14,5 +31,15 @@ pub use token::*;
 pub use lexer::*;
 pub use parser::*;
 pub use value::*;
+pub use decimal::*;
+pub use text::NovaString;
 pub use interpreter::*;
-pub use repl::*;
\ No newline at end of file
+pub use errors::ErrorClasses;
+pub use trace::*;
+pub use embed::*;
+pub use value_serde::*;
+pub use sandbox::*;
+pub use limits::*;
+pub use memory::MemoryUsage;
+pub use repl::*;
+pub use checker::*;
\ No newline at end of file
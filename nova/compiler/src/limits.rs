@@ -0,0 +1,172 @@
+//! Execution limits that stop runaway scripts. Fuel, the wall-clock
+//! timeout and the cancellation flag are checked while the interpreter
+//! evaluates expressions; the call depth is checked on every function call.
+//! A limit that trips aborts the whole run with its own `RuntimeError`
+//! variant, which scripts cannot catch, and the interpreter can be used
+//! again afterwards. The memory limit is accounted for separately; see
+//! `crate::memory`.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionLimits {
+    fuel: Option<u64>,
+    timeout: Option<Duration>,
+    max_call_depth: Option<usize>,
+    max_memory: Option<usize>,
+}
+
+impl ExecutionLimits {
+    /// No limits; this is what `Interpreter::new` uses.
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// Allows each run to evaluate at most `fuel` expressions. Every loop
+    /// iteration evaluates at least one expression, so empty loops burn fuel too.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    /// Stops each run once it has been executing for `timeout`.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Limits how many Nova function calls may be nested.
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = Some(depth);
+        self
+    }
+
+    /// Limits the bytes held by strings, arrays, objects and instances.
+    /// Unlike the other limits this one spans runs, since globals outlive
+    /// them, and running out of memory is a catchable error.
+    pub fn with_max_memory(mut self, bytes: usize) -> Self {
+        self.max_memory = Some(bytes);
+        self
+    }
+
+    pub fn fuel(&self) -> Option<u64> {
+        self.fuel
+    }
+
+    pub fn timeout(&self) -> Option<Duration> {
+        self.timeout
+    }
+
+    pub fn max_call_depth(&self) -> Option<usize> {
+        self.max_call_depth
+    }
+
+    pub fn max_memory(&self) -> Option<usize> {
+        self.max_memory
+    }
+}
+
+/// Thread-safe handle that stops the interpreter it was taken from.
+///
+/// Cancelling while a run is in progress aborts it with
+/// `RuntimeError::Cancelled`, including a run blocked in `sleep`. The
+/// request is consumed once it has been honoured, and one that arrives
+/// while nothing is running is discarded when the next run starts.
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Clears a pending cancellation, returning whether there was one.
+    pub(crate) fn take(&self) -> bool {
+        self.0.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// Per-run bookkeeping for `ExecutionLimits`.
+#[derive(Debug, Default)]
+pub(crate) struct Budget {
+    fuel_left: Option<u64>,
+    deadline: Option<Instant>,
+    ticks: u32,
+    /// Set once the clock or the cancellation flag stopped the run, so that
+    /// `finally` blocks running during unwinding cannot keep it going.
+    tripped: Option<LimitExceeded>,
+    pub(crate) call_depth: usize,
+    /// Number of nested `Interpreter::run_limited` calls; the budget is only
+    /// refilled when the outermost one starts.
+    pub(crate) active_runs: usize,
+}
+
+/// How many evaluation steps pass between checks of the clock and the
+/// cancellation flag, which are much more expensive than counting fuel.
+const CHECK_INTERVAL: u32 = 1024;
+
+/// How long `sleep` waits between checks of the deadline and the
+/// cancellation flag.
+pub(crate) const SLEEP_SLICE: Duration = Duration::from_millis(10);
+
+/// Why a run was stopped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum LimitExceeded {
+    Fuel,
+    Timeout,
+    Cancelled,
+}
+
+impl Budget {
+    pub(crate) fn refill(&mut self, limits: &ExecutionLimits) {
+        self.fuel_left = limits.fuel;
+        self.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.ticks = 0;
+        self.tripped = None;
+        self.call_depth = 0;
+    }
+
+    /// Time until the deadline, if the run has one.
+    pub(crate) fn time_left(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// Accounts for one evaluation step.
+    pub(crate) fn tick(&mut self, cancel: &CancelHandle) -> Result<(), LimitExceeded> {
+        if let Some(reason) = self.tripped {
+            return Err(reason);
+        }
+        if let Some(fuel) = &mut self.fuel_left {
+            if *fuel == 0 {
+                return Err(LimitExceeded::Fuel);
+            }
+            *fuel -= 1;
+        }
+
+        self.ticks += 1;
+        if self.ticks >= CHECK_INTERVAL {
+            self.ticks = 0;
+            self.check_interrupts(cancel)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn check_interrupts(&mut self, cancel: &CancelHandle) -> Result<(), LimitExceeded> {
+        if cancel.take() {
+            self.tripped = Some(LimitExceeded::Cancelled);
+        } else if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            self.tripped = Some(LimitExceeded::Timeout);
+        }
+        self.tripped.map_or(Ok(()), Err)
+    }
+}
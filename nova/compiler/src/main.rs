@@ -1,52 +1,151 @@
-use nova_compiler::{Lexer, Parser, Interpreter, Repl};
+use nova_compiler::{Capabilities, Lexer, Parser, Interpreter, Program, Repl, TypeChecker};
 use std::env;
 use std::fs;
 use std::io;
 
+const USAGE: &str = "Usage: nova [options] [script.nova]
+       nova check script.nova...  Check type annotations without running the scripts
+
+Permissions (scripts are unrestricted unless one of these is given):
+  --sandbox               Deny all capabilities that are not explicitly granted
+  --allow-read[=PATHS]    Allow reading files below PATHS (comma-separated; all if omitted)
+  --allow-write[=PATHS]   Allow reading and writing files below PATHS
+  --allow-net[=HOSTS]     Allow HTTP requests to HOSTS, e.g. api.example.com,*.internal
+  --allow-stdin           Allow input()
+  --allow-clock           Allow now() and sleep()
+  --allow-all             Grant every capability
+
+Limits:
+  --recursion-limit=N     Raise a RecursionError when calls nest deeper than N (default 3000)";
+
 fn main() -> io::Result<()> {
     let args: Vec<String> = env::args().collect();
-    
-    if args.len() == 1 {
-        let mut repl = Repl::new();
-        repl.run()
-    } else if args.len() == 2 {
-        let filename = &args[1];
-        run_file(filename)
-    } else {
-        eprintln!("Usage: {} [script.nova]", args[0]);
+    if args.get(1).map(String::as_str) == Some("check") {
+        if args.len() < 3 {
+            usage_error("No scripts to check");
+        }
+        check_files(&args[2..]);
+    }
+
+    let mut sandbox: Option<Capabilities> = None;
+    let mut recursion_limit = None;
+    let mut script = None;
+    for arg in &args[1..] {
+        if arg == "--help" || arg == "-h" {
+            println!("{}", USAGE);
+            return Ok(());
+        } else if let Some(limit) = arg.strip_prefix("--recursion-limit=") {
+            match limit.parse::<usize>() {
+                Ok(limit) => recursion_limit = Some(limit),
+                Err(_) => usage_error(&format!("Invalid recursion limit: {}", limit)),
+            }
+        } else if arg.starts_with("--") {
+            match parse_permission(arg, sandbox.take().unwrap_or_else(Capabilities::none)) {
+                Some(capabilities) => sandbox = Some(capabilities),
+                None => usage_error(&format!("Unknown option: {}", arg)),
+            }
+        } else if script.is_none() {
+            script = Some(arg.clone());
+        } else {
+            usage_error("Only one script can be run at a time");
+        }
+    }
+
+    let mut interpreter = Interpreter::with_capabilities(sandbox.unwrap_or_default());
+    if let Some(limit) = recursion_limit {
+        interpreter.set_recursion_limit(limit);
+    }
+    match script {
+        Some(filename) => run_file(&filename, interpreter),
+        None => Repl::with_interpreter(interpreter).run(),
+    }
+}
+
+/// Applies one `--allow-*`/`--sandbox` flag, or returns `None` for unknown flags.
+fn parse_permission(arg: &str, capabilities: Capabilities) -> Option<Capabilities> {
+    let (flag, value) = match arg.split_once('=') {
+        Some((flag, value)) => (flag, Some(value)),
+        None => (arg, None),
+    };
+    let values = || value.map(|v| v.split(',').filter(|v| !v.is_empty()).collect::<Vec<_>>());
+
+    let capabilities = match (flag, values()) {
+        ("--sandbox", None) => capabilities,
+        ("--allow-all", None) => Capabilities::all(),
+        ("--allow-stdin", None) => capabilities.allow_stdin(),
+        ("--allow-clock", None) => capabilities.allow_clock(),
+        ("--allow-read", None) => capabilities.allow_read("/"),
+        ("--allow-read", Some(paths)) => paths.into_iter().fold(capabilities, |c, path| c.allow_read(path)),
+        ("--allow-write", None) => capabilities.allow_write("/"),
+        ("--allow-write", Some(paths)) => paths.into_iter().fold(capabilities, |c, path| c.allow_write(path)),
+        ("--allow-net", None) => capabilities.allow_net("*"),
+        ("--allow-net", Some(hosts)) => hosts.into_iter().fold(capabilities, |c, host| c.allow_net(host)),
+        _ => return None,
+    };
+    Some(capabilities)
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    std::process::exit(1);
+}
+
+/// Type-checks each script and exits, with status 1 if any has problems.
+fn check_files(filenames: &[String]) -> ! {
+    let mut problems = 0;
+    for filename in filenames {
+        let program = match fs::read_to_string(filename) {
+            Ok(source) => parse_file(filename, &source),
+            Err(e) => {
+                eprintln!("{}: {}", filename, e);
+                std::process::exit(1);
+            }
+        };
+        for diagnostic in TypeChecker::new().check(&program) {
+            eprintln!("Type Error: {}", diagnostic);
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        eprintln!("{} problem{} found", problems, if problems == 1 { "" } else { "s" });
         std::process::exit(1);
     }
+    std::process::exit(0);
 }
 
-fn run_file(filename: &str) -> io::Result<()> {
-    let source = fs::read_to_string(filename)?;
-    
-    let mut lexer = Lexer::new(&source);
-    let tokens = match lexer.tokenize() {
+/// Parses a script, exiting with an error message if it is malformed.
+fn parse_file(filename: &str, source: &str) -> Program {
+    let mut lexer = Lexer::new_with_file(source, Some(filename.to_string()));
+    let (tokens, locations) = match lexer.tokenize_with_locations() {
         Ok(tokens) => tokens,
         Err(e) => {
             eprintln!("Lexer Error: {}", e);
             std::process::exit(1);
         }
     };
-    
-    let mut parser = Parser::new(tokens);
+
+    let mut parser = Parser::with_locations(tokens, locations);
     match parser.parse() {
-        Ok(program) => {
-            let mut interpreter = Interpreter::new();
-            match interpreter.interpret(&program) {
-                Ok(_) => {}
-                Err(e) => {
-                    eprintln!("Runtime Error: {}", e);
-                    std::process::exit(1);
-                }
-            }
-        }
+        Ok(program) => program,
         Err(e) => {
             eprintln!("Parse Error: {}", e);
             std::process::exit(1);
         }
     }
-    
+}
+
+fn run_file(filename: &str, mut interpreter: Interpreter) -> io::Result<()> {
+    let source = fs::read_to_string(filename)?;
+    let program = parse_file(filename, &source);
+
+    if let Err(e) = interpreter.interpret(&program) {
+        eprintln!("Runtime Error: {}", e);
+        if let Some(trace) = interpreter.stack_trace() {
+            eprint!("{}", trace);
+        }
+        std::process::exit(1);
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,135 @@
+//! Estimates how many bytes script values occupy, for the accounting done
+//! by `nova_runtime::MemoryManager`.
+//!
+//! New strings, arrays, objects and instances are charged to the manager
+//! when they are created, as are scopes the first time a function captures
+//! them and the grapheme tables strings build when they are indexed. When a
+//! charge would exceed the limit, the interpreter frees unreachable cycles,
+//! measures everything still reachable from its scopes and retries with
+//! those numbers, much like a garbage collector would before giving up. Values held only by the interpreter's own Rust frames (such as
+//! arguments being evaluated) are not seen by that measurement, so it is an
+//! approximation that errs on the side of allowing slightly more.
+
+use crate::value::{Environment, Scope, Value};
+use std::collections::{BTreeMap, HashSet};
+use std::mem::size_of;
+use std::rc::Rc;
+
+pub const STRING: &str = "string";
+pub const BYTES: &str = "bytes";
+pub const ARRAY: &str = "array";
+pub const OBJECT: &str = "object";
+pub const INSTANCE: &str = "instance";
+/// Big integers and decimals, whose digits live on the heap.
+pub const NUMBER: &str = "number";
+/// Scopes kept alive by the functions that captured them.
+pub const ENVIRONMENT: &str = "environment";
+
+/// Bytes used per category of value.
+pub type MemoryUsage = BTreeMap<String, usize>;
+
+/// The bytes one entry of an object or instance takes.
+pub fn field_size(key: &str) -> usize {
+    key.len() + size_of::<String>() + size_of::<Value>()
+}
+
+fn fields_size<'a>(keys: impl Iterator<Item = &'a String>) -> usize {
+    keys.map(|key| field_size(key)).sum()
+}
+
+/// The bytes a scope owns itself, not counting the values in it.
+pub fn scope_size(environment: &Environment) -> usize {
+    size_of::<Scope>() + fields_size(environment.scope().borrow().vars.keys())
+}
+
+/// The bytes `value` owns itself, not counting values nested inside it.
+pub fn shallow_size(value: &Value) -> Option<(&'static str, usize)> {
+    match value {
+        Value::String(s) => Some((STRING, s.len() + s.table_size())),
+        Value::Bytes(b) => Some((BYTES, b.len())),
+        Value::BigInt(n) => Some((NUMBER, n.bits().div_ceil(8) as usize)),
+        Value::Decimal(n) => Some((NUMBER, n.digits().bits().div_ceil(8) as usize)),
+        Value::Array(items) => Some((ARRAY, items.len() * size_of::<Value>())),
+        Value::Object(fields) => Some((OBJECT, fields_size(fields.keys()))),
+        Value::Instance { fields, .. } => Some((INSTANCE, fields_size(fields.borrow().keys()))),
+        _ => None,
+    }
+}
+
+/// Walks values and the scopes captured by functions, counting every scope
+/// and every instance once.
+#[derive(Default)]
+struct Measurer {
+    usage: MemoryUsage,
+    seen_scopes: HashSet<usize>,
+    seen_instances: HashSet<usize>,
+}
+
+impl Measurer {
+    fn value(&mut self, value: &Value) {
+        if let Value::Instance { fields, .. } = value {
+            if !self.seen_instances.insert(Rc::as_ptr(fields) as *const () as usize) {
+                return;
+            }
+        }
+        if let Some((category, bytes)) = shallow_size(value) {
+            *self.usage.entry(category.to_string()).or_insert(0) += bytes;
+        }
+
+        match value {
+            Value::Array(items) => items.iter().for_each(|item| self.value(item)),
+            Value::Object(fields) => fields.values().for_each(|field| self.value(field)),
+            Value::Instance { class, fields } => {
+                fields.borrow().values().for_each(|field| self.value(field));
+                self.value(class);
+            }
+            Value::Function { closure, .. } => self.environment(closure),
+            Value::Class { superclass, methods, static_methods, constructor, fields, static_fields, .. } => {
+                methods.values().chain(static_methods.values()).for_each(|method| self.value(method));
+                fields.iter().filter_map(|(_, initializer)| initializer.as_ref()).for_each(|initializer| self.value(initializer));
+                static_fields.borrow().values().for_each(|field| self.value(field));
+                if let Some(constructor) = constructor {
+                    self.value(constructor);
+                }
+                if let Some(superclass) = superclass {
+                    self.value(superclass);
+                }
+            }
+            Value::Trait { methods, .. } => methods.values().for_each(|method| self.value(method)),
+            Value::BoundMethod { receiver, method, args } => {
+                self.value(receiver);
+                self.value(method);
+                args.iter().for_each(|arg| self.value(arg));
+            }
+            _ => {}
+        }
+    }
+
+    fn environment(&mut self, environment: &Environment) {
+        let mut scope = Some(environment.clone());
+        while let Some(current) = scope {
+            if !self.seen_scopes.insert(current.id()) {
+                break;
+            }
+            *self.usage.entry(ENVIRONMENT.to_string()).or_insert(0) += scope_size(&current);
+            current.for_each_variable(|value| self.value(value));
+            scope = current.parent();
+        }
+    }
+}
+
+/// Measures a single value, including everything nested inside it.
+pub fn measure_value(value: &Value) -> MemoryUsage {
+    let mut measurer = Measurer::default();
+    measurer.value(value);
+    measurer.usage
+}
+
+/// Measures everything reachable from `roots`.
+pub fn measure_environments<'a>(roots: impl IntoIterator<Item = &'a Environment>) -> MemoryUsage {
+    let mut measurer = Measurer::default();
+    for root in roots {
+        measurer.environment(root);
+    }
+    measurer.usage
+}
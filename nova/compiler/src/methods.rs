@@ -0,0 +1,159 @@
+//! Methods on built-in types, so calls can be chained left to right:
+//! `s.trim().upper()`, `arr.map(f).filter(g).join(",")`, `n.round(2)` and
+//! `obj.keys()`.
+//!
+//! Every method is the global function of the same name with the receiver
+//! as its first argument, so `s.split(",")` is `split(s, ",")` and the two
+//! spellings can't drift apart. The global functions remain available.
+//! Reading a method without calling it (`let f = s.upper`) gives a function
+//! bound to the receiver.
+//!
+//! On objects a field of the same name wins over a method: `{keys: 1}.keys`
+//! is `1`, and a function stored in a field is called with `obj.field()`.
+//!
+//! Arrays and objects are values, so the global `push`, `pop` and `delete`
+//! return a changed copy. Called as methods they also store that copy back
+//! where the receiver came from: `items.push(x)` updates the variable
+//! `items`, and `this.items.push(x)` the field. A receiver that isn't a
+//! variable or a property, such as `[1].push(2)`, is left as it was, as is
+//! the receiver of a bound method like `let add = items.push`.
+
+use crate::value::{Value, VARIADIC};
+
+/// A method, which is also the name of the native it calls, and that
+/// native's arity.
+type Method = (&'static str, usize);
+
+const STRING_METHODS: &[Method] = &[
+    ("len", 1),
+    ("upper", 1),
+    ("lower", 1),
+    ("trim", 1),
+    ("casefold", 1),
+    ("normalize", VARIADIC),
+    ("width", 1),
+    ("chars", 1),
+    ("split", 2),
+    ("contains", 2),
+    ("substr", 3),
+    ("slice", VARIADIC),
+    ("reverse", 1),
+    ("format", VARIADIC),
+    ("encode", VARIADIC),
+    ("bytes", 1),
+    ("num", VARIADIC),
+    ("int", 1),
+    ("float", 1),
+    ("bigint", 1),
+    ("decimal", 1),
+    ("base64_encode", 1),
+    ("base64_decode", 1),
+    ("hex_encode", 1),
+    ("hex_decode", 1),
+    ("hash", VARIADIC),
+];
+
+const NUMBER_METHODS: &[Method] = &[
+    ("round", VARIADIC),
+    ("abs", 1),
+    ("sqrt", 1),
+    ("pow", 2),
+    ("sin", 1),
+    ("cos", 1),
+    ("int", 1),
+    ("float", 1),
+    ("bigint", 1),
+    ("decimal", 1),
+    ("to_base", 2),
+];
+
+const ARRAY_METHODS: &[Method] = &[
+    ("len", 1),
+    ("push", 2),
+    ("pop", 1),
+    ("join", 2),
+    ("contains", 2),
+    ("slice", VARIADIC),
+    ("reverse", 1),
+    ("sort", 1),
+    ("map", 2),
+    ("filter", 2),
+    ("reduce", VARIADIC),
+    ("find", 2),
+    ("any", 2),
+    ("all", 2),
+    ("flat_map", 2),
+    ("zip", 2),
+    ("chunk", 2),
+    ("window", 2),
+    ("unique", 1),
+    ("group_by", 2),
+    ("partition", 2),
+    ("sort_by", 2),
+    ("sort_with", 2),
+    ("bytes", 1),
+];
+
+const BYTES_METHODS: &[Method] = &[
+    ("len", 1),
+    ("slice", VARIADIC),
+    ("decode", VARIADIC),
+    ("base64_encode", 1),
+    ("hex_encode", 1),
+    ("hash", VARIADIC),
+];
+
+const OBJECT_METHODS: &[Method] = &[
+    ("keys", 1),
+    ("values", 1),
+    ("entries", 1),
+    ("has", 2),
+    ("delete", 2),
+    ("merge", VARIADIC),
+];
+
+/// The methods of values whose `type()` is `type_name`.
+fn methods_of(type_name: &str) -> &'static [Method] {
+    match type_name {
+        "string" => STRING_METHODS,
+        "number" => NUMBER_METHODS,
+        "array" => ARRAY_METHODS,
+        "bytes" => BYTES_METHODS,
+        "object" => OBJECT_METHODS,
+        _ => &[],
+    }
+}
+
+/// Whether values whose `type()` is `type_name` have the method `name`.
+pub(crate) fn has_method(type_name: &str, name: &str) -> bool {
+    methods_of(type_name).iter().any(|(method, _)| *method == name)
+}
+
+/// The native function `receiver.name(...)` calls with `receiver` as its
+/// first argument, if `receiver` has such a method.
+pub(crate) fn builtin_method(receiver: &Value, name: &str) -> Option<Value> {
+    methods_of(receiver.type_name())
+        .iter()
+        .find(|(method, _)| *method == name)
+        .map(|(method, arity)| Value::NativeFunction { name: method.to_string(), arity: *arity })
+}
+
+/// `method` with `receiver` bound as its first argument.
+pub(crate) fn bind_builtin(receiver: &Value, method: Value) -> Value {
+    Value::BoundMethod {
+        receiver: Box::new(receiver.clone()),
+        method: Box::new(method),
+        args: vec![receiver.clone()],
+    }
+}
+
+/// The new value of `receiver` after calling the method `name` on it, which
+/// returned `result`, if the method changes its receiver.
+pub(crate) fn updated_receiver(receiver: &Value, name: &str, result: &Value) -> Option<Value> {
+    match (receiver, name) {
+        (Value::Object(fields), _) if fields.contains_key(name) => None,
+        (Value::Array(_), "push") | (Value::Object(_), "delete") => Some(result.clone()),
+        (Value::Array(items), "pop") if !items.is_empty() => Some(Value::Array(items[..items.len() - 1].to_vec())),
+        _ => None,
+    }
+}
@@ -0,0 +1,398 @@
+//! Arithmetic on Nova's numbers: 64-bit integers, big integers, exact
+//! decimals and floats.
+//!
+//! Operations on two integers are exact; results that don't fit in 64 bits
+//! are promoted to big integers instead of wrapping. Mixing kinds promotes
+//! towards the more general one: integers and big integers become decimals
+//! next to a decimal and floats next to a float. Decimals and floats don't
+//! mix in arithmetic, since the result would silently lose the exactness the
+//! decimal was chosen for; they can still be compared.
+//!
+//! `/` on integers produces a float, while `~/` divides and rounds down,
+//! keeping integers integral. Bitwise operators only accept integers, or
+//! floats with no fractional part.
+
+use crate::ast::BinaryOp;
+use crate::decimal::{Decimal, DecimalContext};
+use crate::interpreter::{RuntimeError, RuntimeResult};
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{FromPrimitive, Signed, ToPrimitive, Zero};
+use std::cmp::Ordering;
+
+/// 2^63, the first float above the `i64` range.
+const I64_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// Applies `op` to two numbers. Returns `None` unless both operands are numbers.
+pub(crate) fn binary(op: &BinaryOp, left: &Value, right: &Value, context: &DecimalContext) -> Option<RuntimeResult<Value>> {
+    if !left.is_number() || !right.is_number() {
+        return None;
+    }
+    let result = match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => integer_binary(op, *l, *r),
+        (Value::Decimal(_), Value::Number(_)) | (Value::Number(_), Value::Decimal(_)) if !is_comparison(op) => {
+            Err(RuntimeError::TypeError(
+                "Cannot mix decimal and float numbers; convert one with decimal() or float()".to_string(),
+            ))
+        }
+        (Value::Decimal(_), _) | (_, Value::Decimal(_)) if !is_comparison(op) => match (to_decimal(left), to_decimal(right)) {
+            (Some(l), Some(r)) => decimal_binary(op, &l, &r, context),
+            _ => unreachable!("only decimals and integers reach here"),
+        },
+        (Value::Number(_), _) | (_, Value::Number(_)) | (Value::Decimal(_), _) | (_, Value::Decimal(_)) => {
+            if is_bitwise(op) {
+                match (to_integer(left), to_integer(right)) {
+                    (Ok(l), Ok(r)) => big_binary(op, &l, &r).map(demoted),
+                    (Err(error), _) | (_, Err(error)) => Err(error),
+                }
+            } else {
+                float_binary(op, left, right)
+            }
+        }
+        _ => big_binary(op, &to_bigint(left)?, &to_bigint(right)?),
+    };
+    Some(result)
+}
+
+/// Orders two numbers by their exact values, so a large integer and the
+/// nearest float don't compare equal. Returns `None` for non-numbers and NaN.
+pub(crate) fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Integer(l), Value::Integer(r)) => Some(l.cmp(r)),
+        (Value::Number(l), Value::Number(r)) => l.partial_cmp(r),
+        (Value::Number(l), _) => compare(right, &Value::Number(*l)).map(Ordering::reverse),
+        (Value::Decimal(l), Value::Number(r)) => Decimal::from_f64(*r).map(|r| l.cmp(&r)).or_else(|| infinite(*r)),
+        (_, Value::Number(r)) => compare_with_float(&to_bigint(left)?, *r),
+        (Value::Decimal(_), _) | (_, Value::Decimal(_)) => Some(to_decimal(left)?.cmp(&to_decimal(right)?)),
+        _ => Some(to_bigint(left)?.cmp(&to_bigint(right)?)),
+    }
+}
+
+/// How a finite number orders against an infinite float.
+fn infinite(float: f64) -> Option<Ordering> {
+    match float {
+        f64::INFINITY => Some(Ordering::Less),
+        f64::NEG_INFINITY => Some(Ordering::Greater),
+        _ => None,
+    }
+}
+
+fn compare_with_float(integer: &BigInt, float: f64) -> Option<Ordering> {
+    if !float.is_finite() {
+        return infinite(float);
+    }
+    let whole = float.trunc();
+    match integer.cmp(&BigInt::from_f64(whole)?) {
+        Ordering::Equal => 0.0.partial_cmp(&(float - whole)),
+        ordering => Some(ordering),
+    }
+}
+
+/// `-value` for a number.
+pub(crate) fn negate(value: &Value) -> Option<RuntimeResult<Value>> {
+    let result = match value {
+        Value::Integer(n) => n.checked_neg().map_or_else(|| Value::BigInt(-BigInt::from(*n)), Value::Integer),
+        Value::BigInt(n) => Value::BigInt(-n),
+        Value::Decimal(n) => Value::Decimal(n.neg()),
+        Value::Number(n) => Value::Number(-n),
+        _ => return None,
+    };
+    Some(Ok(result))
+}
+
+/// `~value` for a number.
+pub(crate) fn bitwise_not(value: &Value) -> Option<RuntimeResult<Value>> {
+    match value {
+        Value::Integer(n) => Some(Ok(Value::Integer(!n))),
+        Value::BigInt(n) => Some(Ok(Value::BigInt(!n))),
+        _ if value.is_number() => Some(to_integer(value).map(|n| integer_value(!n))),
+        _ => None,
+    }
+}
+
+/// Converts a float with no fractional part to an integer, if it fits.
+pub(crate) fn float_to_i64(n: f64) -> Option<i64> {
+    (n.fract() == 0.0 && (-I64_LIMIT..I64_LIMIT).contains(&n)).then_some(n as i64)
+}
+
+/// The value of an integer or big integer as a big integer.
+fn to_bigint(value: &Value) -> Option<BigInt> {
+    match value {
+        Value::Integer(n) => Some(BigInt::from(*n)),
+        Value::BigInt(n) => Some(n.clone()),
+        _ => None,
+    }
+}
+
+/// The exact value of any number but a float as a decimal.
+fn to_decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::Decimal(n) => Some(n.clone()),
+        _ => to_bigint(value).map(Decimal::from),
+    }
+}
+
+/// A big integer result, as a plain integer when it fits.
+fn integer_value(n: BigInt) -> Value {
+    n.to_i64().map_or(Value::BigInt(n), Value::Integer)
+}
+
+/// A big integer computed from plain operands, as a plain integer when it fits.
+fn demoted(value: Value) -> Value {
+    match value {
+        Value::BigInt(n) => integer_value(n),
+        other => other,
+    }
+}
+
+/// The position `index` refers to in a sequence of `len` elements, or
+/// `None` if it is out of bounds. Fractional indices are an error rather
+/// than being truncated.
+pub(crate) fn index(index: &Value, len: usize) -> RuntimeResult<Option<usize>> {
+    let index = match index {
+        Value::Integer(n) => *n,
+        // Too large for any sequence
+        Value::BigInt(_) => return Ok(None),
+        _ => index.as_i64().ok_or_else(|| RuntimeError::TypeError(format!("Index must be an integer, got {}", index)))?,
+    };
+    Ok(usize::try_from(index).ok().filter(|index| *index < len))
+}
+
+/// The range `start..end` selects from a sequence of `len` elements.
+/// Negative bounds count from the end and bounds past either end are
+/// clamped, so slicing never fails on an integer.
+pub(crate) fn slice_bounds(start: &Value, end: Option<&Value>, len: usize) -> RuntimeResult<(usize, usize)> {
+    let bound = |value: &Value| -> RuntimeResult<usize> {
+        let n = match value {
+            Value::BigInt(n) if n.is_negative() => i64::MIN,
+            Value::BigInt(_) => i64::MAX,
+            _ => value.as_i64().ok_or_else(|| RuntimeError::TypeError(format!("Slice bounds must be integers, got {}", value)))?,
+        };
+        let position = if n < 0 { len as i64 + n } else { n };
+        Ok(position.clamp(0, len as i64) as usize)
+    };
+    let start = bound(start)?;
+    let end = match end {
+        Some(end) => bound(end)?,
+        None => len,
+    };
+    Ok((start, end.max(start)))
+}
+
+/// The radix named by a `0x`, `0o` or `0b` prefix.
+fn prefix_radix(text: &str) -> Option<u32> {
+    match text.get(..2)?.to_ascii_lowercase().as_str() {
+        "0x" => Some(16),
+        "0o" => Some(8),
+        "0b" => Some(2),
+        _ => None,
+    }
+}
+
+/// Splits an optional sign off `text`.
+fn split_sign(text: &str) -> (bool, &str) {
+    match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    }
+}
+
+/// Parses an integer written in `radix`, with an optional sign and, in
+/// bases 16, 8 and 2, an optional `0x`, `0o` or `0b` prefix.
+pub(crate) fn parse_integer(text: &str, radix: u32) -> Option<Value> {
+    let (negative, unsigned) = split_sign(text.trim());
+    let digits = match prefix_radix(unsigned) {
+        Some(prefixed) if prefixed == radix => &unsigned[2..],
+        _ => unsigned,
+    };
+    if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+        return None;
+    }
+    let magnitude = BigInt::parse_bytes(digits.as_bytes(), radix)?;
+    Some(integer_value(if negative { -magnitude } else { magnitude }))
+}
+
+/// The radix of a string like `"0xFF"` or `"-0b101"`, if it has a prefix.
+pub(crate) fn literal_radix(text: &str) -> Option<u32> {
+    prefix_radix(split_sign(text.trim()).1)
+}
+
+/// Writes an integer in `radix`, in lowercase and with a leading `-` if negative.
+pub(crate) fn to_base(value: &Value, radix: u32) -> RuntimeResult<String> {
+    if !(2..=36).contains(&radix) {
+        return Err(RuntimeError::InvalidOperation(format!("Radix must be between 2 and 36, got {}", radix)));
+    }
+    let integer = match value {
+        Value::Number(n) if n.fract() == 0.0 => BigInt::from_f64(*n),
+        Value::Decimal(n) if n.is_integer() => Some(n.trunc()),
+        _ => to_bigint(value),
+    };
+    match integer {
+        Some(integer) => Ok(integer.to_str_radix(radix)),
+        None => Err(RuntimeError::TypeError(format!("to_base() requires an integer, got {}", value))),
+    }
+}
+
+/// The integer value of a number used by a bitwise operator.
+fn to_integer(value: &Value) -> RuntimeResult<BigInt> {
+    let integer = match value {
+        Value::Number(n) if n.fract() == 0.0 => BigInt::from_f64(*n),
+        Value::Decimal(n) if n.is_integer() => Some(n.trunc()),
+        _ => to_bigint(value),
+    };
+    integer.ok_or_else(|| RuntimeError::TypeError(format!("Bitwise operators require integers, got {}", value)))
+}
+
+fn is_bitwise(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::BitwiseAnd | BinaryOp::BitwiseOr | BinaryOp::BitwiseXor | BinaryOp::LeftShift | BinaryOp::RightShift
+    )
+}
+
+fn is_comparison(op: &BinaryOp) -> bool {
+    matches!(
+        op,
+        BinaryOp::Equal | BinaryOp::NotEqual | BinaryOp::Less | BinaryOp::Greater | BinaryOp::LessEqual | BinaryOp::GreaterEqual
+    )
+}
+
+/// The result of a comparison operator given how the operands order.
+fn comparison(op: &BinaryOp, ordering: Option<Ordering>) -> RuntimeResult<Value> {
+    let result = match op {
+        BinaryOp::Equal => ordering == Some(Ordering::Equal),
+        BinaryOp::NotEqual => ordering != Some(Ordering::Equal),
+        BinaryOp::Less => ordering == Some(Ordering::Less),
+        BinaryOp::Greater => ordering == Some(Ordering::Greater),
+        BinaryOp::LessEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+        BinaryOp::GreaterEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        _ => return Err(RuntimeError::InvalidOperation(format!("Cannot apply {:?} to numbers", op))),
+    };
+    Ok(Value::Boolean(result))
+}
+
+fn integer_binary(op: &BinaryOp, l: i64, r: i64) -> RuntimeResult<Value> {
+    // Operations that can overflow fall back to big integers
+    let result = match op {
+        BinaryOp::Add => l.checked_add(r),
+        BinaryOp::Subtract => l.checked_sub(r),
+        BinaryOp::Multiply => l.checked_mul(r),
+        BinaryOp::FloorDivide if r != 0 => l.checked_div(r).map(|quotient| {
+            // Round towards negative infinity rather than towards zero
+            if l % r != 0 && (l < 0) != (r < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        }),
+        BinaryOp::Power if r >= 0 => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+        BinaryOp::LeftShift if (0..64).contains(&r) => {
+            let shifted = l << r;
+            (shifted >> r == l).then_some(shifted)
+        }
+        BinaryOp::Divide if r != 0 => return Ok(Value::Number(l as f64 / r as f64)),
+        // `i64::MIN % -1` overflows in the division but the remainder is 0
+        BinaryOp::Modulo if r != 0 => return Ok(Value::Integer(l.checked_rem(r).unwrap_or(0))),
+        BinaryOp::BitwiseAnd => return Ok(Value::Integer(l & r)),
+        BinaryOp::BitwiseOr => return Ok(Value::Integer(l | r)),
+        BinaryOp::BitwiseXor => return Ok(Value::Integer(l ^ r)),
+        BinaryOp::RightShift if (0..64).contains(&r) => return Ok(Value::Integer(l >> r)),
+        _ if is_comparison(op) => return comparison(op, Some(l.cmp(&r))),
+        _ => None,
+    };
+    match result {
+        Some(n) => Ok(Value::Integer(n)),
+        None => big_binary(op, &BigInt::from(l), &BigInt::from(r)).map(demoted),
+    }
+}
+
+fn big_binary(op: &BinaryOp, l: &BigInt, r: &BigInt) -> RuntimeResult<Value> {
+    let result = match op {
+        BinaryOp::Add => l + r,
+        BinaryOp::Subtract => l - r,
+        BinaryOp::Multiply => l * r,
+        BinaryOp::Divide | BinaryOp::FloorDivide | BinaryOp::Modulo if r.is_zero() => {
+            return Err(RuntimeError::DivisionByZero)
+        }
+        BinaryOp::Divide => return Ok(Value::Number(divide_to_f64(l, r))),
+        BinaryOp::FloorDivide => l.div_floor(r),
+        BinaryOp::Modulo => l % r,
+        BinaryOp::Power if r.is_negative() => {
+            return Ok(Value::Number(l.to_f64().unwrap_or(f64::NAN).powf(r.to_f64().unwrap_or(f64::NAN))))
+        }
+        BinaryOp::Power => match r.to_u32() {
+            Some(exponent) => l.pow(exponent),
+            None => return Err(RuntimeError::Overflow(format!("Exponent {} is too large", r))),
+        },
+        BinaryOp::BitwiseAnd => l & r,
+        BinaryOp::BitwiseOr => l | r,
+        BinaryOp::BitwiseXor => l ^ r,
+        BinaryOp::LeftShift | BinaryOp::RightShift if r.is_negative() => {
+            return Err(RuntimeError::InvalidOperation(format!("Shift amount must not be negative, got {}", r)))
+        }
+        BinaryOp::LeftShift => match r.to_u32() {
+            Some(amount) => l << amount,
+            None => return Err(RuntimeError::Overflow(format!("Shift amount {} is too large", r))),
+        },
+        // Shifting right by more bits than `l` has leaves only its sign
+        BinaryOp::RightShift => l >> r.to_u64().unwrap_or(u64::MAX).min(l.bits() + 1),
+        _ => return comparison(op, Some(l.cmp(r))),
+    };
+    Ok(Value::BigInt(result))
+}
+
+/// `l / r` as a float, accurate even when both operands are beyond the
+/// range of floats.
+fn divide_to_f64(l: &BigInt, r: &BigInt) -> f64 {
+    // Scale the numerator so the integer quotient has about 64 significant bits
+    let shift = r.bits() as i64 - l.bits() as i64 + 64;
+    let quotient = if shift >= 0 { (l << shift as u64) / r } else { l / (r << shift.unsigned_abs()) };
+    quotient.to_f64().unwrap_or(f64::NAN) * 2f64.powi(-(shift.clamp(-2000, 2000) as i32))
+}
+
+fn decimal_binary(op: &BinaryOp, l: &Decimal, r: &Decimal, context: &DecimalContext) -> RuntimeResult<Value> {
+    let result = match op {
+        BinaryOp::Add => l.add(r),
+        BinaryOp::Subtract => l.sub(r),
+        BinaryOp::Multiply => l.mul(r),
+        BinaryOp::Divide => l.div(r, context).ok_or(RuntimeError::DivisionByZero)?,
+        BinaryOp::FloorDivide => l.floor_div(r).ok_or(RuntimeError::DivisionByZero)?,
+        BinaryOp::Modulo => l.rem(r).ok_or(RuntimeError::DivisionByZero)?,
+        BinaryOp::Power => {
+            let exponent = r.to_i64().ok_or_else(|| {
+                RuntimeError::TypeError(format!("Decimal exponents must be integers, got {}", r))
+            })?;
+            let power = u32::try_from(exponent.unsigned_abs())
+                .map_err(|_| RuntimeError::Overflow(format!("Exponent {} is too large", exponent)))?;
+            if exponent >= 0 {
+                l.pow(power)
+            } else {
+                Decimal::from(1).div(&l.pow(power), context).ok_or(RuntimeError::DivisionByZero)?
+            }
+        }
+        _ if is_bitwise(op) => {
+            return Err(RuntimeError::TypeError("Bitwise operators require integers, got a decimal".to_string()))
+        }
+        _ => return comparison(op, Some(l.cmp(r))),
+    };
+    Ok(Value::Decimal(result))
+}
+
+fn float_binary(op: &BinaryOp, left: &Value, right: &Value) -> RuntimeResult<Value> {
+    if is_comparison(op) {
+        return comparison(op, compare(left, right));
+    }
+    let (l, r) = (left.as_f64().unwrap_or(f64::NAN), right.as_f64().unwrap_or(f64::NAN));
+    match op {
+        BinaryOp::Add => Ok(Value::Number(l + r)),
+        BinaryOp::Subtract => Ok(Value::Number(l - r)),
+        BinaryOp::Multiply => Ok(Value::Number(l * r)),
+        BinaryOp::Divide | BinaryOp::FloorDivide | BinaryOp::Modulo if r == 0.0 => Err(RuntimeError::DivisionByZero),
+        BinaryOp::Divide => Ok(Value::Number(l / r)),
+        BinaryOp::FloorDivide => Ok(Value::Number((l / r).floor())),
+        BinaryOp::Modulo => Ok(Value::Number(l % r)),
+        BinaryOp::Power => Ok(Value::Number(l.powf(r))),
+        _ => Err(RuntimeError::InvalidOperation(format!("Cannot apply {:?} to numbers", op))),
+    }
+}
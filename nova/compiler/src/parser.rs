@@ -1,8 +1,11 @@
 use crate::ast::*;
+use crate::format::FormatSpec;
 use crate::token::Token;
 
 pub struct Parser {
     tokens: Vec<Token>,
+    /// Source location of each token, if known.
+    locations: Vec<SourceLocation>,
     current: usize,
 }
 
@@ -29,13 +32,27 @@ type ParseResult<T> = Result<T, ParseError>;
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Self::with_locations(tokens, Vec::new())
+    }
+
+    /// Creates a parser that records where calls happen, so that runtime
+    /// errors can report stack traces with line numbers.
+    pub fn with_locations(tokens: Vec<Token>, locations: Vec<SourceLocation>) -> Self {
+        Parser { tokens, locations, current: 0 }
+    }
+
+    fn location(&self) -> SourceLocation {
+        self.locations.get(self.current).cloned().unwrap_or_else(SourceLocation::unknown)
     }
 
     fn current_token(&self) -> &Token {
         self.tokens.get(self.current).unwrap_or(&Token::Eof)
     }
 
+    fn peek_token(&self) -> &Token {
+        self.tokens.get(self.current + 1).unwrap_or(&Token::Eof)
+    }
+
     fn advance(&mut self) -> &Token {
         if self.current < self.tokens.len() {
             self.current += 1;
@@ -85,6 +102,8 @@ impl Parser {
             self.function_statement()
         } else if self.match_token(&Token::Class) {
             self.class_statement()
+        } else if self.match_token(&Token::Trait) {
+            self.trait_statement()
         } else if self.match_token(&Token::Return) {
             self.return_statement()
         } else if self.match_token(&Token::Break) {
@@ -108,12 +127,17 @@ impl Parser {
             _ => return Err(ParseError::UnexpectedToken("Expected identifier".to_string())),
         };
         self.advance();
+        let annotation = if self.match_token(&Token::Colon) {
+            Some(self.type_annotation()?)
+        } else {
+            None
+        };
         
         self.consume(Token::Equal, "Expected '=' after variable name")?;
         let value = self.expression()?;
         self.match_token(&Token::Semicolon);
         
-        Ok(Stmt::Let { name, value })
+        Ok(Stmt::Let { name, value, annotation })
     }
 
     fn function_statement(&mut self) -> ParseResult<Stmt> {
@@ -124,8 +148,18 @@ impl Parser {
         self.advance();
         
         self.consume(Token::LeftParen, "Expected '(' after function name")?;
+        let (params, param_types) = self.parameters()?;
+        let return_type = self.return_type()?;
+        let body = self.expression()?;
         
+        Ok(Stmt::Function { name, params, body, param_types, return_type })
+    }
+
+    /// Parses parameters, each with an optional `: type`, up to and
+    /// including the closing `)`.
+    fn parameters(&mut self) -> ParseResult<(Vec<String>, Vec<Option<TypeAnnotation>>)> {
         let mut params = Vec::new();
+        let mut param_types = Vec::new();
         while !self.check(&Token::RightParen) {
             match self.current_token() {
                 Token::Identifier(param) => {
@@ -134,6 +168,11 @@ impl Parser {
                 }
                 _ => return Err(ParseError::UnexpectedToken("Expected parameter name".to_string())),
             }
+            param_types.push(if self.match_token(&Token::Colon) {
+                Some(self.type_annotation()?)
+            } else {
+                None
+            });
             
             if !self.check(&Token::RightParen) {
                 self.consume(Token::Comma, "Expected ',' between parameters")?;
@@ -141,9 +180,77 @@ impl Parser {
         }
         
         self.consume(Token::RightParen, "Expected ')' after parameters")?;
-        let body = self.expression()?;
-        
-        Ok(Stmt::Function { name, params, body })
+        Ok((params, param_types))
+    }
+
+    /// Parses an optional `-> type` after a parameter list.
+    fn return_type(&mut self) -> ParseResult<Option<TypeAnnotation>> {
+        if self.match_token(&Token::Arrow) {
+            Ok(Some(self.type_annotation()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn type_annotation(&mut self) -> ParseResult<TypeAnnotation> {
+        let location = self.location();
+        let ty = self.type_expr()?;
+        Ok(TypeAnnotation { ty, location })
+    }
+
+    /// `type := nullable ( "|" nullable )*`
+    fn type_expr(&mut self) -> ParseResult<TypeExpr> {
+        let mut members = vec![self.nullable_type()?];
+        while self.match_token(&Token::Pipe) {
+            members.push(self.nullable_type()?);
+        }
+        Ok(if members.len() == 1 { members.remove(0) } else { TypeExpr::Union(members) })
+    }
+
+    /// `nullable := ( IDENTIFIER | "null" | "[" type "]" | "(" type ")" ) "?"*`
+    fn nullable_type(&mut self) -> ParseResult<TypeExpr> {
+        let mut ty = match self.current_token() {
+            Token::Identifier(name) => {
+                let ty = TypeExpr::Named(name.clone());
+                self.advance();
+                ty
+            }
+            Token::Null => {
+                self.advance();
+                TypeExpr::Named("null".to_string())
+            }
+            Token::Fn => {
+                self.advance();
+                TypeExpr::Named("function".to_string())
+            }
+            Token::LeftBracket => {
+                self.advance();
+                let element = self.type_expr()?;
+                self.consume(Token::RightBracket, "Expected ']' after array element type")?;
+                TypeExpr::Array(Box::new(element))
+            }
+            Token::LeftParen => {
+                self.advance();
+                let ty = self.type_expr()?;
+                self.consume(Token::RightParen, "Expected ')' after type")?;
+                ty
+            }
+            _ => return Err(ParseError::UnexpectedToken("Expected type".to_string())),
+        };
+        while self.match_token(&Token::Question) {
+            ty = TypeExpr::Nullable(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    /// A branch of an `if`: an expression, or a lone `return`, `break` or
+    /// `continue` written without braces, as in `if (x == null) return 0`.
+    fn branch(&mut self) -> ParseResult<Expr> {
+        if matches!(self.current_token(), Token::Return | Token::Break | Token::Continue) {
+            Ok(Expr::Block(vec![self.statement()?]))
+        } else {
+            self.expression()
+        }
     }
 
     fn return_statement(&mut self) -> ParseResult<Stmt> {
@@ -185,6 +292,7 @@ impl Parser {
     }
 
     fn assignment(&mut self) -> ParseResult<Expr> {
+        let location = self.location();
         let expr = self.or()?;
         
         if self.match_token(&Token::Equal) {
@@ -192,6 +300,7 @@ impl Parser {
             return Ok(Expr::Assignment {
                 target: Box::new(expr),
                 value: Box::new(value),
+                location,
             });
         }
         
@@ -314,6 +423,7 @@ impl Parser {
                 Token::GreaterEqual => BinaryOp::GreaterEqual,
                 Token::Less => BinaryOp::Less,
                 Token::LessEqual => BinaryOp::LessEqual,
+                Token::InstanceOf => BinaryOp::InstanceOf,
                 _ => break,
             };
             
@@ -383,6 +493,7 @@ impl Parser {
             let operator = match token {
                 Token::Star => BinaryOp::Multiply,
                 Token::Slash => BinaryOp::Divide,
+                Token::TildeSlash => BinaryOp::FloorDivide,
                 Token::Percent => BinaryOp::Modulo,
                 _ => break,
             };
@@ -453,6 +564,7 @@ impl Parser {
     }
 
     fn call(&mut self) -> ParseResult<Expr> {
+        let location = self.location();
         let mut expr = self.primary()?;
         
         loop {
@@ -472,6 +584,7 @@ impl Parser {
                 expr = Expr::Call {
                     callee: Box::new(expr),
                     args,
+                    location: location.clone(),
                 };
             } else if self.match_token(&Token::LeftBracket) {
                 let index = self.expression()?;
@@ -514,6 +627,18 @@ impl Parser {
                 self.advance();
                 Ok(Expr::Literal(Literal::Null))
             }
+            Token::Integer(n) => {
+                self.advance();
+                Ok(Expr::Literal(Literal::Integer(n)))
+            }
+            Token::BigInt(n) => {
+                self.advance();
+                Ok(Expr::Literal(Literal::BigInt(n)))
+            }
+            Token::Decimal(n) => {
+                self.advance();
+                Ok(Expr::Literal(Literal::Decimal(n)))
+            }
             Token::Number(n) => {
                 self.advance();
                 Ok(Expr::Literal(Literal::Number(n)))
@@ -522,6 +647,10 @@ impl Parser {
                 self.advance();
                 Ok(Expr::Literal(Literal::String(s)))
             }
+            Token::Bytes(b) => {
+                self.advance();
+                Ok(Expr::Literal(Literal::Bytes(b)))
+            }
             Token::Identifier(name) => {
                 self.advance();
                 Ok(Expr::Identifier(name))
@@ -601,17 +730,31 @@ impl Parser {
                 self.advance();
                 let body = self.expression()?;
                 
-                let mut catch = None;
-                if self.match_token(&Token::Catch) {
+                let mut catches = Vec::new();
+                while self.match_token(&Token::Catch) {
                     self.consume(Token::LeftParen, "Expected '(' after 'catch'")?;
-                    let catch_var = match self.current_token() {
+                    let variable = match self.current_token() {
                         Token::Identifier(name) => name.clone(),
                         _ => return Err(ParseError::UnexpectedToken("Expected identifier in catch clause".to_string())),
                     };
                     self.advance();
+
+                    let class = if self.match_token(&Token::Colon) {
+                        match self.current_token() {
+                            Token::Identifier(name) => {
+                                let class = name.clone();
+                                self.advance();
+                                Some(class)
+                            }
+                            _ => return Err(ParseError::UnexpectedToken("Expected error class after ':' in catch clause".to_string())),
+                        }
+                    } else {
+                        None
+                    };
+
                     self.consume(Token::RightParen, "Expected ')' after catch variable")?;
-                    let catch_block = self.expression()?;
-                    catch = Some((catch_var, Box::new(catch_block)));
+                    let body = Box::new(self.expression()?);
+                    catches.push(CatchClause { variable, class, body });
                 }
                 
                 let mut finally = None;
@@ -621,7 +764,7 @@ impl Parser {
                 
                 Ok(Expr::Try {
                     body: Box::new(body),
-                    catch,
+                    catches,
                     finally,
                 })
             }
@@ -633,9 +776,9 @@ impl Parser {
             Token::If => {
                 self.advance();
                 let condition = self.expression()?;
-                let then_branch = self.expression()?;
+                let then_branch = self.branch()?;
                 let else_branch = if self.match_token(&Token::Else) {
-                    Some(Box::new(self.expression()?))
+                    Some(Box::new(self.branch()?))
                 } else {
                     None
                 };
@@ -691,6 +834,7 @@ impl Parser {
                 Ok(Expr::Array(elements))
             }
             Token::New => {
+                let location = self.location();
                 self.advance();
                 let class = self.primary()?;
                 
@@ -714,6 +858,7 @@ impl Parser {
                 Ok(Expr::New {
                     class: Box::new(class),
                     args,
+                    location,
                 })
             }
             Token::StringStart => {
@@ -735,7 +880,12 @@ impl Parser {
                         }
                         Token::InterpolationStart => {
                             self.advance();
-                            let expr = self.expression()?;
+                            let mut expr = self.expression()?;
+                            if let Token::FormatSpec(spec) = self.current_token().clone() {
+                                FormatSpec::parse(&spec).map_err(ParseError::UnexpectedToken)?;
+                                self.advance();
+                                expr = Expr::Format { value: Box::new(expr), spec };
+                            }
                             parts.push(expr);
                             self.consume(Token::InterpolationEnd, "Expected '}' after interpolation")?;
                         }
@@ -769,6 +919,7 @@ impl Parser {
         }
     }
     
+    #[allow(dead_code)]
     fn parse_lambda_params(&mut self) -> ParseResult<Vec<String>> {
         let mut params = Vec::new();
         
@@ -818,62 +969,104 @@ impl Parser {
             None
         };
 
+        // `implements` is only a keyword here, so it stays usable as a builtin's name
+        let mut traits = Vec::new();
+        if matches!(self.current_token(), Token::Identifier(word) if word == "implements") {
+            self.advance();
+            loop {
+                match self.current_token() {
+                    Token::Identifier(trait_name) => {
+                        traits.push(trait_name.clone());
+                        self.advance();
+                    }
+                    _ => return Err(ParseError::UnexpectedToken("Expected trait name".to_string())),
+                }
+                if !self.match_token(&Token::Comma) {
+                    break;
+                }
+            }
+        }
+
         self.consume(Token::LeftBrace, "Expected '{' before class body")?;
 
         let mut methods = Vec::new();
+        let mut fields = Vec::new();
+        let mut private_methods = Vec::new();
         
         while !self.check(&Token::RightBrace) && !self.check(&Token::Eof) {
             // Skip newlines in class body
-            if self.match_token(&Token::Newline) {
+            if self.match_token(&Token::Newline) || self.match_token(&Token::Semicolon) {
                 continue;
             }
 
-            // Parse method (function inside class)
-            let is_static = self.match_token(&Token::Static);
+            // Modifiers, in any order
+            let mut is_static = false;
+            let mut is_private = false;
+            loop {
+                if self.match_token(&Token::Static) {
+                    is_static = true;
+                } else if self.match_token(&Token::Private) {
+                    is_private = true;
+                } else if !self.match_token(&Token::Public) {
+                    break;
+                }
+            }
             
-            if self.match_token(&Token::Fn) {
+            // Accessors: `get name() { ... }` and `set name(value) { ... }`
+            let accessor = match (self.current_token(), self.peek_token()) {
+                (Token::Identifier(kind), Token::Identifier(_)) if kind == "get" || kind == "set" => {
+                    Some(kind.clone())
+                }
+                _ => None,
+            };
+            if accessor.is_some() {
+                self.advance();
+            } else if let Token::Identifier(field_name) = self.current_token() {
+                // Field declaration: `name[: type] [= initializer]`
+                let name = field_name.clone();
+                self.advance();
+                let annotation = if self.match_token(&Token::Colon) {
+                    Some(self.type_annotation()?)
+                } else {
+                    None
+                };
+                let initializer = if self.match_token(&Token::Equal) {
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+                fields.push(FieldDecl { name, initializer, is_static, is_private, annotation });
+                continue;
+            }
+
+            // Parse method (function inside class)
+            if accessor.is_some() || self.match_token(&Token::Fn) {
                 let method_name = match self.current_token() {
                     Token::Identifier(name) => name.clone(),
                     Token::Constructor => "constructor".to_string(),
                     _ => return Err(ParseError::UnexpectedToken("Expected method name".to_string())),
                 };
                 self.advance();
-
-                self.consume(Token::LeftParen, "Expected '(' after method name")?;
-
-                let mut params = Vec::new();
-                while !self.check(&Token::RightParen) {
-                    match self.current_token() {
-                        Token::Identifier(param) => {
-                            params.push(param.clone());
-                            self.advance();
-                        }
-                        _ => return Err(ParseError::UnexpectedToken("Expected parameter name".to_string())),
-                    }
-
-                    if !self.check(&Token::RightParen) {
-                        self.consume(Token::Comma, "Expected ',' between parameters")?;
-                    }
+                if is_private {
+                    private_methods.push(method_name.clone());
                 }
 
-                self.consume(Token::RightParen, "Expected ')' after parameters")?;
-                let body = self.expression()?;
-
-                let method = if is_static {
-                    Stmt::Function {
-                        name: format!("static_{}", method_name),
-                        params,
-                        body,
-                    }
-                } else {
-                    Stmt::Function {
-                        name: method_name,
-                        params,
-                        body,
-                    }
+                self.consume(Token::LeftParen, "Expected '(' after method name")?;
+                let (params, param_types) = self.parameters()?;
+                let return_type = self.return_type()?;
+                let method_name = match accessor.as_deref() {
+                    Some("get") if params.is_empty() => getter_key(&method_name),
+                    Some("set") if params.len() == 1 => setter_key(&method_name),
+                    Some("get") => return Err(ParseError::UnexpectedToken("Getters take no parameters".to_string())),
+                    Some(_) => return Err(ParseError::UnexpectedToken("Setters take exactly one parameter".to_string())),
+                    None => method_name,
                 };
+                let body = self.expression()?;
 
-                methods.push(method);
+                let name = if is_static { format!("static_{}", method_name) } else { method_name };
+                methods.push(Stmt::Function { name, params, body, param_types, return_type });
+            } else {
+                return Err(ParseError::UnexpectedToken("Expected field or method declaration in class body".to_string()));
             }
         }
 
@@ -883,6 +1076,48 @@ impl Parser {
             name,
             superclass,
             methods,
+            fields,
+            private_methods,
+            traits,
         })
     }
+
+    fn trait_statement(&mut self) -> ParseResult<Stmt> {
+        let name = match self.current_token() {
+            Token::Identifier(name) => name.clone(),
+            _ => return Err(ParseError::UnexpectedToken("Expected trait name".to_string())),
+        };
+        self.advance();
+        self.consume(Token::LeftBrace, "Expected '{' before trait body")?;
+
+        let mut required = Vec::new();
+        let mut methods = Vec::new();
+        while !self.check(&Token::RightBrace) && !self.check(&Token::Eof) {
+            if self.match_token(&Token::Newline) || self.match_token(&Token::Semicolon) {
+                continue;
+            }
+
+            self.consume(Token::Fn, "Expected method declaration in trait body")?;
+            let method_name = match self.current_token() {
+                Token::Identifier(name) => name.clone(),
+                _ => return Err(ParseError::UnexpectedToken("Expected method name".to_string())),
+            };
+            self.advance();
+            self.consume(Token::LeftParen, "Expected '(' after method name")?;
+            let (params, param_types) = self.parameters()?;
+            let return_type = self.return_type()?;
+
+            // A method without a body is required
+            if self.check(&Token::LeftBrace) {
+                let body = self.expression()?;
+                methods.push(Stmt::Function { name: method_name, params, body, param_types, return_type });
+            } else {
+                required.push(method_name);
+            }
+        }
+
+        self.consume(Token::RightBrace, "Expected '}' after trait body")?;
+
+        Ok(Stmt::Trait { name, required, methods })
+    }
 }
\ No newline at end of file
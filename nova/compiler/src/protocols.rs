@@ -0,0 +1,141 @@
+//! Protocol methods: methods with reserved names such as `__add__` or
+//! `__str__` that let instances of script classes work with operators and
+//! built-ins.
+//!
+//! | Method | Used for |
+//! |---|---|
+//! | `__add__`, `__sub__`, `__mul__`, `__div__`, `__floordiv__`, `__mod__`, `__pow__` | `+ - * / ~/ % **` |
+//! | `__neg__` | unary `-` |
+//! | `__eq__` | `==` and `!=` |
+//! | `__lt__`, `__le__`, `__gt__`, `__ge__` | `< <= > >=` |
+//! | `__str__` | `print`, `str`, string concatenation and interpolation |
+//! | `__len__` | `len` |
+//! | `__index__` | `value[index]` |
+//! | `__call__` | calling the instance like a function |
+//!
+//! Binary operators dispatch on the left operand, except `==` and `!=`,
+//! which also try the right one. Classes that only define `__lt__` (and
+//! optionally `__eq__`) get the other comparisons derived from it.
+
+use crate::ast::BinaryOp;
+use crate::interpreter::{Interpreter, RuntimeError, RuntimeResult};
+use crate::value::Value;
+
+/// The protocol method implementing an arithmetic operator.
+fn arithmetic_method(op: &BinaryOp) -> Option<&'static str> {
+    match op {
+        BinaryOp::Add => Some("__add__"),
+        BinaryOp::Subtract => Some("__sub__"),
+        BinaryOp::Multiply => Some("__mul__"),
+        BinaryOp::Divide => Some("__div__"),
+        BinaryOp::FloorDivide => Some("__floordiv__"),
+        BinaryOp::Modulo => Some("__mod__"),
+        BinaryOp::Power => Some("__pow__"),
+        _ => None,
+    }
+}
+
+impl Interpreter {
+    /// Calls the protocol method `name` on `receiver` if it is an instance
+    /// whose class defines it.
+    pub(crate) fn call_protocol(&mut self, receiver: &Value, name: &str, args: &[Value]) -> RuntimeResult<Option<Value>> {
+        let Value::Instance { class, .. } = receiver else {
+            return Ok(None);
+        };
+        match class.find_method(name) {
+            Some(method) => self.call_closure(&method, args, Some(receiver.clone())).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Applies `op` through protocol methods when either operand is an
+    /// instance. Returns `None` when no protocol method applies.
+    pub(crate) fn overloaded_binary(&mut self, op: &BinaryOp, left: &Value, right: &Value) -> RuntimeResult<Option<Value>> {
+        if !matches!(left, Value::Instance { .. }) && !matches!(right, Value::Instance { .. }) {
+            return Ok(None);
+        }
+
+        match op {
+            BinaryOp::Equal | BinaryOp::NotEqual => {
+                let equal = self.overloaded_equality(left, right)?;
+                Ok(equal.map(|equal| Value::Boolean(equal == matches!(op, BinaryOp::Equal))))
+            }
+            BinaryOp::Less | BinaryOp::LessEqual | BinaryOp::Greater | BinaryOp::GreaterEqual => {
+                self.overloaded_comparison(op, left, right)
+            }
+            _ => {
+                let Some(method) = arithmetic_method(op) else {
+                    return Ok(None);
+                };
+                if let Some(result) = self.call_protocol(left, method, std::slice::from_ref(right))? {
+                    return Ok(Some(result));
+                }
+                // Concatenating an instance with a string uses `__str__`
+                if let (BinaryOp::Add, Value::String(_), _) | (BinaryOp::Add, _, Value::String(_)) = (op, left, right) {
+                    let text = self.stringify(left)? + &self.stringify(right)?;
+                    return Ok(Some(Value::String(text.into())));
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// `left == right` through `__eq__` on either operand.
+    fn overloaded_equality(&mut self, left: &Value, right: &Value) -> RuntimeResult<Option<bool>> {
+        let result = match self.call_protocol(left, "__eq__", std::slice::from_ref(right))? {
+            Some(result) => Some(result),
+            None => self.call_protocol(right, "__eq__", std::slice::from_ref(left))?,
+        };
+        Ok(result.map(|result| result.is_truthy()))
+    }
+
+    fn overloaded_comparison(&mut self, op: &BinaryOp, left: &Value, right: &Value) -> RuntimeResult<Option<Value>> {
+        let method = match op {
+            BinaryOp::Less => "__lt__",
+            BinaryOp::LessEqual => "__le__",
+            BinaryOp::Greater => "__gt__",
+            _ => "__ge__",
+        };
+        if let Some(result) = self.call_protocol(left, method, std::slice::from_ref(right))? {
+            return Ok(Some(result));
+        }
+
+        // Derive the comparison from `__lt__` and equality
+        let Some(less) = self.call_protocol(left, "__lt__", std::slice::from_ref(right))? else {
+            return Ok(None);
+        };
+        let less = less.is_truthy();
+        let result = match op {
+            BinaryOp::GreaterEqual => !less,
+            BinaryOp::LessEqual => less || self.overloaded_equality(left, right)?.unwrap_or(left == right),
+            _ => !less && !self.overloaded_equality(left, right)?.unwrap_or(left == right),
+        };
+        Ok(Some(Value::Boolean(result)))
+    }
+
+    /// The text `print` and `str` show for `value`, using `__str__` for
+    /// instances that define it, also inside arrays and objects.
+    pub(crate) fn stringify(&mut self, value: &Value) -> RuntimeResult<String> {
+        match value {
+            Value::Instance { .. } => match self.call_protocol(value, "__str__", &[])? {
+                Some(Value::String(text)) => Ok(text.into()),
+                Some(other) => Err(RuntimeError::TypeError(
+                    format!("__str__ must return a string, got {}", other.type_name())
+                )),
+                None => Ok(value.to_string()),
+            },
+            Value::Array(items) => {
+                let items = items.iter().map(|item| self.stringify(item)).collect::<RuntimeResult<Vec<_>>>()?;
+                Ok(format!("[{}]", items.join(", ")))
+            }
+            Value::Object(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(key, field)| Ok(format!("{}: {}", key, self.stringify(field)?)))
+                    .collect::<RuntimeResult<Vec<_>>>()?;
+                Ok(format!("{{{}}}", fields.join(", ")))
+            }
+            other => Ok(other.to_string()),
+        }
+    }
+}
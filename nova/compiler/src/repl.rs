@@ -7,11 +7,19 @@ pub struct Repl {
     interpreter: Interpreter,
 }
 
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Repl {
     pub fn new() -> Self {
-        Repl {
-            interpreter: Interpreter::new(),
-        }
+        Self::with_interpreter(Interpreter::new())
+    }
+
+    pub fn with_interpreter(interpreter: Interpreter) -> Self {
+        Repl { interpreter }
     }
 
     pub fn run(&mut self) -> io::Result<()> {
@@ -51,7 +59,7 @@ impl Repl {
 
     fn evaluate_line(&mut self, input: &str) {
         let mut lexer = Lexer::new(input);
-        let tokens = match lexer.tokenize() {
+        let (tokens, locations) = match lexer.tokenize_with_locations() {
             Ok(tokens) => tokens,
             Err(e) => {
                 println!("Lexer Error: {}", e);
@@ -59,7 +67,7 @@ impl Repl {
             }
         };
 
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::with_locations(tokens, locations);
         match parser.parse() {
             Ok(program) => {
                 match self.interpreter.interpret(&program) {
@@ -69,7 +77,10 @@ impl Repl {
                         }
                     }
                     Err(e) => {
-                        println!("Runtime Error: {:?}", e);
+                        println!("Runtime Error: {}", e);
+                        if let Some(trace) = self.interpreter.stack_trace() {
+                            print!("{}", trace);
+                        }
                     }
                 }
             }
@@ -0,0 +1,204 @@
+//! Capability-based restrictions on what scripts may touch outside the
+//! interpreter. Natives that read or write files, use the network, read
+//! stdin or the clock ask the interpreter's `Capabilities` first and fail
+//! with `RuntimeError::PermissionDenied` when the capability was not granted.
+
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+
+/// Requests may follow at most this many redirects, as with reqwest's
+/// default policy.
+const MAX_REDIRECTS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capabilities {
+    read_roots: Vec<PathBuf>,
+    write_roots: Vec<PathBuf>,
+    net_hosts: Vec<String>,
+    stdin: bool,
+    clock: bool,
+    unrestricted: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl Capabilities {
+    /// Every capability; this is what `Interpreter::new` uses.
+    pub fn all() -> Self {
+        Capabilities {
+            unrestricted: true,
+            ..Self::none()
+        }
+    }
+
+    /// No capabilities. Grant individual ones with the `allow_*` builders.
+    pub fn none() -> Self {
+        Capabilities {
+            read_roots: Vec::new(),
+            write_roots: Vec::new(),
+            net_hosts: Vec::new(),
+            stdin: false,
+            clock: false,
+            unrestricted: false,
+        }
+    }
+
+    /// Allows reading files below `root`.
+    pub fn allow_read(mut self, root: impl AsRef<Path>) -> Self {
+        self.read_roots.extend(resolve(root.as_ref()));
+        self
+    }
+
+    /// Allows reading and writing files below `root`.
+    pub fn allow_write(mut self, root: impl AsRef<Path>) -> Self {
+        self.write_roots.extend(resolve(root.as_ref()));
+        self
+    }
+
+    /// Allows HTTP requests to `host`. `*.example.com` matches any subdomain
+    /// and `*` matches every host.
+    pub fn allow_net(mut self, host: &str) -> Self {
+        self.net_hosts.push(host.to_ascii_lowercase());
+        self
+    }
+
+    pub fn allow_stdin(mut self) -> Self {
+        self.stdin = true;
+        self
+    }
+
+    /// Allows reading the system clock and sleeping.
+    pub fn allow_clock(mut self) -> Self {
+        self.clock = true;
+        self
+    }
+
+    pub fn is_unrestricted(&self) -> bool {
+        self.unrestricted
+    }
+
+    pub fn check_read(&self, path: &str) -> Result<(), String> {
+        let target = resolve(Path::new(path));
+        if self.unrestricted
+            || target.is_some_and(|target| {
+                self.read_roots.iter().chain(&self.write_roots).any(|root| target.starts_with(root))
+            })
+        {
+            Ok(())
+        } else {
+            Err(format!("read access to '{}'", path))
+        }
+    }
+
+    pub fn check_write(&self, path: &str) -> Result<(), String> {
+        let target = resolve(Path::new(path));
+        if self.unrestricted
+            || target.is_some_and(|target| self.write_roots.iter().any(|root| target.starts_with(root)))
+        {
+            Ok(())
+        } else {
+            Err(format!("write access to '{}'", path))
+        }
+    }
+
+    pub fn check_net(&self, url: &str) -> Result<(), String> {
+        if self.unrestricted {
+            return Ok(());
+        }
+        let host = reqwest::Url::parse(url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_ascii_lowercase()))
+            .ok_or_else(|| format!("network access to '{}'", url))?;
+
+        let allowed = self.net_hosts.iter().any(|pattern| match pattern.strip_prefix("*.") {
+            Some(domain) => host == domain || host.ends_with(&format!(".{}", domain)),
+            None => pattern == "*" || *pattern == host,
+        });
+        if allowed {
+            Ok(())
+        } else {
+            Err(format!("network access to '{}'", host))
+        }
+    }
+
+    /// A redirect policy that runs `check_net` on every hop, so an allowed
+    /// host can't send a request on to one that isn't. A denied hop fails
+    /// the request with a `RedirectDenied` error as its source.
+    pub fn redirect_policy(&self) -> reqwest::redirect::Policy {
+        let capabilities = self.clone();
+        reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            match capabilities.check_net(attempt.url().as_str()) {
+                Ok(()) => attempt.follow(),
+                Err(denied) => attempt.error(RedirectDenied(denied)),
+            }
+        })
+    }
+
+    pub fn check_stdin(&self) -> Result<(), String> {
+        if self.unrestricted || self.stdin {
+            Ok(())
+        } else {
+            Err("reading from stdin".to_string())
+        }
+    }
+
+    pub fn check_clock(&self) -> Result<(), String> {
+        if self.unrestricted || self.clock {
+            Ok(())
+        } else {
+            Err("clock access".to_string())
+        }
+    }
+}
+
+/// A redirect to a host the sandbox doesn't allow.
+#[derive(Debug)]
+pub struct RedirectDenied(pub String);
+
+impl fmt::Display for RedirectDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "redirect denied: {}", self.0)
+    }
+}
+
+impl std::error::Error for RedirectDenied {}
+
+/// Makes `path` absolute and resolves `.`, `..` and symlinks one component
+/// at a time, the way the OS will, so that a script cannot escape a granted
+/// root with `data/../../etc/passwd` or through a symlink with
+/// `data/link/../etc/passwd`. Components that don't exist yet (files about
+/// to be written) are kept as written. Gives `None` for a path through a
+/// dangling or looping symlink, since where it leads can't be checked.
+fn resolve(path: &Path) -> Option<PathBuf> {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+
+    let mut resolved = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            // `resolved` contains no symlinks, so this is the real parent
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(name) => {
+                resolved.push(name);
+                if resolved.symlink_metadata().is_ok() {
+                    resolved = resolved.canonicalize().ok()?;
+                }
+            }
+            other => resolved.push(other),
+        }
+    }
+    Some(resolved)
+}
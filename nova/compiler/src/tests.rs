@@ -1,12 +1,15 @@
+#![allow(clippy::module_inception)]
+
 #[cfg(test)]
 mod tests {
     use crate::*;
+    use std::time::{Duration, Instant};
 
     fn parse_and_interpret(source: &str) -> Result<Value, String> {
         let mut lexer = Lexer::new(source);
-        let tokens = lexer.tokenize().map_err(|e| format!("Lexer error: {}", e))?;
+        let (tokens, locations) = lexer.tokenize_with_locations().map_err(|e| format!("Lexer error: {}", e))?;
         
-        let mut parser = Parser::new(tokens);
+        let mut parser = Parser::with_locations(tokens, locations);
         let program = parser.parse().map_err(|e| format!("Parse error: {:?}", e))?;
         
         let mut interpreter = Interpreter::new();
@@ -65,7 +68,7 @@ mod tests {
                 "no"
             }
         "#;
-        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("yes".to_string()));
+        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("yes".into()));
     }
 
     #[test]
@@ -73,7 +76,7 @@ mod tests {
         let source = r#"
             "Hello " + "World"
         "#;
-        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("Hello World".to_string()));
+        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("Hello World".into()));
     }
 
     #[test]
@@ -121,7 +124,7 @@ mod tests {
             let arr = ["a", "b", "c"];
             arr[1]
         "#;
-        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("b".to_string()));
+        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("b".into()));
     }
     
     #[test]
@@ -174,19 +177,19 @@ mod tests {
             }
             result
         "#;
-        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("abc".to_string()));
+        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("abc".into()));
     }
     
     #[test]
     fn test_type_function() {
         let source = r#"type(42)"#;
-        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("number".to_string()));
+        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("number".into()));
     }
     
     #[test]
     fn test_str_function() {
         let source = r#"str(123)"#;
-        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("123".to_string()));
+        assert_eq!(parse_and_interpret(source).unwrap(), Value::String("123".into()));
     }
     
     #[test]
@@ -225,4 +228,1694 @@ mod tests {
         "#;
         assert_eq!(parse_and_interpret(source).unwrap(), Value::Number(3.0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_embed_globals_and_calls() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("threshold", 10.0);
+        interpreter.eval("fn over(x) { x > threshold }").unwrap();
+
+        assert!(interpreter.call_as::<bool>("over", &[Value::Number(11.0)]).unwrap());
+        interpreter.set_global("threshold", 20.0);
+        assert!(!interpreter.call_as::<bool>("over", &[Value::Number(11.0)]).unwrap());
+
+        interpreter.eval("let total = 0; fn add(n) { total = total + n }").unwrap();
+        interpreter.call("add", &[Value::Number(5.0)]).unwrap();
+        assert_eq!(interpreter.get_global_as::<i64>("total").unwrap(), 5);
+    }
+
+    #[test]
+    fn test_embed_errors_are_typed() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("let name = \"nova\"").unwrap();
+
+        assert!(matches!(interpreter.get_global_as::<f64>("name"), Err(NovaError::Conversion { expected: "number", found: "string" })));
+        assert!(matches!(interpreter.call("name", &[]), Err(NovaError::NotCallable(_))));
+        assert!(matches!(interpreter.call("missing", &[]), Err(NovaError::UndefinedGlobal(_))));
+        assert!(matches!(interpreter.eval("1 / 0"), Err(NovaError::Runtime(RuntimeError::DivisionByZero))));
+        assert!(matches!(interpreter.eval("let = 1"), Err(NovaError::Parse(_))));
+    }
+
+    #[test]
+    fn test_embed_host_function() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register_function("discount", 2, |args| {
+            let price = f64::from_nova(&args[0])?;
+            let percent = f64::from_nova(&args[1])?;
+            if percent > 100.0 {
+                return Err(NovaError::Host("percent out of range".to_string()));
+            }
+            Ok(Value::Number(price * (1.0 - percent / 100.0)))
+        }).unwrap();
+
+        assert_eq!(interpreter.eval("discount(200, 25)").unwrap(), Value::Number(150.0));
+        assert!(matches!(interpreter.eval("discount(1, 150)"), Err(NovaError::Runtime(RuntimeError::Host(_)))));
+
+        // Built-ins, which method calls dispatch to by name, can't be replaced
+        let replaced = interpreter.register_function("len", 1, |_| Ok(Value::Number(-1.0)));
+        assert!(matches!(replaced, Err(NovaError::ReservedName(name)) if name == "len"));
+        assert_eq!(interpreter.eval("\"abc\".len() + len([1])").unwrap(), Value::Integer(4));
+    }
+
+    struct Counter {
+        count: f64,
+    }
+
+    impl HostObject for Counter {
+        fn type_name(&self) -> &str {
+            "Counter"
+        }
+
+        fn get(&self, property: &str) -> Option<Value> {
+            match property {
+                "count" => Some(Value::Number(self.count)),
+                _ => None,
+            }
+        }
+
+        fn set(&mut self, property: &str, value: Value) -> NovaResult<()> {
+            match property {
+                "count" => {
+                    self.count = f64::from_nova(&value)?;
+                    Ok(())
+                }
+                _ => Err(NovaError::Host(format!("unknown property {}", property))),
+            }
+        }
+
+        fn call_method(&mut self, method: &str, args: &[Value]) -> NovaResult<Value> {
+            match method {
+                "add" => {
+                    self.count += f64::from_nova(&args[0])?;
+                    Ok(Value::Number(self.count))
+                }
+                _ => Err(NovaError::Host(format!("unknown method {}", method))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_embed_host_object() {
+        let mut interpreter = Interpreter::new();
+        let counter = interpreter.register_object("counter", Counter { count: 1.0 });
+
+        let result = interpreter.eval("counter.add(4); counter.count = counter.count * 2; counter.count").unwrap();
+        assert_eq!(result, Value::Number(10.0));
+        assert_eq!(counter.borrow().get("count"), Some(Value::Number(10.0)));
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Tier {
+        Free,
+        Paid { seats: u32 },
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Account {
+        id: u64,
+        owner: String,
+        tags: Vec<String>,
+        tier: Tier,
+        parent: Option<Box<Account>>,
+    }
+
+    #[test]
+    fn test_serde_round_trip_through_script() {
+        let account = Account {
+            id: 7,
+            owner: "ada".to_string(),
+            tags: vec!["vip".to_string()],
+            tier: Tier::Paid { seats: 3 },
+            parent: Some(Box::new(Account {
+                id: 1,
+                owner: "root".to_string(),
+                tags: vec![],
+                tier: Tier::Free,
+                parent: None,
+            })),
+        };
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("account", to_value(&account).unwrap());
+        let result = interpreter.eval("account.parent.owner + \"/\" + account.owner").unwrap();
+        assert_eq!(result, Value::String("root/ada".into()));
+
+        let back: Account = from_value(&interpreter.get_global("account").unwrap()).unwrap();
+        assert_eq!(back, account);
+    }
+
+    #[test]
+    fn test_serde_reads_instances() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Point {
+            x: f64,
+            y: f64,
+        }
+
+        let instance = parse_and_interpret(r#"
+            class Point {
+                fn constructor(x, y) {
+                    this.x = x
+                    this.y = y
+                }
+            }
+            new Point(1.5, -2)
+        "#).unwrap();
+        assert_eq!(from_value::<Point>(&instance).unwrap(), Point { x: 1.5, y: -2.0 });
+    }
+
+    #[test]
+    fn test_serde_errors_report_path() {
+        let value = parse_and_interpret(r#"
+            { id: 3, owner: "x", tags: ["a", 5], tier: "Free", parent: null }
+        "#).unwrap();
+        let error = from_value::<Account>(&value).unwrap_err();
+        assert_eq!(error.path(), "$.tags[1]");
+        assert!(error.to_string().contains("expected a string"));
+
+        let value = parse_and_interpret("{ id: 1.5 }").unwrap();
+        assert_eq!(from_value::<Account>(&value).unwrap_err().path(), "$.id");
+
+        let function = parse_and_interpret("fn f() { 1 } f").unwrap();
+        let error = to_value(&vec![Value::Null, function]).unwrap_err();
+        assert_eq!(error.path(), "$[1]");
+        assert_eq!(error.message(), "cannot serialize a function");
+    }
+
+    fn sandboxed_eval(capabilities: Capabilities, source: &str) -> Result<Value, NovaError> {
+        Interpreter::with_capabilities(capabilities).eval(source)
+    }
+
+    fn is_permission_denied(result: Result<Value, NovaError>) -> bool {
+        matches!(result, Err(NovaError::Runtime(RuntimeError::PermissionDenied(_))))
+    }
+
+    #[test]
+    fn test_sandbox_filesystem_roots() {
+        let root = std::env::temp_dir().join(format!("nova_sandbox_{}", std::process::id()));
+        let data = root.join("data");
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::write(data.join("in.txt"), "rules").unwrap();
+        std::fs::write(root.join("secret.txt"), "secret").unwrap();
+        let path = |p: &std::path::Path| p.display().to_string().replace('\\', "/");
+
+        let read_only = Capabilities::none().allow_read(&data);
+        let source = format!("read_file(\"{}\")", path(&data.join("in.txt")));
+        assert_eq!(sandboxed_eval(read_only.clone(), &source).unwrap(), Value::String("rules".into()));
+
+        let escape = format!("read_file(\"{}/../secret.txt\")", path(&data));
+        assert!(is_permission_denied(sandboxed_eval(read_only.clone(), &escape)));
+
+        let write = format!("write_file(\"{}\", \"x\")", path(&data.join("out.txt")));
+        assert!(is_permission_denied(sandboxed_eval(read_only, &write)));
+        assert!(sandboxed_eval(Capabilities::none().allow_write(&data), &write).is_ok());
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox_import_does_not_probe_denied_paths() {
+        let root = std::env::temp_dir().join(format!("nova_sandbox_import_{}", std::process::id()));
+        let data = root.join("data");
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::write(data.join("lib.nova"), "fn answer() { return 42 }").unwrap();
+        std::fs::write(root.join("secret.nova"), "let x = 1").unwrap();
+        let path = |p: &std::path::Path| p.display().to_string().replace('\\', "/");
+
+        // An existing and a missing file outside the roots fail the same way
+        let read_data = Capabilities::none().allow_read(&data);
+        for outside in ["secret", "missing"] {
+            let source = format!("import \"{}\" as m", path(&root.join(outside)));
+            assert!(is_permission_denied(sandboxed_eval(read_data.clone(), &source)));
+        }
+
+        let source = format!("import \"{}\" as lib\nlib.answer()", path(&data.join("lib")));
+        assert_eq!(sandboxed_eval(read_data.clone(), &source).unwrap(), Value::Integer(42));
+        let missing = format!("import \"{}\" as m", path(&data.join("missing")));
+        assert!(matches!(
+            sandboxed_eval(read_data, &missing),
+            Err(NovaError::Runtime(RuntimeError::InvalidOperation(message))) if message.starts_with("Module not found")
+        ));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox_denies_by_default() {
+        assert!(is_permission_denied(sandboxed_eval(Capabilities::none(), "now()")));
+        assert!(is_permission_denied(sandboxed_eval(Capabilities::none(), "input(\"> \")")));
+        assert!(is_permission_denied(sandboxed_eval(Capabilities::none(), "http_get(\"http://example.com\")")));
+        assert!(sandboxed_eval(Capabilities::none().allow_clock(), "now()").is_ok());
+
+        // random() needs no clock access
+        let Value::Number(n) = sandboxed_eval(Capabilities::none(), "random()").unwrap() else { panic!() };
+        assert!((0.0..=1.0).contains(&n));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sandbox_symlinks_cannot_escape_roots() {
+        let root = std::env::temp_dir().join(format!("nova_sandbox_links_{}", std::process::id()));
+        let data = root.join("data");
+        let outside = root.join("outside");
+        std::fs::create_dir_all(&data).unwrap();
+        std::fs::create_dir_all(outside.join("inner")).unwrap();
+        std::fs::write(outside.join("secret.txt"), "secret").unwrap();
+        std::os::unix::fs::symlink(outside.join("inner"), data.join("link")).unwrap();
+        let path = |p: &std::path::Path| p.display().to_string();
+
+        // data/link/.. is outside/, not data/
+        let escape = format!("read_file(\"{}/link/../secret.txt\")", path(&data));
+        assert!(is_permission_denied(sandboxed_eval(Capabilities::none().allow_read(&data), &escape)));
+        let write = format!("write_file(\"{}/link/../planted.txt\", \"x\")", path(&data));
+        assert!(is_permission_denied(sandboxed_eval(Capabilities::none().allow_write(&data), &write)));
+        assert!(!outside.join("planted.txt").exists());
+
+        // Going through the link into a granted root is fine
+        let through = format!("read_file(\"{}/link/../secret.txt\")", path(&data));
+        assert_eq!(
+            sandboxed_eval(Capabilities::none().allow_read(&outside), &through).unwrap(),
+            Value::String("secret".into())
+        );
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_sandbox_checks_every_redirect() {
+        use std::io::{Read, Write};
+
+        // Redirects an allowed host's request to one that isn't allowed
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 302 Found\r\nLocation: http://blocked.invalid/\r\nContent-Length: 0\r\n\r\n").unwrap();
+        });
+
+        let source = format!("http_get(\"http://127.0.0.1:{}/\")", port);
+        let result = sandboxed_eval(Capabilities::none().allow_net("127.0.0.1"), &source);
+        server.join().unwrap();
+        assert!(is_permission_denied(result));
+    }
+
+    #[test]
+    fn test_sandbox_network_allowlist() {
+        let capabilities = Capabilities::none().allow_net("api.example.com").allow_net("*.internal");
+        assert!(capabilities.check_net("https://api.example.com/v1").is_ok());
+        assert!(capabilities.check_net("http://billing.internal:8080/").is_ok());
+        assert!(capabilities.check_net("https://example.com/").is_err());
+        assert!(capabilities.check_net("https://api.example.com.evil.org/").is_err());
+        assert!(capabilities.check_net("not a url").is_err());
+    }
+
+    #[test]
+    fn test_limits_fuel_stops_infinite_loop() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_fuel(10_000));
+
+        let result = interpreter.eval("while (true) {}");
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::OutOfFuel))));
+
+        // Scripts cannot swallow the limit, and the next run gets fresh fuel
+        let result = interpreter.eval("try { while (true) {} } catch (e) { 1 }");
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::OutOfFuel))));
+        assert_eq!(interpreter.eval("let x = 40\nx + 2").unwrap(), Value::Number(42.0));
+    }
+
+    #[test]
+    fn test_limits_timeout_and_cancellation() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_timeout(Duration::from_millis(50)));
+        let started = Instant::now();
+        let result = interpreter.eval("while (true) {}");
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::Timeout))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let mut interpreter = Interpreter::new();
+        let handle = interpreter.cancel_handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.cancel();
+        });
+        let result = interpreter.eval("while (true) {}");
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::Cancelled))));
+        assert!(!interpreter.cancel_handle().is_cancelled());
+        assert_eq!(interpreter.eval("1 + 1").unwrap(), Value::Number(2.0));
+
+        // A cancellation sent after a run finished doesn't stop the next one
+        interpreter.cancel_handle().cancel();
+        assert_eq!(interpreter.eval("1 + 1").unwrap(), Value::Number(2.0));
+
+        // sleep() wakes up when cancelled
+        let handle = interpreter.cancel_handle();
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(20));
+            handle.cancel();
+        });
+        let started = Instant::now();
+        let result = interpreter.eval("sleep(60)");
+        canceller.join().unwrap();
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::Cancelled))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_limits_max_call_depth() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_call_depth(8));
+        interpreter.eval("fn down(n) { if (n == 0) { 0 } else { 0 + down(n - 1) } }").unwrap();
+
+        assert_eq!(interpreter.call("down", &[5.into()]).unwrap(), Value::Number(0.0));
+        let result = interpreter.call("down", &[30.into()]);
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::CallDepthExceeded(8)))));
+        assert_eq!(interpreter.call("down", &[5.into()]).unwrap(), Value::Number(0.0));
+    }
+
+    #[test]
+    fn test_memory_limit_raises_catchable_error() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_memory(1 << 20));
+        interpreter.eval("let s = \"x\"").unwrap();
+
+        let result = interpreter.eval("while (true) { s = s + s }");
+        match result {
+            Err(NovaError::Runtime(RuntimeError::OutOfMemory(error))) => {
+                assert_eq!(error.category, "string");
+                assert_eq!(error.limit, 1 << 20);
+            }
+            other => panic!("expected out of memory, got {:?}", other),
+        }
+
+        let caught = interpreter.eval("s = \"x\"\ntry { while (true) { s = s + s } } catch (e) { \"caught\" }");
+        assert_eq!(caught.unwrap(), Value::String("caught".into()));
+    }
+
+    #[test]
+    fn test_memory_limit_counts_format_padding() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_memory(4096));
+        assert!(matches!(interpreter.eval("f\"${1:>10000}\""), Err(NovaError::Runtime(RuntimeError::OutOfMemory(_)))));
+        assert!(matches!(interpreter.eval("format(\"{:*^10000}\", 1)"), Err(NovaError::Runtime(RuntimeError::OutOfMemory(_)))));
+    }
+
+    #[test]
+    fn test_memory_limit_ignores_garbage() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_memory(1 << 20));
+        let source = r#"
+            let big = "x"
+            let i = 0
+            while (i < 17) { big = big + big; i = i + 1 }
+            i = 0
+            while (i < 50) { let copy = big + "y"; i = i + 1 }
+            i
+        "#;
+        assert_eq!(interpreter.eval(source).unwrap(), Value::Number(50.0));
+    }
+
+    #[test]
+    fn test_closure_cycles_are_freed() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(r#"
+fn make(n) {
+    let big = [n, n, n, n, n, n, n, n]
+    fn inner() { return big }
+    return inner
+}
+let i = 0
+while (i < 20000) {
+    make(i)
+    i = i + 1
+}
+"#).unwrap();
+        // Each call's scope was only held by the closure it created
+        assert!(crate::gc::tracked() < 5000, "{} scopes survived", crate::gc::tracked());
+
+        // Dropping the interpreter frees the global scope its functions capture
+        let globals = match interpreter.eval("make").unwrap() {
+            Value::Function { closure, .. } => std::rc::Rc::downgrade(closure.scope()),
+            other => panic!("expected a function, got {:?}", other),
+        };
+        drop(interpreter);
+        assert!(globals.upgrade().is_none());
+        assert_eq!(crate::gc::tracked(), 0);
+    }
+
+    #[test]
+    fn test_memory_usage_native() {
+        let mut interpreter = Interpreter::new();
+        let usage = interpreter.eval("let words = [\"hello\", \"world\"]\nmemory_usage()").unwrap();
+        let Value::Object(usage) = usage else { panic!("expected an object") };
+
+        let bytes = |category: &str| match usage.get(category) {
+            Some(Value::Integer(n)) => *n as usize,
+            other => panic!("missing {}: {:?}", category, other),
+        };
+        assert!(bytes("string") >= 10);
+        assert!(bytes("array") >= 2 * std::mem::size_of::<Value>());
+        assert_eq!(bytes("instance"), 0);
+        assert!(bytes("environment") > 0);
+        assert_eq!(bytes("total"), bytes("string") + bytes("array") + bytes("object") + bytes("environment"));
+    }
+
+    #[test]
+    fn test_memory_limit_counts_captured_scopes() {
+        let source = r#"
+fn make(n) {
+    let big = [n, n, n, n, n, n, n, n, n, n, n, n, n, n, n, n]
+    fn inner() { return big }
+    return inner
+}
+let i = 0
+while (i < 20000) {
+    make(i)
+    i = i + 1
+}
+"#;
+        // Closures that are dropped again are freed, so the loop fits
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_memory(5_000_000));
+        interpreter.eval(source).unwrap();
+        assert!(interpreter.memory_usage()["environment"] > 0);
+
+        // Keeping them keeps their scopes. The limit leaves room for the
+        // global scope and the copies `push` makes, but not for the scopes
+        let kept = r#"
+fn make(n) {
+    fn inner() { return n }
+    return inner
+}
+let kept = []
+let i = 0
+while (i < 1000) {
+    kept = push(kept, make(i))
+    i = i + 1
+}
+"#;
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_memory(64 * 1024 + 3 * 1000 * std::mem::size_of::<Value>()));
+        assert!(matches!(
+            interpreter.eval(kept),
+            Err(NovaError::Runtime(RuntimeError::OutOfMemory(error))) if error.category == "environment"
+        ));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(kept).unwrap();
+        assert!(interpreter.memory_usage()["environment"] > 1000 * std::mem::size_of::<crate::value::Scope>());
+    }
+
+    #[test]
+    fn test_memory_limit_counts_grapheme_tables() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_memory(1 << 20));
+        interpreter.set_global("text", Value::String("é".repeat(60_000).as_str().into()));
+        let before = interpreter.memory_usage()["string"];
+        assert_eq!(interpreter.eval("text[59999]").unwrap(), Value::String("é".into()));
+        // One offset per grapheme, plus the end of the text
+        let table = 60_001 * std::mem::size_of::<usize>();
+        assert_eq!(interpreter.memory_usage()["string"], before + table);
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_limits(ExecutionLimits::none().with_max_memory(400_000));
+        interpreter.set_global("text", Value::String("é".repeat(60_000).as_str().into()));
+        assert!(matches!(interpreter.eval("len(text)"), Err(NovaError::Runtime(RuntimeError::OutOfMemory(_)))));
+    }
+
+    #[test]
+    fn test_exceptions_preserve_thrown_values() {
+        let result = parse_and_interpret(r#"
+            try { throw { code: 42 } } catch (e) { e.code }
+        "#);
+        assert_eq!(result.unwrap(), Value::Number(42.0));
+
+        // A thrown string is an Error's message, and `type` reads as `name`,
+        // as scripts written before the error classes expect
+        let result = parse_and_interpret(r#"
+            try { throw "plain" } catch (e) { [e.message, e.name, e.type, e instanceof Error] }
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::String("plain".into()),
+            Value::String("Error".into()),
+            Value::String("Error".into()),
+            Value::Boolean(true),
+        ]));
+        // and the string itself is kept as `value`
+        let result = parse_and_interpret(r#"
+            let reason = "disk full: /var"
+            try { throw reason } catch (e) { [e.value, e.value == reason] }
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::String("disk full: /var".into()),
+            Value::Boolean(true),
+        ]));
+        let result = parse_and_interpret(r#"
+            try { missing_variable } catch (err) { err.type }
+        "#);
+        assert_eq!(result.unwrap(), Value::String("ReferenceError".into()));
+
+        // Rethrowing keeps the very same instance
+        let result = parse_and_interpret(r#"
+            let original = new Error("first")
+            try {
+                try { throw original } catch (e) { throw e }
+            } catch (e) {
+                e.message = "second"
+            }
+            original.message
+        "#);
+        assert_eq!(result.unwrap(), Value::String("second".into()));
+    }
+
+    #[test]
+    fn test_imported_modules_see_error_classes_and_host_functions() {
+        let dir = std::env::temp_dir().join(format!("nova_module_prelude_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("safe.nova"), r#"
+fn divide(a, b) {
+    try { return a / b } catch (e: ZeroDivisionError) { return e.name }
+}
+fn checked(x) {
+    if (x < 0) { throw new TypeError("negative") }
+    return twice(x)
+}
+"#).unwrap();
+
+        let mut interpreter = Interpreter::new();
+        interpreter.register_function("twice", 1, |args| Ok(Value::Number(f64::from_nova(&args[0])? * 2.0))).unwrap();
+        let module = dir.join("safe").display().to_string().replace('\\', "/");
+        interpreter.eval(&format!("import \"{}\" as safe", module)).unwrap();
+
+        assert_eq!(interpreter.eval("safe.divide(1, 0)").unwrap(), Value::String("ZeroDivisionError".into()));
+        assert_eq!(interpreter.eval("safe.checked(4)").unwrap(), Value::Number(8.0));
+        let caught = interpreter.eval("try { safe.checked(-1) } catch (e: TypeError) { e.message }");
+        assert_eq!(caught.unwrap(), Value::String("negative".into()));
+        // What the module sees but didn't define isn't exported
+        assert_eq!(interpreter.eval("keys(safe)").unwrap(), Value::Array(vec![
+            Value::String("checked".into()),
+            Value::String("divide".into()),
+        ]));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_exceptions_native_errors_and_typed_catch() {
+        let result = parse_and_interpret(r#"
+            try { 1 / 0 } catch (e: TypeError) { "type" } catch (e: ZeroDivisionError) { e.name + ": " + e.message }
+        "#);
+        assert_eq!(result.unwrap(), Value::String("ZeroDivisionError: Division by zero".into()));
+
+        let result = parse_and_interpret(r#"
+            try { read_file("/definitely/not/here.txt") } catch (e: IOError) { e.name }
+        "#);
+        assert_eq!(result.unwrap(), Value::String("IOError".into()));
+
+        let result = parse_and_interpret(r#"
+            try { missing_variable } catch (e: Error) { e.name }
+        "#);
+        assert_eq!(result.unwrap(), Value::String("ReferenceError".into()));
+
+        // No matching clause: the original error reaches the host
+        let result = Interpreter::new().eval("try { 1 / 0 } catch (e: IOError) { 0 }");
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::DivisionByZero))));
+    }
+
+    #[test]
+    fn test_exceptions_custom_classes_and_cause() {
+        let result = parse_and_interpret(r#"
+            class ConfigError extends IOError {}
+
+            fn load() {
+                try {
+                    read_file("/definitely/not/here.txt")
+                } catch (e: IOError) {
+                    throw new ConfigError("config unavailable", e)
+                }
+            }
+
+            try { load() } catch (e: IOError) { [e.name, e.message, e.cause.name, str(e)] }
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::String("ConfigError".into()),
+            Value::String("config unavailable".into()),
+            Value::String("IOError".into()),
+            Value::String("ConfigError: config unavailable".into()),
+        ]));
+
+        let error = Interpreter::new().eval("throw new TypeError(\"bad input\")").unwrap_err();
+        match error {
+            NovaError::Runtime(RuntimeError::Thrown(value)) => {
+                assert!(value.is_error());
+                assert_eq!(value.get_field("message"), Some(Value::String("bad input".into())));
+            }
+            other => panic!("expected a thrown error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_stack_trace_in_catch() {
+        let result = parse_and_interpret(r#"
+class Parser {
+    fn parse(text) {
+        return check(text)
+    }
+}
+fn check(text) {
+    throw new Error("bad: " + text)
+}
+try {
+    let p = new Parser()
+    p.parse("x")
+} catch (e) {
+    [len(e.stack), e.stack[0].function, e.stack[0].line, e.stack[1].function, e.stack[1].line]
+}
+"#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::Number(2.0),
+            Value::String("check".into()),
+            Value::Number(4.0),
+            Value::String("Parser.parse".into()),
+            Value::Number(12.0),
+        ]));
+
+        // Errors raised by the interpreter get the stack they unwound through
+        let result = parse_and_interpret(r#"
+fn inner() { return 1 / 0 }
+fn outer() { return inner() }
+try { outer() } catch (e: ZeroDivisionError) { [e.stack[0].function, e.stack[1].function] }
+"#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::String("inner".into()),
+            Value::String("outer".into()),
+        ]));
+    }
+
+    #[test]
+    fn test_stack_trace_of_uncaught_error() {
+        let mut interpreter = Interpreter::new();
+        let source = "fn fail() { return undefined_name }\nfn run() { return fail() }\nrun()";
+        assert!(interpreter.eval(source).is_err());
+
+        let trace = interpreter.stack_trace().expect("a trace for the uncaught error");
+        let frames: Vec<_> = trace.frames().iter().map(|frame| (frame.function.as_str(), frame.call_site.line)).collect();
+        assert_eq!(frames, vec![("fail", 2), ("run", 3)]);
+        assert_eq!(trace.to_string(), "    at fail (called at 2:19)\n    at run (called at 3:1)\n");
+
+        // A later successful run clears it
+        interpreter.eval("1 + 1").unwrap();
+        assert!(interpreter.stack_trace().is_none());
+    }
+
+    #[test]
+    fn test_stack_trace_elides_deep_stacks() {
+        let frames = (0..50)
+            .map(|depth| StackFrame {
+                function: format!("f{}", depth),
+                call_site: SourceLocation::new(depth + 1, 1, None),
+            })
+            .collect();
+        let printed = StackTrace::new(frames).to_string();
+        let lines: Vec<_> = printed.lines().collect();
+
+        assert_eq!(lines.len(), 21);
+        assert_eq!(lines[0], "    at f0 (called at 1:1)");
+        assert_eq!(lines[16], "    ... 30 more frames");
+        assert_eq!(lines[20], "    at f49 (called at 50:1)");
+    }
+
+    #[test]
+    fn test_recursion_limit_raises_recursion_error() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.recursion_limit(), DEFAULT_RECURSION_LIMIT);
+        interpreter.set_recursion_limit(50);
+
+        let result = interpreter.eval(r#"
+            fn forever(n) { 1 + forever(n + 1) }
+            try { forever(0) } catch (e: RecursionError) { [e.name, e.message] }
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::String("RecursionError".into()),
+            Value::String("Maximum recursion depth of 50 exceeded".into()),
+        ]));
+
+        let result = interpreter.eval("forever(0)");
+        assert!(matches!(result, Err(NovaError::Runtime(RuntimeError::RecursionLimitExceeded(50)))));
+
+        // The depth is back to zero after the error unwound
+        let result = interpreter.eval("fn depth(n) { if (n == 0) { 0 } else { 1 + depth(n - 1) } }\ndepth(45)");
+        assert_eq!(result.unwrap(), Value::Number(45.0));
+    }
+
+    #[test]
+    fn test_deep_recursion_outgrows_thread_stack() {
+        // Far deeper than the test thread's stack could hold on its own
+        let result = parse_and_interpret(r#"
+            fn sum(n) { if (n == 0) { 0 } else { n + sum(n - 1) } }
+            sum(1000)
+        "#);
+        assert_eq!(result.unwrap(), Value::Number(500500.0));
+    }
+
+    #[test]
+    fn test_tail_calls_do_not_grow_the_stack() {
+        // Each of these runs far deeper than the recursion limit allows
+        let mut interpreter = Interpreter::new();
+        interpreter.set_recursion_limit(100);
+
+        let result = interpreter.eval(r#"
+            fn count(n, acc) {
+                if (n == 0) { return acc }
+                return count(n - 1, acc + 1)
+            }
+            count(20000, 0)
+        "#);
+        assert_eq!(result.unwrap(), Value::Number(20000.0));
+
+        let result = interpreter.eval(r#"
+            fn is_even(n) { if (n == 0) { true } else { is_odd(n - 1) } }
+            fn is_odd(n) { if (n == 0) { false } else { is_even(n - 1) } }
+            let parity = [is_even(10001), is_odd(10001)]
+            parity
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![Value::Boolean(false), Value::Boolean(true)]));
+
+        let result = interpreter.eval(r#"
+            class Counter {
+                fn constructor() { this.steps = 0 }
+                fn run(n) {
+                    if (n > 0) {
+                        this.steps = this.steps + 1
+                        return this.run(n - 1)
+                    }
+                    return this.steps
+                }
+            }
+            let counter = new Counter()
+            counter.run(5000)
+        "#);
+        assert_eq!(result.unwrap(), Value::Number(5000.0));
+    }
+
+    #[test]
+    fn test_return_exits_nested_blocks() {
+        let result = parse_and_interpret(r#"
+            fn first_over(items, limit) {
+                for item in items {
+                    if (item > limit) { return item }
+                }
+                return null
+            }
+            let found = [first_over([1, 5, 9], 3), first_over([1, 2], 3)]
+            found
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![Value::Number(5.0), Value::Null]));
+    }
+
+    #[test]
+    fn test_inheritance_and_super() {
+        let result = parse_and_interpret(r#"
+            class Animal {
+                fn constructor(name) { this.name = name }
+                fn speak() { return this.name + " makes a sound" }
+                fn describe() { return "I am " + this.name }
+            }
+            class Dog extends Animal {
+                fn constructor(name, breed) {
+                    super(name)
+                    this.breed = breed
+                }
+                fn speak() { return super.speak() + " (woof)" }
+            }
+            class Puppy extends Dog {
+                fn speak() { return super.speak() + "!" }
+            }
+            let p = new Puppy("Rex", "lab")
+            let said = [p.speak(), p.describe(), p.breed]
+            said
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::String("Rex makes a sound (woof)!".into()),
+            Value::String("I am Rex".into()),
+            Value::String("lab".into()),
+        ]));
+
+        let result = parse_and_interpret("fn f() { super.speak() }\nf()");
+        assert!(result.unwrap_err().contains("outside derived class method"));
+    }
+
+    #[test]
+    fn test_instanceof_and_class_builtins() {
+        let result = parse_and_interpret(r#"
+            class Shape {}
+            class Circle extends Shape {}
+            let c = new Circle()
+            let checks = [
+                c instanceof Circle, c instanceof Shape, new Shape() instanceof Circle, 5 instanceof Shape,
+                class_of(c) == Circle, superclass_of(Circle) == Shape, superclass_of(Shape), class_of("text")
+            ]
+            checks
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::Boolean(true),
+            Value::Boolean(true),
+            Value::Boolean(false),
+            Value::Boolean(false),
+            Value::Boolean(true),
+            Value::Boolean(true),
+            Value::Null,
+            Value::Null,
+        ]));
+
+        let result = parse_and_interpret("class A {}\nnew A() instanceof 3");
+        assert!(result.unwrap_err().contains("must be a class"));
+    }
+
+    #[test]
+    fn test_class_fields_and_static_members() {
+        let result = parse_and_interpret(r#"
+            class Counter {
+                static created = 0
+                count = 0
+                label = "counter " + str(Counter.created)
+                fn constructor() { Counter.created = Counter.created + 1 }
+                fn increment() { this.count = this.count + 1; return this }
+                static fn total() { return this.created }
+            }
+            let a = new Counter()
+            let b = new Counter()
+            a.increment().increment()
+            let state = [a.count, b.count, b.label, Counter.created, Counter.total()]
+            state
+        "#);
+        assert_eq!(result.unwrap(), Value::Array(vec![
+            Value::Number(2.0),
+            Value::Number(0.0),
+            Value::String("counter 1".into()),
+            Value::Number(2.0),
+            Value::Number(2.0),
+        ]));
+    }
+
+    #[test]
+    fn test_private_members() {
+        let source = r#"
+            class Account {
+                private balance = 0
+                private static opened = 0
+                fn constructor() { Account.opened = Account.opened + 1 }
+                fn deposit(amount) { this.balance = this.balance + amount; return this.current() }
+                private fn current() { return this.balance }
+                static fn count() { return Account.opened }
+            }
+            let account = new Account()
+        "#;
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(source).unwrap();
+        assert_eq!(interpreter.eval("account.deposit(5)").unwrap(), Value::Number(5.0));
+        assert_eq!(interpreter.eval("Account.count()").unwrap(), Value::Number(1.0));
+
+        for outside in ["account.balance", "account.balance = 100", "account.current()", "Account.opened"] {
+            let error = interpreter.eval(outside).unwrap_err().to_string();
+            assert!(error.contains("Cannot access private member"), "{}: {}", outside, error);
+        }
+        assert_eq!(interpreter.eval("account.deposit(0)").unwrap(), Value::Number(5.0));
+
+        // Other classes can't reach in, even by rebinding `this`; subclasses can
+        interpreter.eval(r#"
+            class Evil {
+                fn thief() { fn g() { return this.balance } return g }
+                static fn census() { return Account.opened }
+            }
+            class Savings extends Account {
+                fn peek() { return this.balance }
+            }
+        "#).unwrap();
+        for outside in ["call(new Evil().thief(), account)", "Evil.census()"] {
+            let error = interpreter.eval(outside).unwrap_err().to_string();
+            assert!(error.contains("Cannot access private member"), "{}: {}", outside, error);
+        }
+        assert_eq!(interpreter.eval("let savings = new Savings()\nsavings.deposit(3)\nsavings.peek()").unwrap(), Value::Number(3.0));
+    }
+
+    #[test]
+    fn test_getters_and_setters() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(r#"
+            class Rect {
+                private w = 0
+                private h = 0
+                fn constructor(w, h) { this.w = w; this.h = h }
+                get area() { return this.w * this.h }
+                get width() { return this.w }
+                set width(value) {
+                    if (value < 0) { throw new TypeError("width must not be negative") }
+                    this.w = value
+                }
+                static get kind() { return "shape" }
+            }
+            let r = new Rect(2, 3)
+        "#).unwrap();
+
+        assert_eq!(interpreter.eval("r.area").unwrap(), Value::Number(6.0));
+        assert_eq!(interpreter.eval("r.width = 5").unwrap(), Value::Number(5.0));
+        assert_eq!(interpreter.eval("r.area").unwrap(), Value::Number(15.0));
+        assert_eq!(interpreter.eval("Rect.kind").unwrap(), Value::String("shape".into()));
+
+        let error = interpreter.eval("r.area = 1").unwrap_err().to_string();
+        assert!(error.contains("read-only property 'area'"), "{}", error);
+        let error = interpreter.eval("r.width = -1").unwrap_err().to_string();
+        assert!(error.contains("width must not be negative"), "{}", error);
+        assert_eq!(interpreter.eval("r.width").unwrap(), Value::Number(5.0));
+    }
+
+    #[test]
+    fn test_operator_overloading() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(r#"
+            class Vector {
+                fn constructor(x, y) { this.x = x; this.y = y }
+                fn __add__(other) { return new Vector(this.x + other.x, this.y + other.y) }
+                fn __mul__(k) { return new Vector(this.x * k, this.y * k) }
+                fn __neg__() { return new Vector(-this.x, -this.y) }
+                fn __eq__(other) { return other instanceof Vector and this.x == other.x and this.y == other.y }
+                fn __str__() { return "Vector(" + str(this.x) + ", " + str(this.y) + ")" }
+            }
+            class Money {
+                fn constructor(cents) { this.cents = cents }
+                fn __lt__(other) { return this.cents < other.cents }
+                fn __eq__(other) { return this.cents == other.cents }
+            }
+            let a = new Vector(1, 2)
+            let b = new Vector(3, 4)
+        "#).unwrap();
+
+        assert_eq!(interpreter.eval("str(a + b)").unwrap(), Value::String("Vector(4, 6)".into()));
+        assert_eq!(interpreter.eval("str(-(a * 2))").unwrap(), Value::String("Vector(-2, -4)".into()));
+        assert_eq!(interpreter.eval("a + b == new Vector(4, 6)").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("a != b").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("\"a is \" + a").unwrap(), Value::String("a is Vector(1, 2)".into()));
+        assert_eq!(interpreter.eval("str([a, b])").unwrap(), Value::String("[Vector(1, 2), Vector(3, 4)]".into()));
+
+        assert_eq!(interpreter.eval("new Money(1) < new Money(2)").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("new Money(2) <= new Money(2)").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("new Money(2) > new Money(2)").unwrap(), Value::Boolean(false));
+        assert_eq!(interpreter.eval("new Money(3) >= new Money(2)").unwrap(), Value::Boolean(true));
+
+        let error = interpreter.eval("a - b").unwrap_err().to_string();
+        assert!(error.contains("Type error"), "{}", error);
+    }
+
+    #[test]
+    fn test_container_and_callable_protocols() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(r#"
+            class Range {
+                fn constructor(n) { this.n = n }
+                fn __len__() { return this.n }
+                fn __index__(i) { return i * i }
+            }
+            class Adder {
+                fn constructor(k) { this.k = k }
+                fn __call__(x) { return x + this.k }
+            }
+            class Broken {
+                fn __str__() { return 42 }
+            }
+            let r = new Range(5)
+            let add2 = new Adder(2)
+        "#).unwrap();
+
+        assert_eq!(interpreter.eval("len(r)").unwrap(), Value::Number(5.0));
+        assert_eq!(interpreter.eval("r[3]").unwrap(), Value::Number(9.0));
+        assert_eq!(interpreter.eval("add2(40)").unwrap(), Value::Number(42.0));
+
+        let error = interpreter.eval("r(1)").unwrap_err().to_string();
+        assert!(error.contains("not callable"), "{}", error);
+        let error = interpreter.eval("str(new Broken())").unwrap_err().to_string();
+        assert!(error.contains("__str__ must return a string"), "{}", error);
+    }
+
+    #[test]
+    fn test_traits() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(r#"
+            trait Shape {
+                fn area()
+                fn describe() { return this.name + " with area " + str(this.area()) }
+            }
+            trait Named {
+                fn label() { return "<" + this.name + ">" }
+            }
+            class Square implements Shape, Named {
+                fn constructor(side) { this.name = "square"; this.side = side }
+                fn area() { return this.side * this.side }
+            }
+            class Circle implements Shape {
+                fn constructor() { this.name = "circle" }
+                fn area() { return 3 }
+                fn describe() { return "round" }
+            }
+            class Cube extends Square {}
+            let s = new Square(2)
+        "#).unwrap();
+
+        assert_eq!(interpreter.eval("s.describe()").unwrap(), Value::String("square with area 4".into()));
+        assert_eq!(interpreter.eval("s.label()").unwrap(), Value::String("<square>".into()));
+        assert_eq!(interpreter.eval("new Circle().describe()").unwrap(), Value::String("round".into()));
+        assert_eq!(interpreter.eval("s instanceof Shape").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("new Cube(1) instanceof Named").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("new Circle() instanceof Named").unwrap(), Value::Boolean(false));
+        assert_eq!(interpreter.eval("implements(s, Named)").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("implements(Circle, Named)").unwrap(), Value::Boolean(false));
+        assert_eq!(interpreter.eval("implements(42, Shape)").unwrap(), Value::Boolean(false));
+
+        let error = interpreter.eval("class Blob implements Shape {}").unwrap_err().to_string();
+        assert!(error.contains("Class Blob must implement 'area' required by trait Shape"), "{}", error);
+        let error = interpreter.eval(r#"
+            trait Labelled { fn label() { return "labelled" } }
+            class Both implements Named, Labelled {}
+        "#).unwrap_err().to_string();
+        assert!(error.contains("conflicting default method 'label'"), "{}", error);
+        let error = interpreter.eval("implements(s, Square)").unwrap_err().to_string();
+        assert!(error.contains("requires a trait"), "{}", error);
+    }
+
+    #[test]
+    fn test_bound_methods() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval(r#"
+            class Counter {
+                static made = 0
+                fn constructor() { this.count = 0 }
+                fn increment() { this.count = this.count + 1; return this.count }
+                fn add(a, b) { this.count = this.count + a + b; return this.count }
+                static fn total() { return this.made }
+            }
+            fn twice(f) { f(); return f() }
+            fn describe(prefix) { return prefix + str(this.count) }
+            let counter = new Counter()
+            let other = new Counter()
+            let f = counter.increment
+        "#).unwrap();
+
+        assert_eq!(interpreter.eval("f()").unwrap(), Value::Number(1.0));
+        assert_eq!(interpreter.eval("twice(counter.increment)").unwrap(), Value::Number(3.0));
+        assert_eq!(interpreter.eval("counter.count").unwrap(), Value::Number(3.0));
+        assert_eq!(interpreter.eval("let t = Counter.total; t()").unwrap(), Value::Number(0.0));
+        assert_eq!(interpreter.eval("type(f)").unwrap(), Value::String("function".into()));
+
+        assert_eq!(interpreter.eval("bind(describe, counter)(\"count: \")").unwrap(), Value::String("count: 3".into()));
+        assert_eq!(interpreter.eval("bind(describe, counter, \"n=\")()").unwrap(), Value::String("n=3".into()));
+        assert_eq!(interpreter.eval("call(describe, other, \"other: \")").unwrap(), Value::String("other: 0".into()));
+        assert_eq!(interpreter.eval("apply(other.add, null, [2, 3])").unwrap(), Value::Number(5.0));
+        assert_eq!(interpreter.eval("apply(describe, other, [\"\"])").unwrap(), Value::String("5".into()));
+        // Rebinding a bound method keeps its original receiver
+        assert_eq!(interpreter.eval("call(f, other)").unwrap(), Value::Number(4.0));
+
+        let error = interpreter.eval("bind(42, counter)").unwrap_err().to_string();
+        assert!(error.contains("bind expects a function"), "{}", error);
+        let error = interpreter.eval("apply(describe, other, 1)").unwrap_err().to_string();
+        assert!(error.contains("array of arguments"), "{}", error);
+    }
+
+    fn check_source(source: &str) -> Vec<String> {
+        let (tokens, locations) = Lexer::new(source).tokenize_with_locations().unwrap();
+        let program = Parser::with_locations(tokens, locations).parse().unwrap();
+        TypeChecker::new().check(&program).iter().map(|diagnostic| diagnostic.to_string()).collect()
+    }
+
+    #[test]
+    fn test_type_annotations_do_not_change_behavior() {
+        let source = r#"
+            class Point {
+                x: number = 1
+                label: string? = null
+                fn constructor(x: number) { this.x = x }
+                fn scaled(k: number) -> number { return this.x * k }
+            }
+            fn first(items: [number], fallback: number | string) -> number | string {
+                if (len(items) == 0) { return fallback }
+                return items[0]
+            }
+            let p: Point = new Point(2)
+            let wrong: string = 5
+            p.scaled(3) + first([], 10) + wrong
+        "#;
+        assert_eq!(parse_and_interpret(source).unwrap(), Value::Number(21.0));
+    }
+
+    #[test]
+    fn test_type_checker_reports_mismatches() {
+        let diagnostics = check_source(r#"
+class Point {
+    x: number = 0
+    fn constructor(x: number) { this.x = x }
+    fn norm() -> number { return this.x }
+}
+fn greet(name: string, times: number) -> string { return "hi " + name }
+let n: number = len("abc")
+let s: string = n
+let p: Point = new Point("1")
+p.x = "wide"
+greet("a")
+let xs: [number] = [1, 2, "3"]
+let maybe: Point? = null
+maybe = 3
+fn flag() -> bool { 42 }
+let t: numbr = 1
+"#);
+        assert_eq!(diagnostics, vec![
+            "9:8: 's' is declared as string but initialized with number",
+            "10:16: Argument 1 of 'new Point' should be number but got string",
+            "11:1: Field 'x' is declared as number but got string",
+            "12:1: 'greet' expects 2 arguments but got 1",
+            "13:9: 'xs' is declared as [number] but initialized with [number | string]",
+            "15:1: 'maybe' is declared as Point | null but got number",
+            "16:14: Function is declared to return bool but returns number",
+            "17:8: Unknown type 'numbr'",
+        ]);
+    }
+
+    #[test]
+    fn test_type_checker_accepts_dynamic_code() {
+        let diagnostics = check_source(r#"
+trait Shape { fn area() }
+class Square implements Shape {
+    fn constructor(side) { this.side = side }
+    fn area() { return this.side * this.side }
+}
+class Cube extends Square {}
+fn total(shapes: [Shape]) -> number {
+    let sum = 0
+    for shape in shapes { sum = sum + shape.area() }
+    return sum
+}
+let count = 0
+count = "many"
+let label: string = "n=" + count
+let shape: Shape = new Cube(2)
+let shapes: [Shape]? = [new Square(1), shape]
+let area: number = total([new Square(1)])
+let unknown: number = json_parse("1")
+"#);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_type_checker_narrows_null_checks() {
+        let diagnostics = check_source(r#"
+fn f(x: number?) -> number {
+    if (x == null) return 0
+    return x
+}
+fn g(x: number?) -> number {
+    if (x != null) { return x }
+    return -1
+}
+fn h(x: number?) -> number {
+    if (null == x) { throw new TypeError("missing") } else { x = x + 1 }
+    return x
+}
+fn sum(items: [number?]) -> number {
+    let total = 0
+    for item in items {
+        if (item == null) { continue }
+        let n: number = item
+    }
+    return total
+}
+fn reassigned(x: number?, y: number?) -> number {
+    if (x == null) { return 0 }
+    x = y
+    return x
+}
+fn unguarded(x: number?) -> number {
+    if (x == null) { print("no value") }
+    return x
+}
+"#);
+        assert_eq!(diagnostics, vec![
+            "22:42: Function is declared to return number but returns number | null",
+            "27:29: Function is declared to return number but returns number | null",
+        ]);
+        assert_eq!(parse_and_interpret("fn f(x) { if (x == null) return 0\n return x }\nf(null) + f(2)").unwrap(), Value::Integer(2));
+    }
+
+    #[test]
+    fn test_integer_arithmetic() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval("7 ~/ 2").unwrap(), Value::Integer(3));
+        assert_eq!(interpreter.eval("-7 ~/ 2").unwrap(), Value::Integer(-4));
+        assert_eq!(interpreter.eval("7 / 2").unwrap(), Value::Number(3.5));
+        assert_eq!(interpreter.eval("7.5 ~/ 2").unwrap(), Value::Number(3.0));
+        assert_eq!(interpreter.eval("// comment\n9 ~/ 2 // comment").unwrap(), Value::Integer(4));
+        assert!(matches!(interpreter.eval("1 + 2.5").unwrap(), Value::Number(n) if n == 3.5));
+        assert!(matches!(interpreter.eval("2 ** 10").unwrap(), Value::Integer(1024)));
+        assert!(matches!(interpreter.eval("2 ** -1").unwrap(), Value::Number(n) if n == 0.5));
+        assert_eq!(interpreter.eval("9007199254740993 + 0").unwrap(), Value::Integer(9007199254740993));
+        assert_eq!(interpreter.eval("9007199254740993 == 9007199254740992.0").unwrap(), Value::Boolean(false));
+        assert_eq!(interpreter.eval("1 == 1.0").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("(1 << 62) | 1").unwrap(), Value::Integer((1 << 62) | 1));
+        assert!(matches!(interpreter.eval("5 & 1.5"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+        assert!(matches!(interpreter.eval("5 ~/ 0"), Err(NovaError::Runtime(RuntimeError::DivisionByZero))));
+    }
+
+    #[test]
+    fn test_integer_overflow_promotes_to_bigint() {
+        let mut interpreter = Interpreter::new();
+        let big = |digits: &str| Value::BigInt(digits.parse().unwrap());
+        assert_eq!(interpreter.eval("9223372036854775807 + 1").unwrap(), big("9223372036854775808"));
+        assert_eq!(interpreter.eval("3 ** 40").unwrap(), big("12157665459056928801"));
+        assert_eq!(interpreter.eval("1 << 64").unwrap(), big("18446744073709551616"));
+        assert_eq!(interpreter.eval("99999999999999999999").unwrap(), big("99999999999999999999"));
+        assert!(matches!(interpreter.eval("(1 << 64) - (1 << 64)").unwrap(), Value::BigInt(_)));
+        assert!(matches!(interpreter.eval("int(1 << 64)"), Err(NovaError::Runtime(RuntimeError::Overflow(_)))));
+        let caught = interpreter.eval(r#"
+            try { int(10.0 ** 30) } catch (e: OverflowError) { e.message }
+        "#);
+        assert_eq!(caught.unwrap(), Value::String("1000000000000000000000000000000 does not fit in a 64-bit integer".into()));
+    }
+
+    #[test]
+    fn test_int_and_float_conversions() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval("int(3.9)").unwrap(), Value::Integer(3));
+        assert_eq!(interpreter.eval("int(-3.9)").unwrap(), Value::Integer(-3));
+        assert_eq!(interpreter.eval("int(\" 42 \")").unwrap(), Value::Integer(42));
+        assert!(matches!(interpreter.eval("float(2)").unwrap(), Value::Number(n) if n == 2.0));
+        assert!(matches!(interpreter.eval("num(\"7\")").unwrap(), Value::Integer(7)));
+        assert!(matches!(interpreter.eval("len([1, 2])").unwrap(), Value::Integer(2)));
+        assert!(matches!(interpreter.eval("int(10.0 ** 300)"), Err(NovaError::Runtime(RuntimeError::Overflow(_)))));
+        assert!(matches!(interpreter.eval("int(\"3.5\")"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+    }
+
+    #[test]
+    fn test_fractional_index_is_an_error() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval("[10, 20, 30][2.0]").unwrap(), Value::Integer(30));
+        assert!(matches!(interpreter.eval("[10, 20, 30][1.5]"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+        assert!(matches!(interpreter.eval("[10, 20, 30][-1]"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert!(matches!(interpreter.eval("\"abc\"[0.5]"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+    }
+
+    #[test]
+    fn test_bigint_literals_and_arithmetic() {
+        let mut interpreter = Interpreter::new();
+        let big = |digits: &str| Value::BigInt(digits.parse().unwrap());
+        assert_eq!(interpreter.eval("123n").unwrap(), big("123"));
+        assert_eq!(
+            interpreter.eval("123456789012345678901234567890n * 10n + 1").unwrap(),
+            big("1234567890123456789012345678901")
+        );
+        assert_eq!(interpreter.eval("-7n ~/ 2n").unwrap(), big("-4"));
+        assert_eq!(interpreter.eval("5n == 5").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("(1 << 70) > 1.5 * (1 << 62)").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("str(2n ** 70)").unwrap(), Value::String("1180591620717411303424".into()));
+        assert_eq!(interpreter.eval("bigint(\"1_000\") + bigint(2.9)").unwrap(), big("1002"));
+        assert_eq!(interpreter.eval("num(\"18446744073709551616\")").unwrap(), big("18446744073709551616"));
+        assert!(matches!(interpreter.eval("1n / 0n"), Err(NovaError::Runtime(RuntimeError::DivisionByZero))));
+    }
+
+    #[test]
+    fn test_decimal_arithmetic_and_rounding() {
+        let mut interpreter = Interpreter::new();
+        let mut decimal = |text: &str| interpreter.eval(text).unwrap().to_string();
+        assert_eq!(decimal("0.1d + 0.2d"), "0.3");
+        assert_eq!(decimal("12.30d * 2"), "24.60");
+        assert_eq!(decimal("19.99d * 3 - 0.97d"), "59.00");
+        assert_eq!(decimal("10.00d / 4d"), "2.50");
+        assert_eq!(decimal("1d / 3d"), "0.3333333333333333333333333333");
+        assert_eq!(decimal("round(2.665d, 2)"), "2.66");
+        assert_eq!(decimal("round(2.665d, 2, \"half_up\")"), "2.67");
+        assert_eq!(decimal("round(-2.5d, 0, \"floor\")"), "-3");
+        assert_eq!(decimal("decimal(\"1.50\") + decimal(0.1)"), "1.60");
+        assert_eq!(decimal("set_decimal_context(2, \"down\")\n2d / 3d"), "0.66");
+        assert_eq!(interpreter.eval("1.0d == 1.00d").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("0.5d < 0.75").unwrap(), Value::Boolean(true));
+        assert!(matches!(interpreter.eval("1.5d + 1.5"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+        assert!(matches!(interpreter.eval("round(1d, 0, \"sideways\")"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert_eq!(interpreter.decimal_context(), DecimalContext { places: 2, rounding: RoundingMode::Down });
+    }
+
+    #[test]
+    fn test_big_numbers_round_trip_through_json() {
+        let mut interpreter = Interpreter::new();
+        let json = interpreter
+            .eval("json_stringify([123456789012345678901234567890n, 12.30d, 7, 1.5])")
+            .unwrap();
+        assert_eq!(json, Value::String("[123456789012345678901234567890,12.30,7,1.5]".into()));
+        let parsed = interpreter
+            .eval("json_parse(\"[123456789012345678901234567890, 0.1000000000000000000001, 12.30, 1.5]\")")
+            .unwrap();
+        let Value::Array(items) = parsed else { panic!("expected an array") };
+        assert!(matches!(&items[0], Value::BigInt(_)));
+        assert!(matches!(&items[1], Value::Decimal(n) if n.to_string() == "0.1000000000000000000001"));
+        assert!(matches!(items[2], Value::Number(n) if n == 12.3));
+        assert!(matches!(items[3], Value::Number(n) if n == 1.5));
+
+        let big: u128 = from_value(&interpreter.eval("2n ** 100").unwrap()).unwrap();
+        assert_eq!(big, 1 << 100);
+        let price: String = from_value(&interpreter.eval("19.90d").unwrap()).unwrap();
+        assert_eq!(price, "19.90");
+        assert!(matches!(to_value(&(1u128 << 100)).unwrap(), Value::BigInt(_)));
+    }
+
+    #[test]
+    fn test_u64_round_trips_beyond_the_integer_range() {
+        for n in [u64::MAX, 1 << 63] {
+            let value = to_value(&n).unwrap();
+            assert!(matches!(&value, Value::BigInt(big) if big.to_string() == n.to_string()));
+            assert_eq!(from_value::<u64>(&value).unwrap(), n);
+            assert_eq!(u64::from_nova(&n.into_nova()).unwrap(), n);
+
+            let json = to_value(&serde_json::json!({ "id": n })).unwrap();
+            assert_eq!(serde_json::to_value(&json).unwrap(), serde_json::json!({ "id": n }));
+        }
+        assert_eq!(serde_json::to_value(Value::Number(1e20)).unwrap(), serde_json::json!(1e20));
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("n", (1u64 << 63).into_nova());
+        assert_eq!(interpreter.eval("n * 2 - 1 == 18446744073709551615n").unwrap(), Value::Boolean(true));
+        interpreter.eval("n = n * 2 - 1").unwrap();
+        assert_eq!(interpreter.get_global_as::<u64>("n").unwrap(), u64::MAX);
+        assert!(interpreter.eval("n = n + 1").is_ok());
+        assert!(interpreter.get_global_as::<u64>("n").is_err());
+    }
+
+    #[test]
+    fn test_radix_literals_separators_and_exponents() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval("0xFF + 0o755 + 0b1010").unwrap(), Value::Integer(255 + 493 + 10));
+        assert_eq!(interpreter.eval("1_000_000").unwrap(), Value::Integer(1_000_000));
+        assert_eq!(interpreter.eval("6.02e23").unwrap(), Value::Number(6.02e23));
+        assert_eq!(interpreter.eval("1.5E-3").unwrap(), Value::Number(0.0015));
+        assert_eq!(interpreter.eval("0xFFn").unwrap(), Value::BigInt(255.into()));
+        assert_eq!(interpreter.eval("str(6.02e23)").unwrap(), Value::String("602000000000000000000000".into()));
+        for (source, message) in [
+            ("0x", "expected digits after the prefix"),
+            ("1__0", "underscores must separate digits"),
+            ("1_", "underscores must separate digits"),
+            ("1e", "expected digits in the exponent"),
+            ("0b102", "Invalid digit '2' in binary literal"),
+            ("12px", "Invalid character 'p' after number literal '12'"),
+        ] {
+            let error = Lexer::new(source).tokenize().unwrap_err();
+            assert!(error.contains(message), "{}: {}", source, error);
+        }
+    }
+
+    #[test]
+    fn test_num_with_radix_and_to_base() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!(interpreter.eval("num(\"ff\", 16)").unwrap(), Value::Integer(255));
+        assert_eq!(interpreter.eval("num(\"0b101\", 2)").unwrap(), Value::Integer(5));
+        assert_eq!(interpreter.eval("num(\"-0x1F\")").unwrap(), Value::Integer(-31));
+        assert_eq!(interpreter.eval("num(\"zz\", 36)").unwrap(), Value::Integer(1295));
+        assert_eq!(interpreter.eval("to_base(255, 16)").unwrap(), Value::String("ff".into()));
+        assert_eq!(interpreter.eval("to_base(-10, 2)").unwrap(), Value::String("-1010".into()));
+        assert_eq!(interpreter.eval("num(to_base(2n ** 70, 36), 36) == 2n ** 70").unwrap(), Value::Boolean(true));
+        assert!(matches!(interpreter.eval("num(\"zz\", 16)"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert!(matches!(interpreter.eval("to_base(10, 37)"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert!(matches!(interpreter.eval("to_base(1.5, 2)"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+    }
+
+    #[test]
+    fn test_bytes_literals_indexing_and_slicing() {
+        let mut interpreter = Interpreter::new();
+        let bytes = |b: &[u8]| Value::Bytes(b.to_vec());
+        assert_eq!(interpreter.eval(r#"b"hi\x00\xff""#).unwrap(), bytes(b"hi\x00\xff"));
+        assert_eq!(interpreter.eval(r#"type(b"")"#).unwrap(), Value::String("bytes".into()));
+        assert_eq!(interpreter.eval(r#"len(b"abc")"#).unwrap(), Value::Integer(3));
+        assert_eq!(interpreter.eval(r#"b"\xff"[0]"#).unwrap(), Value::Integer(255));
+        assert_eq!(interpreter.eval(r#"slice(b"abcd", 1, -1)"#).unwrap(), bytes(b"bc"));
+        assert_eq!(interpreter.eval("slice([1, 2, 3], -2)").unwrap(), Value::Array(vec![Value::Integer(2), Value::Integer(3)]));
+        assert_eq!(interpreter.eval(r#"slice("héllo", 1, 100)"#).unwrap(), Value::String("éllo".into()));
+        assert_eq!(interpreter.eval(r#"b"ab" + b"c" == bytes([97, 98, 99])"#).unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval(r#"str(b"a\"\n\x01")"#).unwrap(), Value::String(r#"b"a\"\n\x01""#.into()));
+        assert!(matches!(interpreter.eval(r#"b"ab"[2]"#), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert!(matches!(interpreter.eval("bytes([256])"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert!(Lexer::new(r#"b"é""#).tokenize().unwrap_err().contains("Non-ASCII character"));
+    }
+
+    #[test]
+    fn test_bytes_encodings_base64_and_files() {
+        let mut interpreter = Interpreter::new();
+        let string = |s: &str| Value::String(s.into());
+        assert_eq!(interpreter.eval(r#"encode("é")"#).unwrap(), Value::Bytes(vec![0xc3, 0xa9]));
+        assert_eq!(interpreter.eval(r#"encode("é", "utf-16be")"#).unwrap(), Value::Bytes(vec![0x00, 0xe9]));
+        assert_eq!(interpreter.eval(r#"decode(encode("héllo 🎉", "utf-16"), "UTF-16LE")"#).unwrap(), string("héllo 🎉"));
+        assert_eq!(interpreter.eval(r#"decode(b"caf\xe9", "latin-1")"#).unwrap(), string("café"));
+        assert_eq!(interpreter.eval(r#"base64_encode("hello")"#).unwrap(), string("aGVsbG8="));
+        assert_eq!(interpreter.eval(r#"decode(base64_decode("aGVsbG8="))"#).unwrap(), string("hello"));
+        assert_eq!(interpreter.eval(r#"hex_encode(b"\x01\xab")"#).unwrap(), string("01ab"));
+        assert_eq!(interpreter.eval(r#"hash("abc") == hash(b"abc", "sha256")"#).unwrap(), Value::Boolean(true));
+        assert_eq!(
+            interpreter.eval(r#"hash("abc")"#).unwrap(),
+            string("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+        assert_eq!(interpreter.eval(r#"hash("abc", "md5")"#).unwrap(), string("900150983cd24fb0d6963f7d28e17f72"));
+        for source in [r#"decode(b"\xff")"#, r#"encode("€", "latin-1")"#, r#"decode(b"a", "utf-16")"#, r#"encode("x", "ebcdic")"#] {
+            assert!(matches!(interpreter.eval(source), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))), "{}", source);
+        }
+
+        let path = std::env::temp_dir().join(format!("nova_bytes_{}.bin", std::process::id()));
+        let path = path.display().to_string().replace('\\', "/");
+        interpreter.eval(&format!(r#"write_file_bytes("{}", b"\x00\x01\xfe")"#, path)).unwrap();
+        assert_eq!(interpreter.eval(&format!(r#"read_file_bytes("{}")"#, path)).unwrap(), Value::Bytes(vec![0, 1, 0xfe]));
+        assert!(matches!(interpreter.eval(&format!(r#"read_file("{}")"#, path)), Err(NovaError::Runtime(RuntimeError::Io(_)))));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(from_value::<Vec<u8>>(&Value::Bytes(vec![1, 2])).unwrap(), vec![1, 2]);
+        assert_eq!(to_value(&Value::Bytes(vec![7])).unwrap(), Value::Bytes(vec![7]));
+    }
+
+    #[test]
+    fn test_raw_and_triple_quoted_strings() {
+        let mut interpreter = Interpreter::new();
+        let string = |s: &str| Value::String(s.into());
+        assert_eq!(interpreter.eval(r#"r"C:\new\path""#).unwrap(), string(r"C:\new\path"));
+        assert_eq!(interpreter.eval(r#"r'\d+'"#).unwrap(), string(r"\d+"));
+        let indented = "let s = \"\"\"\n    first\n      second\\tline\n    \"\"\"\ns";
+        assert_eq!(interpreter.eval(indented).unwrap(), string("first\n  second\tline"));
+        assert_eq!(interpreter.eval("\"\"\"say \"hi\" \"\"\"").unwrap(), string("say \"hi\" "));
+        assert_eq!(interpreter.eval("r\"\"\"\n  a\\n\n  \"\"\"").unwrap(), string("a\\n"));
+        assert!(Lexer::new("\"\"\"open").tokenize().unwrap_err().contains("Unterminated triple-quoted string"));
+    }
+
+    #[test]
+    fn test_interpolation_with_format_specifiers() {
+        let mut interpreter = Interpreter::new();
+        let mut format = |source: &str| interpreter.eval(source).unwrap().to_string();
+        assert_eq!(format("let x = 5\nf\"a ${x} b ${x * 2}\""), "a 5 b 10");
+        assert_eq!(format("let price = 3.14159\nf\"${price:.2f}\""), "3.14");
+        assert_eq!(format("f\"[${42:>6}] [${42:<4}] [${\"hi\":*^6}]\""), "[    42] [42  ] [**hi**]");
+        assert_eq!(format("f\"${255:#x} ${255:X} ${5:08b} ${-42:+06d}\""), "0xff FF 00000101 -00042");
+        assert_eq!(format("f\"${1234567.891:,.2f} ${0.256:.1%} ${6.02e23:.2e}\""), "1,234,567.89 25.6% 6.02e+23");
+        assert_eq!(format("f\"${2.665d:.2f} ${2n ** 64:,d}\""), "2.66 18,446,744,073,709,551,616");
+        assert_eq!(format("f\"${ {a: 1}.a } ${f\"${1 + 1}\"} \\${x}\""), "1 2 ${x}");
+        assert!(matches!(interpreter.eval("f\"${1.5:d}\""), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+        assert!(matches!(interpreter.eval("f\"${\"a\":.2f}\""), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+        assert!(matches!(interpreter.eval("f\"${1:.2q}\""), Err(NovaError::Parse(_))));
+        assert!(matches!(interpreter.eval("f\"${1:>10001}\""), Err(NovaError::Parse(_))));
+        assert!(matches!(interpreter.eval("f\"${1:.99999999999999999999f}\""), Err(NovaError::Parse(_))));
+        assert_eq!(interpreter.eval("len(f\"${1:>10000}\")").unwrap(), Value::Integer(10000));
+    }
+
+    #[test]
+    fn test_format_builtin() {
+        let mut interpreter = Interpreter::new();
+        let string = |s: &str| Value::String(s.into());
+        assert_eq!(interpreter.eval("format(\"{} + {} = {:.1f}\", 1, 2, 3)").unwrap(), string("1 + 2 = 3.0"));
+        assert_eq!(interpreter.eval("format(\"{1}{0} {{literal}} {0:>3}\", \"a\", \"b\")").unwrap(), string("ba {literal}   a"));
+        for source in ["format(\"{\", 1)", "format(\"}\")", "format(\"{1}\", 1)", "format(\"{:.q}\", 1)",
+                       "format(\"{:>10001}\", 1)", "format(\"{:099999999999999999999}\", 1)"] {
+            assert!(matches!(interpreter.eval(source), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))), "{}", source);
+        }
+    }
+
+    #[test]
+    fn test_strings_count_graphemes() {
+        let mut interpreter = Interpreter::new();
+        let string = |s: &str| Value::String(s.into());
+        interpreter.eval("let s = \"e\\u0301t\\u00e9 🇫🇷\"").unwrap();
+        assert_eq!(interpreter.eval("len(s)").unwrap(), Value::Integer(5));
+        assert_eq!(interpreter.eval("s[0]").unwrap(), string("e\u{301}"));
+        assert_eq!(interpreter.eval("s[4]").unwrap(), string("🇫🇷"));
+        assert_eq!(interpreter.eval("reverse(s)").unwrap(), string("🇫🇷 \u{e9}te\u{301}"));
+        assert_eq!(interpreter.eval("slice(s, -1)").unwrap(), string("🇫🇷"));
+        assert_eq!(interpreter.eval("substr(s, 1, 2)").unwrap(), string("t\u{e9}"));
+        assert_eq!(interpreter.eval("len(split(s, \"\"))").unwrap(), Value::Integer(5));
+        assert_eq!(interpreter.eval("let n = 0\nfor g in s { n = n + 1 }\nn").unwrap(), Value::Integer(5));
+        assert_eq!(interpreter.eval("len(chars(s))").unwrap(), Value::Integer(7));
+        assert_eq!(interpreter.eval("len(bytes(s))").unwrap(), Value::Integer(15));
+        assert!(matches!(interpreter.eval("s[5]"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+
+        let text = NovaString::from("a\r\nb");
+        assert_eq!((text.grapheme_count(), text.grapheme(1)), (3, Some("\r\n")));
+    }
+
+    #[test]
+    fn test_case_folding_normalization_and_width() {
+        let mut interpreter = Interpreter::new();
+        let string = |s: &str| Value::String(s.into());
+        assert_eq!(interpreter.eval("casefold(\"Straße\") == casefold(\"STRASSE\")").unwrap(), Value::Boolean(true));
+        // Cherokee folds to upper case, which lowercasing can't produce
+        assert_eq!(interpreter.eval("casefold(\"\u{AB70}\u{13A0}\")").unwrap(), string("\u{13A0}\u{13A0}"));
+        assert_eq!(interpreter.eval("\"\\u00e9\" == \"e\\u0301\"").unwrap(), Value::Boolean(false));
+        assert_eq!(interpreter.eval("normalize(\"e\\u0301\") == \"\\u00e9\"").unwrap(), Value::Boolean(true));
+        assert_eq!(interpreter.eval("normalize(\"\\u00e9\", \"nfd\")").unwrap(), string("e\u{301}"));
+        assert_eq!(interpreter.eval("normalize(\"ﬁ\", \"NFKC\")").unwrap(), string("fi"));
+        assert!(matches!(interpreter.eval("normalize(\"a\", \"NFX\")"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert_eq!(interpreter.eval("width(\"日本\") + width(\"e\\u0301\")").unwrap(), Value::Integer(5));
+        assert_eq!(interpreter.eval("f\"[${\"日本\":<6}|${\"e\\u0301\":>3}]\"").unwrap(), string("[日本  |  e\u{301}]"));
+    }
+
+    #[test]
+    fn test_methods_on_builtin_types() {
+        let mut interpreter = Interpreter::new();
+        let string = |s: &str| Value::String(s.into());
+        interpreter.eval("fn double(x) { return x * 2 }\nfn big(x) { return x > 2 }").unwrap();
+        assert_eq!(interpreter.eval("\"  hello \".trim().upper()").unwrap(), string("HELLO"));
+        assert_eq!(interpreter.eval("[3, 1, 2].map(double).filter(big).join(\",\")").unwrap(), string("6,4"));
+        assert_eq!(interpreter.eval("[3, 1, 2].sort().reverse()").unwrap(), interpreter.eval("[3, 2, 1]").unwrap());
+        assert_eq!(interpreter.eval("12.3456.round(2)").unwrap(), Value::Number(12.35));
+        assert_eq!(interpreter.eval("{b: 2, a: 1}.keys()").unwrap(), interpreter.eval("[\"b\", \"a\"]").unwrap());
+        assert_eq!(interpreter.eval("\"{}-{}\".format(1, 2)").unwrap(), string("1-2"));
+        assert_eq!(interpreter.eval("b\"hi\".hex_encode()").unwrap(), string("6869"));
+        assert_eq!(interpreter.eval("let up = \"abc\".upper\nup()").unwrap(), string("ABC"));
+        assert_eq!(interpreter.eval("upper(trim(\" a \"))").unwrap(), string("A"));
+
+        // Fields of objects win over methods, and functions in fields can be called
+        assert_eq!(interpreter.eval("{keys: 5}.keys").unwrap(), Value::Integer(5));
+        assert_eq!(interpreter.eval("{f: double}.f(4)").unwrap(), Value::Integer(8));
+        assert!(matches!(interpreter.eval("\"a\".nope()"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+        assert!(matches!(interpreter.eval("\"a\".split()"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+    }
+
+    #[test]
+    fn test_mutating_methods_update_receiver() {
+        let mut interpreter = Interpreter::new();
+        let eval = |interpreter: &mut Interpreter, source: &str| interpreter.eval(source).unwrap().to_string();
+        interpreter.eval(r#"
+let xs = [1]
+xs.push(2)
+let last = xs.pop()
+xs.push(3)
+let o = {a: 1, b: 2}
+o.delete("a")
+class Stack {
+    fn constructor() { this.items = [] }
+    fn add(x) { this.items.push(x) }
+    fn take() { return this.items.pop() }
+}
+let s = new Stack()
+s.add("a")
+s.add("b")
+let top = s.take()
+"#).unwrap();
+        assert_eq!(eval(&mut interpreter, "xs"), "[1, 3]");
+        assert_eq!(eval(&mut interpreter, "last"), "2");
+        assert_eq!(eval(&mut interpreter, "o"), "{b: 2}");
+        assert_eq!(eval(&mut interpreter, "top"), "b");
+        assert_eq!(eval(&mut interpreter, "s.items"), "[a]");
+
+        // The global functions and temporaries keep value semantics
+        assert_eq!(eval(&mut interpreter, "push(xs, 4)"), "[1, 3, 4]");
+        assert_eq!(eval(&mut interpreter, "[1].push(2)"), "[1, 2]");
+        assert_eq!(eval(&mut interpreter, "xs"), "[1, 3]");
+    }
+
+    #[test]
+    fn test_type_checker_knows_builtin_methods() {
+        let diagnostics = check_source(r#"
+let s: string = " a "
+let n: number = s.trim().upper()
+s.split()
+let words: [string] = s.split(",")
+"#);
+        assert_eq!(diagnostics, vec![
+            "3:8: 'n' is declared as number but initialized with string",
+            "4:1: 'string.split' expects 1 arguments but got 0",
+        ]);
+    }
+
+    #[test]
+    fn test_higher_order_array_functions() {
+        let mut interpreter = Interpreter::new();
+        let eval = |interpreter: &mut Interpreter, source: &str| interpreter.eval(source).unwrap().to_string();
+        interpreter.eval(r#"
+fn add(a, b) { return a + b }
+fn even(x) { return x % 2 == 0 }
+fn pair(x) { return [x, x * 10] }
+fn parity(x) { if (x % 2 == 0) { return "even" } return "odd" }
+let xs = [5, 3, 8, 1, 4]
+"#).unwrap();
+        assert_eq!(eval(&mut interpreter, "xs.reduce(add)"), "21");
+        assert_eq!(eval(&mut interpreter, "reduce(xs, add, 100)"), "121");
+        assert_eq!(eval(&mut interpreter, "[xs.find(even), [1].find(even)]"), "[8, null]");
+        assert_eq!(eval(&mut interpreter, "[xs.any(even), xs.all(even), [].all(even)]"), "[true, false, true]");
+        assert_eq!(eval(&mut interpreter, "[1, 2].flat_map(pair)"), "[1, 10, 2, 20]");
+        assert_eq!(eval(&mut interpreter, "zip([1, 2, 3], [\"a\", \"b\"])"), "[[1, a], [2, b]]");
+        assert_eq!(eval(&mut interpreter, "xs.chunk(2)"), "[[5, 3], [8, 1], [4]]");
+        assert_eq!(eval(&mut interpreter, "xs.window(4)"), "[[5, 3, 8, 1], [3, 8, 1, 4]]");
+        assert_eq!(eval(&mut interpreter, "[1, 2, 1.0, 3, 2].unique()"), "[1, 2, 3]");
+        assert_eq!(eval(&mut interpreter, "[1d, 1, 1n, 1.5, 1.5d, [1], [1], {a: 1}, {a: 1}, null, null, \"a\", \"a\"].unique()"), "[1, 1.5, [1], {a: 1}, null, a]");
+        let many: Vec<String> = (0..50_000).map(|i| (i % 20_000).to_string()).collect();
+        assert_eq!(eval(&mut interpreter, &format!("len(unique([{}]))", many.join(", "))), "20000");
+        assert_eq!(eval(&mut interpreter, "xs.group_by(parity).odd"), "[5, 3, 1]");
+        assert_eq!(eval(&mut interpreter, "xs.partition(even)"), "[[8, 4], [5, 3, 1]]");
+
+        assert!(matches!(interpreter.eval("reduce([], add)"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert!(matches!(interpreter.eval("xs.chunk(0)"), Err(NovaError::Runtime(RuntimeError::InvalidOperation(_)))));
+        assert!(matches!(interpreter.eval("xs.map(5)"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+        let thrown = "fn boom(x) { throw \"bad \" + str(x) }\nlet caught = null\ntry { xs.map(boom) } catch (e) { caught = e.message }\ncaught";
+        assert_eq!(eval(&mut interpreter, thrown), "bad 5");
+    }
+
+    #[test]
+    fn test_stable_sorts() {
+        let mut interpreter = Interpreter::new();
+        let eval = |interpreter: &mut Interpreter, source: &str| interpreter.eval(source).unwrap().to_string();
+        interpreter.eval(r#"
+fn first(p) { return p[0] }
+fn descending(a, b) { return b[0] - a[0] }
+let pairs = [[2, "x"], [1, "y"], [2, "a"], [1, "b"]]
+class Version {
+    fn constructor(n) { this.n = n }
+    fn __lt__(other) { return this.n < other.n }
+    fn __str__() { return "v" + str(this.n) }
+}
+"#).unwrap();
+        assert_eq!(eval(&mut interpreter, "pairs.sort_by(first)"), "[[1, y], [1, b], [2, x], [2, a]]");
+        assert_eq!(eval(&mut interpreter, "pairs.sort_with(descending)"), "[[2, x], [2, a], [1, y], [1, b]]");
+        assert_eq!(eval(&mut interpreter, "[\"b\", \"A\", \"c\"].sort_by(lower)"), "[A, b, c]");
+        assert_eq!(eval(&mut interpreter, "sort([3, \"b\", null, 1.5, true, \"a\", [1], 2n])"), "[null, true, 1.5, 2, 3, a, b, [1]]");
+        assert_eq!(eval(&mut interpreter, "str(sort([new Version(3), new Version(1), new Version(2)]))"), "[v1, v2, v3]");
+        assert!(matches!(interpreter.eval("fn bad(a, b) { return \"less\" }\nsort_with([1, 2], bad)"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+    }
+
+    #[test]
+    fn test_objects_keep_insertion_order() {
+        let mut interpreter = Interpreter::new();
+        let eval = |interpreter: &mut Interpreter, source: &str| interpreter.eval(source).unwrap().to_string();
+        interpreter.eval("let o = {zeta: 1, alpha: 2, mid: 3}").unwrap();
+        assert_eq!(eval(&mut interpreter, "o"), "{zeta: 1, alpha: 2, mid: 3}");
+        assert_eq!(eval(&mut interpreter, "[keys(o), o.values(), o.entries()]"), "[[zeta, alpha, mid], [1, 2, 3], [[zeta, 1], [alpha, 2], [mid, 3]]]");
+        assert_eq!(eval(&mut interpreter, "let seen = \"\"\nfor key in o { seen = seen + key[0] }\nseen"), "zam");
+        assert_eq!(eval(&mut interpreter, "json_stringify(o)"), r#"{"zeta":1,"alpha":2,"mid":3}"#);
+        assert_eq!(eval(&mut interpreter, r#"json_parse("{\"z\": 1, \"a\": {\"y\": 2, \"b\": 3}}")"#), "{z: 1, a: {y: 2, b: 3}}");
+        assert_eq!(eval(&mut interpreter, "[o.has(\"mid\"), has(o, \"nope\")]"), "[true, false]");
+        assert_eq!(eval(&mut interpreter, "[delete(o, \"alpha\"), o]"), "[{zeta: 1, mid: 3}, {zeta: 1, alpha: 2, mid: 3}]");
+        assert_eq!(eval(&mut interpreter, "merge(o, {alpha: 20, extra: 4}, {zeta: 0})"), "{zeta: 0, alpha: 20, mid: 3, extra: 4}");
+        assert_eq!(interpreter.eval("{a: 1, b: 2} == {b: 2, a: 1}").unwrap(), Value::Boolean(true));
+        assert!(matches!(interpreter.eval("merge(o, 1)"), Err(NovaError::Runtime(RuntimeError::TypeError(_)))));
+
+        let value: Value = to_value(&serde_json::json!({"second": 2, "first": 1})).unwrap();
+        assert_eq!(value.to_string(), "{second: 2, first: 1}");
+        assert_eq!(serde_json::to_string(&value.to_json()).unwrap(), r#"{"second":2,"first":1}"#);
+    }
+}
@@ -0,0 +1,254 @@
+//! Strings as scripts see them.
+//!
+//! A Nova string is a sequence of grapheme clusters: what a reader would
+//! call a character, such as `é` written as `e` plus a combining accent, or
+//! a flag made of two regional indicators. `len`, indexing, `slice`,
+//! `substr`, `reverse` and `for` loops all count in graphemes, so they never
+//! split a character in two. `chars` gives the code points instead and
+//! `encode` the UTF-8 bytes.
+//!
+//! `NovaString` shares its text between copies and works out where each
+//! grapheme starts the first time it is indexed, so indexing a string in a
+//! loop costs O(1) per access rather than a scan from the start. Pure ASCII
+//! text needs no table at all.
+
+use std::cell::{Cell, OnceCell};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::rc::Rc;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Where the graphemes of a string start.
+#[derive(Debug)]
+enum Boundaries {
+    /// Every byte is a grapheme.
+    Ascii,
+    /// The byte offset of each grapheme, followed by the length of the text.
+    Offsets(Box<[usize]>),
+}
+
+thread_local! {
+    static TABLE_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The bytes taken by grapheme tables built since the last call, for the
+/// interpreter to charge to its memory budget.
+pub fn take_table_bytes() -> usize {
+    TABLE_BYTES.with(Cell::take)
+}
+
+/// An immutable string with cached grapheme boundaries. Cloning is cheap:
+/// copies share the text and the boundaries.
+#[derive(Clone, Default)]
+pub struct NovaString {
+    text: Rc<str>,
+    boundaries: Rc<OnceCell<Boundaries>>,
+}
+
+impl NovaString {
+    pub fn new(text: &str) -> Self {
+        NovaString { text: Rc::from(text), boundaries: Rc::default() }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    fn boundaries(&self) -> &Boundaries {
+        self.boundaries.get_or_init(|| {
+            // "\r\n" is a single grapheme, the only ASCII sequence that is
+            if self.text.is_ascii() && !self.text.contains("\r\n") {
+                Boundaries::Ascii
+            } else {
+                let offsets = self.text.grapheme_indices(true).map(|(offset, _)| offset).chain([self.text.len()]);
+                let offsets: Box<[usize]> = offsets.collect();
+                TABLE_BYTES.with(|bytes| bytes.set(bytes.get() + std::mem::size_of_val(&*offsets)));
+                Boundaries::Offsets(offsets)
+            }
+        })
+    }
+
+    /// The bytes taken by the grapheme table, once it has been built.
+    pub fn table_size(&self) -> usize {
+        match self.boundaries.get() {
+            Some(Boundaries::Offsets(offsets)) => std::mem::size_of_val(&**offsets),
+            _ => 0,
+        }
+    }
+
+    /// The number of graphemes.
+    pub fn grapheme_count(&self) -> usize {
+        match self.boundaries() {
+            Boundaries::Ascii => self.text.len(),
+            Boundaries::Offsets(offsets) => offsets.len() - 1,
+        }
+    }
+
+    /// The byte offset where grapheme `index` starts; `grapheme_count()`
+    /// gives the end of the text.
+    fn offset(&self, index: usize) -> usize {
+        match self.boundaries() {
+            Boundaries::Ascii => index,
+            Boundaries::Offsets(offsets) => offsets[index],
+        }
+    }
+
+    /// Grapheme `index`, if there is one.
+    pub fn grapheme(&self, index: usize) -> Option<&str> {
+        (index < self.grapheme_count()).then(|| &self.text[self.offset(index)..self.offset(index + 1)])
+    }
+
+    /// Graphemes `start..end`, which must be in bounds.
+    pub fn graphemes(&self, start: usize, end: usize) -> &str {
+        &self.text[self.offset(start)..self.offset(end)]
+    }
+
+    /// The columns the text takes up in a terminal: East Asian wide
+    /// characters and most emoji take two, combining marks none.
+    pub fn width(&self) -> usize {
+        self.text.width()
+    }
+}
+
+/// The text with its graphemes in reverse order.
+pub fn reverse_graphemes(text: &str) -> String {
+    text.graphemes(true).rev().collect()
+}
+
+/// Each grapheme of `text`.
+pub fn split_graphemes(text: &str) -> impl Iterator<Item = &str> {
+    text.graphemes(true)
+}
+
+/// Unicode default case folding (`CaseFolding.txt`, full mappings), for
+/// comparing strings without regard to case:
+/// `casefold("Straße") == casefold("STRASSE")`.
+pub fn casefold(text: &str) -> String {
+    caseless::default_case_fold_str(text)
+}
+
+/// `text` in Unicode normalization form `form`: NFC, NFD, NFKC or NFKD.
+pub fn normalize(text: &str, form: &str) -> Result<String, String> {
+    match form.to_ascii_uppercase().as_str() {
+        "NFC" => Ok(text.nfc().collect()),
+        "NFD" => Ok(text.nfd().collect()),
+        "NFKC" => Ok(text.nfkc().collect()),
+        "NFKD" => Ok(text.nfkd().collect()),
+        _ => Err(format!("Unknown normalization form '{}', expected one of: NFC, NFD, NFKC, NFKD", form)),
+    }
+}
+
+/// Display width of `text`; see `NovaString::width`.
+pub fn display_width(text: &str) -> usize {
+    text.width()
+}
+
+impl Deref for NovaString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl AsRef<str> for NovaString {
+    fn as_ref(&self) -> &str {
+        &self.text
+    }
+}
+
+impl AsRef<[u8]> for NovaString {
+    fn as_ref(&self) -> &[u8] {
+        self.text.as_bytes()
+    }
+}
+
+impl AsRef<std::path::Path> for NovaString {
+    fn as_ref(&self) -> &std::path::Path {
+        std::path::Path::new(&*self.text)
+    }
+}
+
+impl From<String> for NovaString {
+    fn from(text: String) -> Self {
+        NovaString { text: Rc::from(text), boundaries: Rc::default() }
+    }
+}
+
+impl From<char> for NovaString {
+    fn from(c: char) -> Self {
+        NovaString::new(c.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl From<&str> for NovaString {
+    fn from(text: &str) -> Self {
+        NovaString::new(text)
+    }
+}
+
+impl From<NovaString> for String {
+    fn from(text: NovaString) -> Self {
+        text.as_str().to_string()
+    }
+}
+
+impl PartialEq for NovaString {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl Eq for NovaString {}
+
+impl PartialEq<str> for NovaString {
+    fn eq(&self, other: &str) -> bool {
+        &*self.text == other
+    }
+}
+
+impl PartialEq<&str> for NovaString {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.text == *other
+    }
+}
+
+impl PartialOrd for NovaString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for NovaString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.text.cmp(&other.text)
+    }
+}
+
+impl Hash for NovaString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.text.hash(state)
+    }
+}
+
+impl serde::Serialize for NovaString {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
+}
+
+impl fmt::Display for NovaString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&*self.text, f)
+    }
+}
+
+impl fmt::Debug for NovaString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.text, f)
+    }
+}
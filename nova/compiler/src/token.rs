@@ -2,14 +2,19 @@
 pub enum Token {
     // Literals
     Identifier(String),
+    Integer(i64),
+    BigInt(num_bigint::BigInt),
+    Decimal(crate::decimal::Decimal),
     Number(f64),
     String(String),
+    Bytes(Vec<u8>),
     
     // Arithmetic operators
     Plus,
     Minus,
     Star,
     Slash,
+    TildeSlash,    // ~/
     Percent,
     DoubleStar,    // **
     
@@ -79,7 +84,9 @@ pub enum Token {
     From,
     Export,
     Class,
+    Trait,
     Extends,
+    InstanceOf,
     Super,
     This,
     Static,
@@ -97,6 +104,7 @@ pub enum Token {
     StringEnd(String),    // end part of interpolated string
     InterpolationStart,   // ${
     InterpolationEnd,     // }
+    FormatSpec(String),   // `.2f` in ${price:.2f}
     
     // Special tokens
     Newline,
@@ -132,7 +140,9 @@ impl Token {
             "from" => Some(Token::From),
             "export" => Some(Token::Export),
             "class" => Some(Token::Class),
+            "trait" => Some(Token::Trait),
             "extends" => Some(Token::Extends),
+            "instanceof" => Some(Token::InstanceOf),
             "super" => Some(Token::Super),
             "this" => Some(Token::This),
             "static" => Some(Token::Static),
@@ -159,10 +169,10 @@ impl Token {
 
     pub fn is_binary_operator(&self) -> bool {
         matches!(self,
-            Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent |
+            Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::TildeSlash | Token::Percent |
             Token::DoubleStar | Token::EqualEqual | Token::BangEqual | Token::Less |
-            Token::Greater | Token::LessEqual | Token::GreaterEqual | Token::And |
-            Token::Or | Token::Ampersand | Token::Pipe | Token::Caret |
+            Token::Greater | Token::LessEqual | Token::GreaterEqual | Token::InstanceOf |
+            Token::And | Token::Or | Token::Ampersand | Token::Pipe | Token::Caret |
             Token::LeftShift | Token::RightShift
         )
     }
@@ -179,10 +189,10 @@ impl Token {
             Token::Caret => Some(4),
             Token::Ampersand => Some(5),
             Token::EqualEqual | Token::BangEqual => Some(6),
-            Token::Less | Token::Greater | Token::LessEqual | Token::GreaterEqual => Some(7),
+            Token::Less | Token::Greater | Token::LessEqual | Token::GreaterEqual | Token::InstanceOf => Some(7),
             Token::LeftShift | Token::RightShift => Some(8),
             Token::Plus | Token::Minus => Some(9),
-            Token::Star | Token::Slash | Token::Percent => Some(10),
+            Token::Star | Token::Slash | Token::TildeSlash | Token::Percent => Some(10),
             Token::DoubleStar => Some(11),
             _ => None,
         }
@@ -193,12 +203,17 @@ impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Token::Identifier(name) => write!(f, "identifier '{}'", name),
+            Token::Integer(n) => write!(f, "integer '{}'", n),
+            Token::BigInt(n) => write!(f, "big integer '{}n'", n),
+            Token::Decimal(n) => write!(f, "decimal '{}d'", n),
             Token::Number(n) => write!(f, "number '{}'", n),
             Token::String(s) => write!(f, "string \"{}\"", s),
+            Token::Bytes(b) => write!(f, "bytes {}", crate::Value::Bytes(b.clone())),
             Token::Plus => write!(f, "'+'"),
             Token::Minus => write!(f, "'-'"),
             Token::Star => write!(f, "'*'"),
             Token::Slash => write!(f, "'/'"),
+            Token::TildeSlash => write!(f, "'~/'"),
             Token::Percent => write!(f, "'%'"),
             Token::DoubleStar => write!(f, "'**'"),
             Token::Equal => write!(f, "'='"),
@@ -211,6 +226,7 @@ impl std::fmt::Display for Token {
             Token::GreaterEqual => write!(f, "'>='"),
             Token::And => write!(f, "'and'"),
             Token::Or => write!(f, "'or'"),
+            Token::InstanceOf => write!(f, "'instanceof'"),
             Token::LeftParen => write!(f, "'('"),
             Token::RightParen => write!(f, "')'"),
             Token::LeftBrace => write!(f, "'{{'"),
@@ -0,0 +1,93 @@
+//! Stack traces for runtime errors.
+//!
+//! The interpreter keeps a frame for every call to a Nova function, method
+//! or constructor, recording the name it was called by and where the call
+//! happened. When an error unwinds out of a frame the stack is captured, so
+//! it can be printed for uncaught errors and handed to `catch` blocks as
+//! the `stack` field of error instances.
+
+use crate::ast::SourceLocation;
+use crate::value::Value;
+use indexmap::IndexMap;
+use std::fmt;
+
+/// Traces deeper than this are printed with the middle frames elided.
+const MAX_PRINTED_FRAMES: usize = 20;
+/// How many of the outermost frames are still printed for elided traces.
+const OUTERMOST_PRINTED_FRAMES: usize = 4;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+    pub function: String,
+    /// Where the function was called from.
+    pub call_site: SourceLocation,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.call_site.line == 0 {
+            write!(f, "at {}", self.function)
+        } else {
+            write!(f, "at {} (called at {})", self.function, self.call_site)
+        }
+    }
+}
+
+/// A captured call stack, innermost frame first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StackTrace {
+    frames: Vec<StackFrame>,
+}
+
+impl StackTrace {
+    pub fn new(frames: Vec<StackFrame>) -> Self {
+        StackTrace { frames }
+    }
+
+    pub fn frames(&self) -> &[StackFrame] {
+        &self.frames
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// The trace as scripts see it in `e.stack`: an array of
+    /// `{ function, line, column }` objects, innermost first.
+    pub fn to_value(&self) -> Value {
+        let frames = self.frames.iter().map(|frame| {
+            let mut object = IndexMap::from([
+                ("function".to_string(), Value::String(frame.function.clone().into())),
+                ("line".to_string(), Value::Integer(frame.call_site.line as i64)),
+                ("column".to_string(), Value::Integer(frame.call_site.column as i64)),
+            ]);
+            if let Some(file) = &frame.call_site.file {
+                object.insert("file".to_string(), Value::String(file.clone().into()));
+            }
+            Value::Object(object)
+        });
+        Value::Array(frames.collect())
+    }
+}
+
+impl fmt::Display for StackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.frames.len();
+        let innermost = if total > MAX_PRINTED_FRAMES {
+            MAX_PRINTED_FRAMES - OUTERMOST_PRINTED_FRAMES
+        } else {
+            total
+        };
+
+        for frame in &self.frames[..innermost] {
+            writeln!(f, "    {}", frame)?;
+        }
+        if innermost < total {
+            writeln!(f, "    ... {} more frames", total - innermost - OUTERMOST_PRINTED_FRAMES)?;
+            for frame in &self.frames[total - OUTERMOST_PRINTED_FRAMES..] {
+                writeln!(f, "    {}", frame)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,14 +1,37 @@
 use crate::ast::Expr;
-use std::collections::HashMap;
+use crate::decimal::Decimal;
+use crate::embed::HostObjectRef;
+use crate::text::NovaString;
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::rc::Rc;
 
-#[derive(Debug, Clone, PartialEq)]
+/// Arity of native functions that accept any number of arguments.
+pub const VARIADIC: usize = usize::MAX;
+
+#[derive(Debug, Clone)]
 pub enum Value {
+    /// A 64-bit integer. Integer literals produce these; arithmetic on them
+    /// is exact and raises `OverflowError` rather than wrapping.
+    Integer(i64),
+    /// An integer of any size, written `123n` or produced when integer
+    /// arithmetic overflows 64 bits.
+    BigInt(BigInt),
+    /// An exact decimal number, written `12.34d`.
+    Decimal(Decimal),
     Number(f64),
-    String(String),
+    String(NovaString),
+    /// An immutable byte string, written `b"..."`. Indexing yields integers
+    /// from 0 to 255.
+    Bytes(Vec<u8>),
     Boolean(bool),
     Array(Vec<Value>),
-    Object(HashMap<String, Value>),
+    /// Fields in the order they were first added.
+    Object(IndexMap<String, Value>),
     Function {
         params: Vec<String>,
         body: Expr,
@@ -18,17 +41,42 @@ pub enum Value {
         name: String,
         arity: usize,
     },
+    /// A function bound to a receiver, produced by reading a method off an
+    /// instance or class, or by `bind`. Calls run `method` with `this` set
+    /// to `receiver`, after any arguments bound in advance.
+    BoundMethod {
+        receiver: Box<Value>,
+        method: Box<Value>,
+        args: Vec<Value>,
+    },
     Class {
         name: String,
         superclass: Option<Box<Value>>,
         methods: HashMap<String, Value>,
         static_methods: HashMap<String, Value>,
         constructor: Option<Box<Value>>,
+        /// Instance fields in declaration order, each with its initializer:
+        /// a function run with `this` bound to every new instance.
+        fields: Vec<(String, Option<Value>)>,
+        /// Static fields, shared by every copy of the class value.
+        static_fields: Rc<RefCell<HashMap<String, Value>>>,
+        /// Members that can only be accessed from inside the class.
+        private_members: HashSet<String>,
+        /// The traits the class implements.
+        traits: Vec<Value>,
+    },
+    Trait {
+        name: String,
+        required: Vec<String>,
+        methods: HashMap<String, Value>,
     },
+    /// Instances are shared: copies of an instance value all refer to the
+    /// same fields, so mutations through `this` or any alias are visible everywhere.
     Instance {
-        class: Box<Value>,
-        fields: HashMap<String, Value>,
+        class: Rc<Value>,
+        fields: Rc<RefCell<HashMap<String, Value>>>,
     },
+    HostObject(HostObjectRef),
     Null,
 }
 
@@ -36,87 +84,224 @@ impl Value {
     // Class-specific methods
     pub fn get_method(&self, name: &str) -> Option<Value> {
         match self {
-            Value::Class { methods, static_methods, .. } => {
-                methods.get(name).cloned().or_else(|| static_methods.get(name).cloned())
-            }
-            Value::Instance { class, .. } => {
-                if let Value::Class { methods, .. } = class.as_ref() {
-                    methods.get(name).cloned()
-                } else {
-                    None
-                }
-            }
+            Value::Class { .. } => self.find_method(name).or_else(|| self.find_static_method(name)),
+            Value::Instance { class, .. } => class.find_method(name),
+            _ => None,
+        }
+    }
+
+    /// Looks a method up in this class and then in its superclasses.
+    pub fn find_method(&self, name: &str) -> Option<Value> {
+        match self {
+            Value::Class { methods, superclass, .. } => methods
+                .get(name)
+                .cloned()
+                .or_else(|| superclass.as_ref()?.find_method(name)),
+            _ => None,
+        }
+    }
+
+    /// Like `find_method`, for static methods.
+    pub fn find_static_method(&self, name: &str) -> Option<Value> {
+        match self {
+            Value::Class { static_methods, superclass, .. } => static_methods
+                .get(name)
+                .cloned()
+                .or_else(|| superclass.as_ref()?.find_static_method(name)),
             _ => None,
         }
     }
 
     pub fn get_field(&self, name: &str) -> Option<Value> {
         match self {
-            Value::Instance { fields, .. } => fields.get(name).cloned(),
+            Value::Instance { fields, .. } => fields.borrow().get(name).cloned(),
             _ => None,
         }
     }
 
-    pub fn set_field(&mut self, name: String, value: Value) -> Result<(), String> {
+    pub fn set_field(&self, name: String, value: Value) -> Result<(), String> {
         match self {
             Value::Instance { fields, .. } => {
-                fields.insert(name, value);
+                fields.borrow_mut().insert(name, value);
                 Ok(())
             }
             _ => Err("Cannot set field on non-instance".to_string()),
         }
     }
 
-    pub fn is_instance_of(&self, class: &Value) -> bool {
+    /// For a class, the static field `name` it declares or inherits.
+    pub fn get_static_field(&self, name: &str) -> Option<Value> {
+        match self {
+            Value::Class { static_fields, superclass, .. } => static_fields
+                .borrow()
+                .get(name)
+                .cloned()
+                .or_else(|| superclass.as_ref()?.get_static_field(name)),
+            _ => None,
+        }
+    }
+
+    /// Assigns a static field on the class that declares it, or on this
+    /// class if no class in the chain does. Returns whether it is new.
+    pub fn set_static_field(&self, name: &str, value: Value) -> bool {
+        let Value::Class { static_fields, superclass, .. } = self else {
+            return false;
+        };
+        let declared_here = static_fields.borrow().contains_key(name);
+        match superclass {
+            Some(superclass) if !declared_here && superclass.get_static_field(name).is_some() => {
+                superclass.set_static_field(name, value)
+            }
+            _ => static_fields.borrow_mut().insert(name.to_string(), value).is_none(),
+        }
+    }
+
+    /// For a class, whether `member` is private to it or to a superclass.
+    pub fn is_private_member(&self, member: &str) -> bool {
+        match self {
+            Value::Class { private_members, superclass, .. } => {
+                private_members.contains(member) || superclass.as_ref().is_some_and(|superclass| superclass.is_private_member(member))
+            }
+            _ => false,
+        }
+    }
+
+    /// For a class, whether it is `class` or one of its subclasses. Classes
+    /// are compared by identity, so two classes with the same name differ.
+    pub fn is_or_extends(&self, class: &Value) -> bool {
         match (self, class) {
-            (Value::Instance { class: instance_class, .. }, Value::Class { name, .. }) => {
-                if let Value::Class { name: instance_class_name, .. } = instance_class.as_ref() {
-                    instance_class_name == name
-                } else {
-                    false
-                }
+            (Value::Class { static_fields, superclass, .. }, Value::Class { static_fields: other, .. }) => {
+                Rc::ptr_eq(static_fields, other) || superclass.as_ref().is_some_and(|superclass| superclass.is_or_extends(class))
+            }
+            _ => false,
+        }
+    }
+
+    /// For a class, the constructor it defines or inherits.
+    pub fn find_constructor(&self) -> Option<Value> {
+        match self {
+            Value::Class { constructor: Some(constructor), .. } => Some(constructor.as_ref().clone()),
+            Value::Class { superclass: Some(superclass), .. } => superclass.find_constructor(),
+            _ => None,
+        }
+    }
+
+    /// Whether this is the class `class_name` or one of its subclasses.
+    pub fn extends(&self, class_name: &str) -> bool {
+        match self {
+            Value::Class { name, superclass, .. } => {
+                name == class_name || superclass.as_ref().is_some_and(|parent| parent.extends(class_name))
             }
             _ => false,
         }
     }
 
+    /// Whether this class or one of its superclasses implements the trait `trait_name`.
+    pub fn implements(&self, trait_name: &str) -> bool {
+        match self {
+            Value::Class { traits, superclass, .. } => {
+                traits.iter().any(|implemented| matches!(implemented, Value::Trait { name, .. } if name == trait_name))
+                    || superclass.as_ref().is_some_and(|parent| parent.implements(trait_name))
+            }
+            _ => false,
+        }
+    }
+
+    /// Whether this is an instance of `Error` or one of its subclasses.
+    pub fn is_error(&self) -> bool {
+        matches!(self, Value::Instance { class, .. } if class.extends("Error"))
+    }
+
+    /// Whether this is an instance of `class` or of one of its subclasses,
+    /// or, if `class` is a trait, of a class implementing it.
+    pub fn is_instance_of(&self, class: &Value) -> bool {
+        match (self, class) {
+            (Value::Instance { class: instance_class, .. }, Value::Class { name, .. }) => instance_class.extends(name),
+            (Value::Instance { class: instance_class, .. }, Value::Trait { name, .. }) => instance_class.implements(name),
+            _ => false,
+        }
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             Value::Boolean(b) => *b,
             Value::Null => false,
+            Value::Integer(n) => *n != 0,
+            Value::BigInt(n) => !n.is_zero(),
+            Value::Decimal(n) => !n.is_zero(),
             Value::Number(n) => *n != 0.0,
             Value::String(s) => !s.is_empty(),
+            Value::Bytes(b) => !b.is_empty(),
             Value::Array(arr) => !arr.is_empty(),
             Value::Object(obj) => !obj.is_empty(),
-            Value::Function { .. } | Value::NativeFunction { .. } => true,
-            Value::Class { .. } => true,
+            Value::Function { .. } | Value::NativeFunction { .. } | Value::BoundMethod { .. } => true,
+            Value::Class { .. } | Value::Trait { .. } => true,
             Value::Instance { .. } => true,
+            Value::HostObject(_) => true,
         }
     }
 
     pub fn type_name(&self) -> &'static str {
         match self {
-            Value::Number(_) => "number",
+            Value::Integer(_) | Value::BigInt(_) | Value::Decimal(_) | Value::Number(_) => "number",
             Value::String(_) => "string",
+            Value::Bytes(_) => "bytes",
             Value::Boolean(_) => "boolean",
             Value::Array(_) => "array",
             Value::Object(_) => "object",
             Value::Function { .. } => "function",
             Value::NativeFunction { .. } => "function",
+            Value::BoundMethod { .. } => "function",
             Value::Class { .. } => "class",
+            Value::Trait { .. } => "trait",
             Value::Instance { .. } => "instance",
+            Value::HostObject(_) => "host_object",
             Value::Null => "null",
         }
     }
 
+    /// The value of either kind of number as a float.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Integer(n) => Some(*n as f64),
+            Value::BigInt(n) => n.to_f64(),
+            Value::Decimal(n) => Some(n.to_f64()),
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The value of an integer, or of any other number with no fractional
+    /// part, if it fits in 64 bits.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Integer(n) => Some(*n),
+            Value::BigInt(n) => n.to_i64(),
+            Value::Decimal(n) => n.to_i64(),
+            Value::Number(n) => crate::numeric::float_to_i64(*n),
+            _ => None,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Integer(_) | Value::BigInt(_) | Value::Decimal(_) | Value::Number(_))
+    }
+
     pub fn is_callable(&self) -> bool {
-        matches!(self, Value::Function { .. } | Value::NativeFunction { .. } | Value::Class { .. })
+        matches!(self, Value::Function { .. } | Value::NativeFunction { .. } | Value::BoundMethod { .. } | Value::Class { .. })
     }
 
     pub fn to_json(&self) -> serde_json::Value {
         match self {
+            Value::Integer(n) => serde_json::json!(*n),
+            // Written out digit for digit, so no precision is lost
+            Value::BigInt(_) | Value::Decimal(_) => match self.to_string().parse() {
+                Ok(number) => serde_json::Value::Number(number),
+                Err(_) => serde_json::Value::Null,
+            },
             Value::Number(n) => serde_json::json!(*n),
             Value::String(s) => serde_json::json!(s),
+            Value::Bytes(b) => serde_json::json!(b),
             Value::Boolean(b) => serde_json::json!(*b),
             Value::Array(arr) => {
                 let json_arr: Vec<serde_json::Value> = arr.iter().map(|v| v.to_json()).collect();
@@ -134,20 +319,35 @@ impl Value {
         }
     }
 
+    /// Integers become big integers when they don't fit in 64 bits, and
+    /// other numbers become decimals when a float can't hold them exactly.
+    fn from_json_number(number: &serde_json::Number) -> Value {
+        if let Some(n) = number.as_i64() {
+            return Value::Integer(n);
+        }
+        let text = number.to_string();
+        if let Ok(n) = text.parse::<BigInt>() {
+            return Value::BigInt(n);
+        }
+        match (number.as_f64(), text.parse::<Decimal>()) {
+            (Some(float), Ok(decimal)) if Decimal::from_f64(float).as_ref() != Some(&decimal) => Value::Decimal(decimal),
+            (Some(float), _) => Value::Number(float),
+            (None, Ok(decimal)) => Value::Decimal(decimal),
+            (None, Err(_)) => Value::Null,
+        }
+    }
+
     pub fn from_json(json: &serde_json::Value) -> Value {
         match json {
-            serde_json::Value::Number(n) => Value::Number(n.as_f64().unwrap_or(0.0)),
-            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Number(n) => Value::from_json_number(n),
+            serde_json::Value::String(s) => Value::String(s.clone().into()),
             serde_json::Value::Bool(b) => Value::Boolean(*b),
             serde_json::Value::Array(arr) => {
-                let nova_arr: Vec<Value> = arr.iter().map(|v| Value::from_json(v)).collect();
+                let nova_arr: Vec<Value> = arr.iter().map(Value::from_json).collect();
                 Value::Array(nova_arr)
             }
             serde_json::Value::Object(obj) => {
-                let mut nova_obj = HashMap::new();
-                for (k, v) in obj {
-                    nova_obj.insert(k.clone(), Value::from_json(v));
-                }
+                let nova_obj = obj.iter().map(|(k, v)| (k.clone(), Value::from_json(v))).collect();
                 Value::Object(nova_obj)
             }
             serde_json::Value::Null => Value::Null,
@@ -155,17 +355,100 @@ impl Value {
     }
 }
 
+/// Numbers compare by value whatever their kind, so `1 == 1.0` and
+/// `1n == 1.00d`; every other variant compares structurally.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Integer(_) | Value::BigInt(_) | Value::Decimal(_) | Value::Number(_), _) if other.is_number() => {
+                crate::numeric::compare(self, other) == Some(std::cmp::Ordering::Equal)
+            }
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => a == b,
+            (
+                Value::Function { params, body, closure },
+                Value::Function { params: other_params, body: other_body, closure: other_closure },
+            ) => params == other_params && body == other_body && closure == other_closure,
+            (
+                Value::NativeFunction { name, arity },
+                Value::NativeFunction { name: other_name, arity: other_arity },
+            ) => name == other_name && arity == other_arity,
+            (
+                Value::BoundMethod { receiver, method, args },
+                Value::BoundMethod { receiver: other_receiver, method: other_method, args: other_args },
+            ) => receiver == other_receiver && method == other_method && args == other_args,
+            (
+                Value::Class { name, superclass, methods, static_methods, constructor, fields, static_fields, private_members, traits },
+                Value::Class {
+                    name: other_name,
+                    superclass: other_superclass,
+                    methods: other_methods,
+                    static_methods: other_static_methods,
+                    constructor: other_constructor,
+                    fields: other_fields,
+                    static_fields: other_static_fields,
+                    private_members: other_private_members,
+                    traits: other_traits,
+                },
+            ) => {
+                name == other_name
+                    && superclass == other_superclass
+                    && methods == other_methods
+                    && static_methods == other_static_methods
+                    && constructor == other_constructor
+                    && fields == other_fields
+                    && static_fields == other_static_fields
+                    && private_members == other_private_members
+                    && traits == other_traits
+            }
+            (
+                Value::Trait { name, required, methods },
+                Value::Trait { name: other_name, required: other_required, methods: other_methods },
+            ) => name == other_name && required == other_required && methods == other_methods,
+            (Value::Instance { class, fields }, Value::Instance { class: other_class, fields: other_fields }) => {
+                class == other_class && fields == other_fields
+            }
+            (Value::HostObject(a), Value::HostObject(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            _ => false,
+        }
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
+            Value::Integer(n) => write!(f, "{}", n),
+            Value::BigInt(n) => write!(f, "{}", n),
+            Value::Decimal(n) => write!(f, "{}", n),
             Value::Number(n) => {
-                if n.fract() == 0.0 {
+                if n.fract() == 0.0 && n.abs() < 1e18 {
                     write!(f, "{}", *n as i64)
                 } else {
                     write!(f, "{}", n)
                 }
             }
             Value::String(s) => write!(f, "{}", s),
+            Value::Bytes(bytes) => {
+                write!(f, "b\"")?;
+                for &byte in bytes {
+                    match byte {
+                        b'"' => write!(f, "\\\"")?,
+                        b'\\' => write!(f, "\\\\")?,
+                        b'\n' => write!(f, "\\n")?,
+                        b'\t' => write!(f, "\\t")?,
+                        b'\r' => write!(f, "\\r")?,
+                        0x20..=0x7e => write!(f, "{}", byte as char)?,
+                        _ => write!(f, "\\x{:02x}", byte)?,
+                    }
+                }
+                write!(f, "\"")
+            }
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Array(arr) => {
                 let elements: Vec<String> = arr.iter().map(|v| format!("{}", v)).collect();
@@ -180,68 +463,152 @@ impl fmt::Display for Value {
             Value::Function { params, .. } => {
                 write!(f, "<function({})>", params.join(", "))
             }
+            Value::NativeFunction { name, arity: VARIADIC } => {
+                write!(f, "<native function {}(... args)>", name)
+            }
             Value::NativeFunction { name, arity } => {
                 write!(f, "<native function {}({} args)>", name, arity)
             }
+            Value::BoundMethod { method, .. } => match method.as_ref() {
+                Value::Function { params, .. } => write!(f, "<bound function({})>", params.join(", ")),
+                other => write!(f, "<bound {}>", other),
+            },
             Value::Class { name, .. } => {
                 write!(f, "<class {}>", name)
             }
-            Value::Instance { class, .. } => {
-                if let Value::Class { name, .. } = class.as_ref() {
+            Value::Trait { name, .. } => {
+                write!(f, "<trait {}>", name)
+            }
+            Value::Instance { class, fields } => {
+                if let (true, Some(Value::String(message))) = (self.is_error(), fields.borrow().get("message")) {
+                    let name = match fields.borrow().get("name") {
+                        Some(Value::String(name)) => name.clone(),
+                        _ => "Error".to_string().into(),
+                    };
+                    write!(f, "{}: {}", name, message)
+                } else if let Value::Class { name, .. } = class.as_ref() {
                     write!(f, "<{} instance>", name)
                 } else {
                     write!(f, "<instance>")
                 }
             }
+            Value::HostObject(host) => write!(f, "<{}>", host.borrow().type_name()),
             Value::Null => write!(f, "null"),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A lexical scope. Cloning an `Environment` yields another handle to the
+/// same scope, so closures, callers and the host all observe each other's
+/// assignments.
+#[derive(Clone)]
 pub struct Environment {
-    pub vars: HashMap<String, Value>,
-    pub parent: Option<Box<Environment>>,
+    scope: Rc<RefCell<Scope>>,
+}
+
+pub(crate) struct Scope {
+    pub(crate) vars: HashMap<String, Value>,
+    pub(crate) parent: Option<Environment>,
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PartialEq for Environment {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.scope, &other.scope)
+    }
+}
+
+impl fmt::Debug for Environment {
+    // Scopes routinely contain functions that capture the scope itself, so
+    // only the variable names are printed to avoid infinite recursion.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let scope = self.scope.borrow();
+        let mut names: Vec<&String> = scope.vars.keys().collect();
+        names.sort();
+        f.debug_struct("Environment")
+            .field("vars", &names)
+            .field("has_parent", &scope.parent.is_some())
+            .finish()
+    }
 }
 
 impl Environment {
     pub fn new() -> Self {
         Environment {
-            vars: HashMap::new(),
-            parent: None,
+            scope: Rc::new(RefCell::new(Scope {
+                vars: HashMap::new(),
+                parent: None,
+            })),
         }
     }
 
     pub fn with_parent(parent: Environment) -> Self {
         Environment {
-            vars: HashMap::new(),
-            parent: Some(Box::new(parent)),
+            scope: Rc::new(RefCell::new(Scope {
+                vars: HashMap::new(),
+                parent: Some(parent),
+            })),
         }
     }
 
     pub fn define(&mut self, name: String, value: Value) {
-        self.vars.insert(name, value);
+        self.scope.borrow_mut().vars.insert(name, value);
     }
 
     pub fn get(&self, name: &str) -> Option<Value> {
-        self.vars.get(name).cloned().or_else(|| {
-            self.parent
+        let scope = self.scope.borrow();
+        scope.vars.get(name).cloned().or_else(|| {
+            scope.parent
                 .as_ref()
                 .and_then(|parent| parent.get(name))
         })
     }
 
     pub fn set(&mut self, name: &str, value: Value) -> Result<(), String> {
-        if self.vars.contains_key(name) {
-            self.vars.insert(name.to_string(), value);
+        let mut scope = self.scope.borrow_mut();
+        if let Some(slot) = scope.vars.get_mut(name) {
+            *slot = value;
             Ok(())
-        } else if let Some(ref mut parent) = self.parent {
+        } else if let Some(ref mut parent) = scope.parent {
             parent.set(name, value)
         } else {
             Err(format!("Undefined variable: {}", name))
         }
     }
 
+    /// Identifies the underlying scope; all clones of a handle share it.
+    pub(crate) fn id(&self) -> usize {
+        Rc::as_ptr(&self.scope) as *const () as usize
+    }
+
+    pub(crate) fn scope(&self) -> &Rc<RefCell<Scope>> {
+        &self.scope
+    }
+
+    pub(crate) fn parent(&self) -> Option<Environment> {
+        self.scope.borrow().parent.clone()
+    }
+
+    /// Calls `f` with each variable defined directly in this scope.
+    pub(crate) fn for_each_variable(&self, mut f: impl FnMut(&Value)) {
+        for value in self.scope.borrow().vars.values() {
+            f(value);
+        }
+    }
+
+    /// Returns the outermost scope of this chain.
+    pub fn global(&self) -> Environment {
+        match &self.scope.borrow().parent {
+            Some(parent) => parent.global(),
+            None => self.clone(),
+        }
+    }
+
     pub fn define_natives(&mut self) {
         // Core I/O functions
         self.define("print".to_string(), Value::NativeFunction {
@@ -272,9 +639,49 @@ impl Environment {
 
         self.define("num".to_string(), Value::NativeFunction {
             name: "num".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("to_base".to_string(), Value::NativeFunction {
+            name: "to_base".to_string(),
+            arity: 2,
+        });
+
+        self.define("int".to_string(), Value::NativeFunction {
+            name: "int".to_string(),
+            arity: 1,
+        });
+
+        self.define("float".to_string(), Value::NativeFunction {
+            name: "float".to_string(),
+            arity: 1,
+        });
+
+        self.define("bigint".to_string(), Value::NativeFunction {
+            name: "bigint".to_string(),
             arity: 1,
         });
 
+        self.define("decimal".to_string(), Value::NativeFunction {
+            name: "decimal".to_string(),
+            arity: 1,
+        });
+
+        self.define("round".to_string(), Value::NativeFunction {
+            name: "round".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("decimal_context".to_string(), Value::NativeFunction {
+            name: "decimal_context".to_string(),
+            arity: 0,
+        });
+
+        self.define("set_decimal_context".to_string(), Value::NativeFunction {
+            name: "set_decimal_context".to_string(),
+            arity: 2,
+        });
+
         self.define("bool".to_string(), Value::NativeFunction {
             name: "bool".to_string(),
             arity: 1,
@@ -305,6 +712,26 @@ impl Environment {
             name: "values".to_string(),
             arity: 1,
         });
+        
+        self.define("entries".to_string(), Value::NativeFunction {
+            name: "entries".to_string(),
+            arity: 1,
+        });
+        
+        self.define("has".to_string(), Value::NativeFunction {
+            name: "has".to_string(),
+            arity: 2,
+        });
+        
+        self.define("delete".to_string(), Value::NativeFunction {
+            name: "delete".to_string(),
+            arity: 2,
+        });
+        
+        self.define("merge".to_string(), Value::NativeFunction {
+            name: "merge".to_string(),
+            arity: VARIADIC,
+        });
 
         // JSON functions
         self.define("json_parse".to_string(), Value::NativeFunction {
@@ -405,6 +832,101 @@ impl Environment {
             name: "contains".to_string(),
             arity: 2,
         });
+        
+        self.define("map".to_string(), Value::NativeFunction {
+            name: "map".to_string(),
+            arity: 2,
+        });
+        
+        self.define("filter".to_string(), Value::NativeFunction {
+            name: "filter".to_string(),
+            arity: 2,
+        });
+        
+        self.define("reduce".to_string(), Value::NativeFunction {
+            name: "reduce".to_string(),
+            arity: VARIADIC,
+        });
+        
+        self.define("find".to_string(), Value::NativeFunction {
+            name: "find".to_string(),
+            arity: 2,
+        });
+        
+        self.define("any".to_string(), Value::NativeFunction {
+            name: "any".to_string(),
+            arity: 2,
+        });
+        
+        self.define("all".to_string(), Value::NativeFunction {
+            name: "all".to_string(),
+            arity: 2,
+        });
+        
+        self.define("flat_map".to_string(), Value::NativeFunction {
+            name: "flat_map".to_string(),
+            arity: 2,
+        });
+        
+        self.define("zip".to_string(), Value::NativeFunction {
+            name: "zip".to_string(),
+            arity: 2,
+        });
+        
+        self.define("chunk".to_string(), Value::NativeFunction {
+            name: "chunk".to_string(),
+            arity: 2,
+        });
+        
+        self.define("window".to_string(), Value::NativeFunction {
+            name: "window".to_string(),
+            arity: 2,
+        });
+        
+        self.define("unique".to_string(), Value::NativeFunction {
+            name: "unique".to_string(),
+            arity: 1,
+        });
+        
+        self.define("group_by".to_string(), Value::NativeFunction {
+            name: "group_by".to_string(),
+            arity: 2,
+        });
+        
+        self.define("partition".to_string(), Value::NativeFunction {
+            name: "partition".to_string(),
+            arity: 2,
+        });
+        
+        self.define("sort_by".to_string(), Value::NativeFunction {
+            name: "sort_by".to_string(),
+            arity: 2,
+        });
+        
+        self.define("sort_with".to_string(), Value::NativeFunction {
+            name: "sort_with".to_string(),
+            arity: 2,
+        });
+        
+        self.define("chars".to_string(), Value::NativeFunction {
+            name: "chars".to_string(),
+            arity: 1,
+        });
+        
+        self.define("casefold".to_string(), Value::NativeFunction {
+            name: "casefold".to_string(),
+            arity: 1,
+        });
+        
+        self.define("normalize".to_string(), Value::NativeFunction {
+            name: "normalize".to_string(),
+            arity: VARIADIC,
+        });
+        
+        self.define("width".to_string(), Value::NativeFunction {
+            name: "width".to_string(),
+            arity: 1,
+        });
 
         // Array utility functions
         self.define("reverse".to_string(), Value::NativeFunction {
@@ -428,6 +950,43 @@ impl Environment {
             arity: 1,
         });
 
+        // Runtime introspection
+        self.define("memory_usage".to_string(), Value::NativeFunction {
+            name: "memory_usage".to_string(),
+            arity: 0,
+        });
+
+        self.define("class_of".to_string(), Value::NativeFunction {
+            name: "class_of".to_string(),
+            arity: 1,
+        });
+
+        self.define("superclass_of".to_string(), Value::NativeFunction {
+            name: "superclass_of".to_string(),
+            arity: 1,
+        });
+
+        self.define("implements".to_string(), Value::NativeFunction {
+            name: "implements".to_string(),
+            arity: 2,
+        });
+
+        // Calling functions with an explicit 'this'
+        self.define("bind".to_string(), Value::NativeFunction {
+            name: "bind".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("call".to_string(), Value::NativeFunction {
+            name: "call".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("apply".to_string(), Value::NativeFunction {
+            name: "apply".to_string(),
+            arity: 3,
+        });
+
         // File I/O functions
         self.define("exists".to_string(), Value::NativeFunction {
             name: "exists".to_string(),
@@ -459,9 +1018,69 @@ impl Environment {
             name: "regex_find_all".to_string(),
             arity: 2,
         });
+
+        self.define("read_file_bytes".to_string(), Value::NativeFunction {
+            name: "read_file_bytes".to_string(),
+            arity: 1,
+        });
+
+        self.define("write_file_bytes".to_string(), Value::NativeFunction {
+            name: "write_file_bytes".to_string(),
+            arity: 2,
+        });
+
+        self.define("slice".to_string(), Value::NativeFunction {
+            name: "slice".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("format".to_string(), Value::NativeFunction {
+            name: "format".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("bytes".to_string(), Value::NativeFunction {
+            name: "bytes".to_string(),
+            arity: 1,
+        });
+
+        self.define("encode".to_string(), Value::NativeFunction {
+            name: "encode".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("decode".to_string(), Value::NativeFunction {
+            name: "decode".to_string(),
+            arity: VARIADIC,
+        });
+
+        self.define("base64_encode".to_string(), Value::NativeFunction {
+            name: "base64_encode".to_string(),
+            arity: 1,
+        });
+
+        self.define("base64_decode".to_string(), Value::NativeFunction {
+            name: "base64_decode".to_string(),
+            arity: 1,
+        });
+
+        self.define("hex_encode".to_string(), Value::NativeFunction {
+            name: "hex_encode".to_string(),
+            arity: 1,
+        });
+
+        self.define("hex_decode".to_string(), Value::NativeFunction {
+            name: "hex_decode".to_string(),
+            arity: 1,
+        });
+
+        self.define("hash".to_string(), Value::NativeFunction {
+            name: "hash".to_string(),
+            arity: VARIADIC,
+        });
     }
 
     pub fn get_all_variables(&self) -> std::collections::HashMap<String, Value> {
-        self.vars.clone()
+        self.scope.borrow().vars.clone()
     }
 }
\ No newline at end of file
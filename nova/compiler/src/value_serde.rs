@@ -0,0 +1,769 @@
+//! serde support for `Value`.
+//!
+//! `to_value` turns any `Serialize` type into a Nova value and `from_value`
+//! reads any `Deserialize` type back out of one, including class instances,
+//! whose fields are read like an object's. Errors carry the path of the
+//! offending value, e.g. `invalid type: string "x", expected u32 at $.users[2].age`.
+//!
+//! Big integers serialize as 64- or 128-bit integers when they fit and as
+//! strings of digits otherwise; decimals serialize as strings so that no
+//! digits are lost. Either can still be read into an `f64`.
+//!
+//! Bytes serialize with `serialize_bytes` and read back into either a byte
+//! buffer or a `Vec<u8>`.
+
+use crate::value::Value;
+use num_bigint::BigInt;
+use num_traits::ToPrimitive;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Serialize};
+use indexmap::IndexMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+/// A serialization or deserialization error and where in the value it occurred.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerdeError {
+    message: String,
+    path: Vec<PathSegment>,
+}
+
+impl SerdeError {
+    fn new(message: impl Into<String>) -> Self {
+        SerdeError { message: message.into(), path: Vec::new() }
+    }
+
+    // Errors are created at the innermost value and gain one segment per
+    // container while they propagate outwards.
+    fn at_key(mut self, key: &str) -> Self {
+        self.path.insert(0, PathSegment::Key(key.to_string()));
+        self
+    }
+
+    fn at_index(mut self, index: usize) -> Self {
+        self.path.insert(0, PathSegment::Index(index));
+        self
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The location of the error, e.g. `$.users[2].age`.
+    pub fn path(&self) -> String {
+        let mut path = String::from("$");
+        for segment in &self.path {
+            match segment {
+                PathSegment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        path
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.path())
+    }
+}
+
+impl std::error::Error for SerdeError {}
+
+impl ser::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::new(msg.to_string())
+    }
+}
+
+impl de::Error for SerdeError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        SerdeError::new(msg.to_string())
+    }
+}
+
+pub type SerdeResult<T> = Result<T, SerdeError>;
+
+/// Converts any serializable Rust value into a Nova value.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> SerdeResult<Value> {
+    value.serialize(ValueSerializer)
+}
+
+/// Reads a Rust value out of a Nova value.
+pub fn from_value<T: de::DeserializeOwned>(value: &Value) -> SerdeResult<T> {
+    T::deserialize(&detach_instances(value))
+}
+
+/// Copies `value`, turning every instance into an object holding its fields.
+fn detach_instances(value: &Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.iter().map(detach_instances).collect()),
+        Value::Object(fields) => Value::Object(
+            fields.iter().map(|(key, value)| (key.clone(), detach_instances(value))).collect()
+        ),
+        Value::Instance { fields, .. } => Value::Object(
+            fields.borrow().iter().map(|(key, value)| (key.clone(), detach_instances(value))).collect()
+        ),
+        other => other.clone(),
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{SerializeMap, SerializeSeq};
+
+        match self {
+            Value::Integer(n) => serializer.serialize_i64(*n),
+            Value::BigInt(n) => match (n.to_u64(), n.to_i128(), n.to_u128()) {
+                (Some(n), _, _) => serializer.serialize_u64(n),
+                (_, Some(n), _) => serializer.serialize_i128(n),
+                (_, _, Some(n)) => serializer.serialize_u128(n),
+                _ => serializer.serialize_str(&n.to_string()),
+            },
+            Value::Decimal(n) => serializer.serialize_str(&n.to_string()),
+            Value::Number(n) => {
+                if n.fract() == 0.0 && n.abs() < 9_007_199_254_740_992.0 {
+                    serializer.serialize_i64(*n as i64)
+                } else {
+                    serializer.serialize_f64(*n)
+                }
+            }
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Bytes(b) => serializer.serialize_bytes(b),
+            Value::Boolean(b) => serializer.serialize_bool(*b),
+            Value::Null => serializer.serialize_unit(),
+            Value::Array(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items {
+                    seq.serialize_element(item)?;
+                }
+                seq.end()
+            }
+            Value::Object(fields) => {
+                let mut map = serializer.serialize_map(Some(fields.len()))?;
+                for (key, value) in fields {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            }
+            Value::Instance { fields, .. } => Value::Object(fields.borrow().clone().into_iter().collect()).serialize(serializer),
+            _ => Err(ser::Error::custom(format!("cannot serialize a {}", self.type_name()))),
+        }
+    }
+}
+
+impl<'de> de::Deserialize<'de> for Value {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ValueVisitor;
+
+        impl<'de> Visitor<'de> for ValueVisitor {
+            type Value = Value;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("any Nova value")
+            }
+
+            fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+                Ok(Value::Boolean(v))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+                Ok(Value::Integer(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+                Ok(big_integer(BigInt::from(v)))
+            }
+
+            fn visit_i128<E>(self, v: i128) -> Result<Value, E> {
+                Ok(big_integer(BigInt::from(v)))
+            }
+
+            fn visit_u128<E>(self, v: u128) -> Result<Value, E> {
+                Ok(big_integer(BigInt::from(v)))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+                Ok(Value::Number(v))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+                Ok(Value::String(v.to_string().into()))
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Value, E> {
+                Ok(Value::String(v.into()))
+            }
+
+            fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+                Ok(Value::Bytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Value, E> {
+                Ok(Value::Bytes(v))
+            }
+
+            fn visit_unit<E>(self) -> Result<Value, E> {
+                Ok(Value::Null)
+            }
+
+            fn visit_none<E>(self) -> Result<Value, E> {
+                Ok(Value::Null)
+            }
+
+            fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+                de::Deserialize::deserialize(deserializer)
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(Value::Array(items))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+                let mut fields = IndexMap::new();
+                while let Some((key, value)) = map.next_entry::<String, Value>()? {
+                    fields.insert(key, value);
+                }
+                Ok(Value::Object(fields))
+            }
+        }
+
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// A `serde::Serializer` whose output is a Nova `Value`.
+pub struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = SerdeError;
+    type SerializeSeq = SerializeArray;
+    type SerializeTuple = SerializeArray;
+    type SerializeTupleStruct = SerializeArray;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray>;
+    type SerializeMap = SerializeObject;
+    type SerializeStruct = SerializeObject;
+    type SerializeStructVariant = SerializeVariant<SerializeObject>;
+
+    fn serialize_bool(self, v: bool) -> SerdeResult<Value> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> SerdeResult<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_i16(self, v: i16) -> SerdeResult<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_i32(self, v: i32) -> SerdeResult<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_i64(self, v: i64) -> SerdeResult<Value> {
+        Ok(Value::Integer(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> SerdeResult<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_u16(self, v: u16) -> SerdeResult<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_u32(self, v: u32) -> SerdeResult<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_u64(self, v: u64) -> SerdeResult<Value> {
+        Ok(big_integer(BigInt::from(v)))
+    }
+
+    fn serialize_i128(self, v: i128) -> SerdeResult<Value> {
+        Ok(big_integer(BigInt::from(v)))
+    }
+
+    fn serialize_u128(self, v: u128) -> SerdeResult<Value> {
+        Ok(big_integer(BigInt::from(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> SerdeResult<Value> {
+        Ok(Value::Number(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> SerdeResult<Value> {
+        Ok(Value::Number(v))
+    }
+
+    fn serialize_char(self, v: char) -> SerdeResult<Value> {
+        Ok(Value::String(v.to_string().into()))
+    }
+
+    fn serialize_str(self, v: &str) -> SerdeResult<Value> {
+        Ok(Value::String(v.to_string().into()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> SerdeResult<Value> {
+        Ok(Value::Bytes(v.to_vec()))
+    }
+
+    fn serialize_none(self) -> SerdeResult<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> SerdeResult<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> SerdeResult<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> SerdeResult<Value> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> SerdeResult<Value> {
+        Ok(Value::String(variant.to_string().into()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> SerdeResult<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> SerdeResult<Value> {
+        let inner = value.serialize(ValueSerializer).map_err(|e| e.at_key(variant))?;
+        let mut object = IndexMap::new();
+        object.insert(variant.to_string(), inner);
+        Ok(Value::Object(object))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> SerdeResult<SerializeArray> {
+        Ok(SerializeArray { items: Vec::with_capacity(len.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, len: usize) -> SerdeResult<SerializeArray> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> SerdeResult<SerializeArray> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> SerdeResult<SerializeVariant<SerializeArray>> {
+        Ok(SerializeVariant { variant, inner: self.serialize_seq(Some(len))? })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> SerdeResult<SerializeObject> {
+        Ok(SerializeObject { fields: IndexMap::new(), next_key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> SerdeResult<SerializeObject> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> SerdeResult<SerializeVariant<SerializeObject>> {
+        Ok(SerializeVariant { variant, inner: self.serialize_map(Some(len))? })
+    }
+}
+
+pub struct SerializeArray {
+    items: Vec<Value>,
+}
+
+impl SerializeArray {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        let index = self.items.len();
+        self.items.push(value.serialize(ValueSerializer).map_err(|e| e.at_index(index))?);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> SerdeResult<Value> {
+        Ok(Value::Array(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> SerdeResult<Value> {
+        Ok(Value::Array(self.items))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> SerdeResult<Value> {
+        Ok(Value::Array(self.items))
+    }
+}
+
+pub struct SerializeObject {
+    fields: IndexMap<String, Value>,
+    next_key: Option<String>,
+}
+
+impl SerializeObject {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> SerdeResult<()> {
+        let value = value.serialize(ValueSerializer).map_err(|e| e.at_key(&key))?;
+        self.fields.insert(key, value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeObject {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> SerdeResult<()> {
+        // Object keys are strings; numbers and booleans are accepted and
+        // converted the same way `str()` would print them.
+        let key = match key.serialize(ValueSerializer)? {
+            Value::String(s) => s.to_string(),
+            key @ (Value::Integer(_) | Value::Number(_) | Value::Boolean(_)) => key.to_string(),
+            other => return Err(SerdeError::new(format!("object keys must be strings, got {}", other.type_name()))),
+        };
+        self.next_key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| SerdeError::new("serialize_value called before serialize_key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> SerdeResult<Value> {
+        Ok(Value::Object(self.fields))
+    }
+}
+
+impl ser::SerializeStruct for SerializeObject {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> SerdeResult<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> SerdeResult<Value> {
+        // With exact JSON numbers, serde_json serializes a number as a
+        // struct holding its digits
+        if let (1, Some(Value::String(digits))) = (self.fields.len(), self.fields.get(JSON_NUMBER_TOKEN)) {
+            let number = digits.parse().map_err(|_| SerdeError::new(format!("invalid JSON number '{}'", digits)))?;
+            return Ok(Value::from_json(&serde_json::Value::Number(number)));
+        }
+        Ok(Value::Object(self.fields))
+    }
+}
+
+/// The struct name and field serde_json uses for its exact numbers.
+const JSON_NUMBER_TOKEN: &str = "$serde_json::private::Number";
+
+/// Enum variants with data are represented as `{ variant: data }`.
+pub struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &'static str, inner: Value) -> SerdeResult<Value> {
+        let mut object = IndexMap::new();
+        object.insert(variant.to_string(), inner);
+        Ok(Value::Object(object))
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> SerdeResult<()> {
+        self.inner.push(value).map_err(|e| e.at_key(self.variant))
+    }
+
+    fn end(self) -> SerdeResult<Value> {
+        Self::wrap(self.variant, Value::Array(self.inner.items))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeObject> {
+    type Ok = Value;
+    type Error = SerdeError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> SerdeResult<()> {
+        self.inner.insert(key.to_string(), value).map_err(|e| e.at_key(self.variant))
+    }
+
+    fn end(self) -> SerdeResult<Value> {
+        Self::wrap(self.variant, Value::Object(self.inner.fields))
+    }
+}
+
+/// A big integer as a plain integer when it fits in 64 bits.
+fn big_integer(n: BigInt) -> Value {
+    n.to_i64().map_or(Value::BigInt(n), Value::Integer)
+}
+
+impl Value {
+    fn unexpected(&self) -> de::Unexpected<'_> {
+        match self {
+            Value::Integer(n) => de::Unexpected::Signed(*n),
+            Value::BigInt(_) => de::Unexpected::Other("big integer"),
+            Value::Decimal(_) => de::Unexpected::Other("decimal"),
+            Value::Number(n) => de::Unexpected::Float(*n),
+            Value::String(s) => de::Unexpected::Str(s),
+            Value::Bytes(b) => de::Unexpected::Bytes(b),
+            Value::Boolean(b) => de::Unexpected::Bool(*b),
+            Value::Array(_) => de::Unexpected::Seq,
+            Value::Object(_) | Value::Instance { .. } => de::Unexpected::Map,
+            Value::Null => de::Unexpected::Unit,
+            other => de::Unexpected::Other(other.type_name()),
+        }
+    }
+
+    fn invalid_type<E: de::Error>(&self, expected: &dyn de::Expected) -> E {
+        E::invalid_type(self.unexpected(), expected)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for &'de Value {
+    type Error = SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            Value::Integer(n) => visitor.visit_i64(*n),
+            Value::BigInt(n) => match (n.to_u64(), n.to_i128(), n.to_u128()) {
+                (Some(n), _, _) => visitor.visit_u64(n),
+                (_, Some(n), _) => visitor.visit_i128(n),
+                (_, _, Some(n)) => visitor.visit_u128(n),
+                _ => visitor.visit_string(n.to_string()),
+            },
+            Value::Decimal(n) => visitor.visit_string(n.to_string()),
+            Value::Number(n) => {
+                if n.fract() == 0.0 && *n >= i64::MIN as f64 && *n < i64::MAX as f64 {
+                    visitor.visit_i64(*n as i64)
+                } else {
+                    visitor.visit_f64(*n)
+                }
+            }
+            Value::String(s) => visitor.visit_borrowed_str(s),
+            Value::Bytes(b) => visitor.visit_borrowed_bytes(b),
+            Value::Boolean(b) => visitor.visit_bool(*b),
+            Value::Null => visitor.visit_unit(),
+            Value::Array(items) => visitor.visit_seq(ArrayAccess { items: items.iter(), index: 0 }),
+            Value::Object(fields) => {
+                visitor.visit_map(ObjectAccess { entries: fields.iter(), value: None })
+            }
+            // Instance fields live behind a RefCell and cannot be borrowed for
+            // 'de; `from_value` reads a detached copy instead.
+            _ => Err(self.invalid_type(&visitor)),
+        }
+    }
+
+    /// Bytes also read as a sequence of integers, so they fill a `Vec<u8>`.
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            Value::Bytes(b) => visitor.visit_seq(de::value::SeqDeserializer::new(b.iter().copied())),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self.as_f64() {
+            Some(n) => visitor.visit_f64(n),
+            None => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        self.deserialize_f64(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> SerdeResult<V::Value> {
+        match self {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> SerdeResult<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> SerdeResult<V::Value> {
+        match self {
+            Value::String(variant) => visitor.visit_enum(variant.as_str().into_deserializer()),
+            Value::Object(fields) if fields.len() == 1 => {
+                let (variant, value) = fields.iter().next().unwrap();
+                visitor
+                    .visit_enum(EnumAccess { variant, value })
+                    .map_err(|e| e.at_key(variant))
+            }
+            _ => Err(self.invalid_type(&"a string or single-key object for an enum")),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string
+        bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+struct ArrayAccess<'de> {
+    items: std::slice::Iter<'de, Value>,
+    index: usize,
+}
+
+impl<'de> SeqAccess<'de> for ArrayAccess<'de> {
+    type Error = SerdeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> SerdeResult<Option<T::Value>> {
+        match self.items.next() {
+            Some(item) => {
+                let index = self.index;
+                self.index += 1;
+                seed.deserialize(item).map(Some).map_err(|e| e.at_index(index))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct ObjectAccess<'de> {
+    entries: indexmap::map::Iter<'de, String, Value>,
+    value: Option<(&'de String, &'de Value)>,
+}
+
+impl<'de> MapAccess<'de> for ObjectAccess<'de> {
+    type Error = SerdeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> SerdeResult<Option<K::Value>> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                self.value = Some((key, value));
+                seed.deserialize(key.as_str().into_deserializer()).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> SerdeResult<V::Value> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| SerdeError::new("next_value called before next_key"))?;
+        seed.deserialize(value).map_err(|e| e.at_key(key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: &'de String,
+    value: &'de Value,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = SerdeError;
+    type Variant = VariantAccess<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> SerdeResult<(V::Value, VariantAccess<'de>)> {
+        let variant = seed.deserialize(self.variant.as_str().into_deserializer())?;
+        Ok((variant, VariantAccess { value: self.value }))
+    }
+}
+
+struct VariantAccess<'de> {
+    value: &'de Value,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'de> {
+    type Error = SerdeError;
+
+    fn unit_variant(self) -> SerdeResult<()> {
+        match self.value {
+            Value::Null => Ok(()),
+            other => Err(other.invalid_type(&"null for a unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> SerdeResult<T::Value> {
+        seed.deserialize(self.value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> SerdeResult<V::Value> {
+        de::Deserializer::deserialize_seq(self.value, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> SerdeResult<V::Value> {
+        de::Deserializer::deserialize_map(self.value, visitor)
+    }
+}
@@ -4,6 +4,12 @@ pub struct GarbageCollector {
     // GC implementation
 }
 
+impl Default for GarbageCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl GarbageCollector {
     pub fn new() -> Self {
         Self {}
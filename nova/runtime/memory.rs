@@ -1,11 +1,151 @@
 // Nova Memory Management
+//
+// The interpreter owns its values directly, so the memory manager does not
+// allocate anything itself. It keeps the books: how many bytes of each kind
+// of value were live at the last measurement, how much has been allocated
+// since, and whether a new allocation would push the total over the limit.
 
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Returned when an allocation does not fit in the configured limit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutOfMemory {
+    pub category: String,
+    pub requested: usize,
+    pub in_use: usize,
+    pub limit: usize,
+}
+
+impl fmt::Display for OutOfMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "allocating {} bytes of {} would exceed the memory limit ({} of {} bytes in use)",
+            self.requested, self.category, self.in_use, self.limit
+        )
+    }
+}
+
+impl std::error::Error for OutOfMemory {}
+
+#[derive(Debug, Clone, Default)]
 pub struct MemoryManager {
-    // Memory management implementation
+    limit: Option<usize>,
+    /// Bytes per category found live by the last call to `set_live`.
+    live: BTreeMap<String, usize>,
+    /// Bytes allocated since then, which may or may not still be live.
+    allocated: BTreeMap<String, usize>,
 }
 
 impl MemoryManager {
     pub fn new() -> Self {
-        Self {}
+        Self::default()
+    }
+
+    /// Creates a manager that refuses allocations beyond `bytes`.
+    pub fn with_limit(bytes: usize) -> Self {
+        MemoryManager {
+            limit: Some(bytes),
+            ..Self::default()
+        }
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+    }
+
+    /// Bytes live at the last measurement plus everything allocated since.
+    /// This is an upper bound on the real usage.
+    pub fn in_use(&self) -> usize {
+        self.live.values().sum::<usize>() + self.allocated.values().sum::<usize>()
+    }
+
+    /// Upper bound on the bytes in use for one category.
+    pub fn usage(&self, category: &str) -> usize {
+        self.live.get(category).copied().unwrap_or(0) + self.allocated.get(category).copied().unwrap_or(0)
+    }
+
+    /// Upper bounds on the bytes in use, per category.
+    pub fn usage_by_category(&self) -> BTreeMap<String, usize> {
+        let mut usage = self.live.clone();
+        for (category, bytes) in &self.allocated {
+            *usage.entry(category.clone()).or_insert(0) += bytes;
+        }
+        usage
     }
-}
\ No newline at end of file
+
+    /// Records an allocation, or refuses it if it would exceed the limit.
+    /// A refused allocation is not recorded.
+    pub fn allocate(&mut self, category: &str, bytes: usize) -> Result<(), OutOfMemory> {
+        if let Some(limit) = self.limit {
+            let in_use = self.in_use();
+            if in_use.saturating_add(bytes) > limit {
+                return Err(OutOfMemory {
+                    category: category.to_string(),
+                    requested: bytes,
+                    in_use,
+                    limit,
+                });
+            }
+        }
+        *self.allocated.entry(category.to_string()).or_insert(0) += bytes;
+        Ok(())
+    }
+
+    /// Replaces the books with a fresh measurement of the live values,
+    /// forgetting allocations that have been freed since.
+    pub fn set_live(&mut self, live: BTreeMap<String, usize>) {
+        self.live = live;
+        self.allocated.clear();
+    }
+
+    /// Forgets all usage but keeps the limit.
+    pub fn reset(&mut self) {
+        self.live.clear();
+        self.allocated.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocations_are_tracked_per_category() {
+        let mut memory = MemoryManager::new();
+        memory.allocate("string", 10).unwrap();
+        memory.allocate("array", 32).unwrap();
+        memory.allocate("string", 5).unwrap();
+
+        assert_eq!(memory.usage("string"), 15);
+        assert_eq!(memory.usage("array"), 32);
+        assert_eq!(memory.in_use(), 47);
+    }
+
+    #[test]
+    fn test_limit_refuses_allocation() {
+        let mut memory = MemoryManager::with_limit(100);
+        memory.allocate("string", 60).unwrap();
+
+        let error = memory.allocate("array", 50).unwrap_err();
+        assert_eq!(error.in_use, 60);
+        assert_eq!(error.limit, 100);
+        assert_eq!(memory.in_use(), 60);
+    }
+
+    #[test]
+    fn test_set_live_forgets_freed_allocations() {
+        let mut memory = MemoryManager::with_limit(100);
+        memory.allocate("string", 90).unwrap();
+        assert!(memory.allocate("string", 20).is_err());
+
+        memory.set_live(BTreeMap::from([("string".to_string(), 30)]));
+        memory.allocate("string", 20).unwrap();
+        assert_eq!(memory.usage("string"), 50);
+    }
+}
@@ -4,6 +4,12 @@ pub struct NativeFunctionRegistry {
     // Native function registry
 }
 
+impl Default for NativeFunctionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NativeFunctionRegistry {
     pub fn new() -> Self {
         Self {}
@@ -4,6 +4,12 @@ pub struct NovaVM {
     // VM implementation will go here
 }
 
+impl Default for NovaVM {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaVM {
     pub fn new() -> Self {
         Self {}
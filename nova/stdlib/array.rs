@@ -4,6 +4,12 @@ pub struct ArrayModule {
     // Array functions implementation
 }
 
+impl Default for ArrayModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ArrayModule {
     pub fn new() -> Self {
         Self {}
@@ -1,6 +1,7 @@
 // Nova Standard Library - Collections Module
 
 use std::collections::{VecDeque};
+use std::fmt;
 
 /// Wrapper for f64 that implements Eq and Ord
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -28,6 +29,12 @@ pub struct NovaList {
     items: Vec<NovaValue>,
 }
 
+impl Default for NovaList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaList {
     pub fn new() -> Self {
         Self { items: Vec::new() }
@@ -90,7 +97,7 @@ impl NovaList {
         self.items.contains(value)
     }
 
-    pub fn iter(&self) -> std::slice::Iter<NovaValue> {
+    pub fn iter(&self) -> std::slice::Iter<'_, NovaValue> {
         self.items.iter()
     }
 
@@ -111,6 +118,12 @@ pub struct NovaSet {
     items: Vec<NovaValue>, // Using Vec instead of HashSet to avoid Hash trait requirement
 }
 
+impl Default for NovaSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaSet {
     pub fn new() -> Self {
         Self {
@@ -186,7 +199,7 @@ impl NovaSet {
         result
     }
 
-    pub fn iter(&self) -> std::slice::Iter<NovaValue> {
+    pub fn iter(&self) -> std::slice::Iter<'_, NovaValue> {
         self.items.iter()
     }
 }
@@ -197,6 +210,12 @@ pub struct NovaMap {
     items: Vec<(NovaValue, NovaValue)>, // Using Vec instead of HashMap to avoid Hash trait requirement
 }
 
+impl Default for NovaMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaMap {
     pub fn new() -> Self {
         Self {
@@ -263,7 +282,7 @@ impl NovaMap {
         self.items.iter().map(|(_, v)| v).collect()
     }
 
-    pub fn iter(&self) -> std::slice::Iter<(NovaValue, NovaValue)> {
+    pub fn iter(&self) -> std::slice::Iter<'_, (NovaValue, NovaValue)> {
         self.items.iter()
     }
 }
@@ -274,6 +293,12 @@ pub struct NovaQueue {
     items: VecDeque<NovaValue>,
 }
 
+impl Default for NovaQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaQueue {
     pub fn new() -> Self {
         Self {
@@ -318,6 +343,12 @@ pub struct NovaStack {
     items: Vec<NovaValue>,
 }
 
+impl Default for NovaStack {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaStack {
     pub fn new() -> Self {
         Self { items: Vec::new() }
@@ -360,6 +391,12 @@ pub struct NovaOrderedMap {
     items: Vec<(NovaValue, NovaValue)>, // Using Vec instead of BTreeMap
 }
 
+impl Default for NovaOrderedMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaOrderedMap {
     pub fn new() -> Self {
         Self {
@@ -429,7 +466,7 @@ impl NovaOrderedMap {
         self.items.iter().map(|(_, v)| v).collect()
     }
 
-    pub fn iter(&self) -> std::slice::Iter<(NovaValue, NovaValue)> {
+    pub fn iter(&self) -> std::slice::Iter<'_, (NovaValue, NovaValue)> {
         self.items.iter()
     }
 }
@@ -440,6 +477,12 @@ pub struct NovaOrderedSet {
     items: Vec<NovaValue>, // Using Vec instead of BTreeSet
 }
 
+impl Default for NovaOrderedSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaOrderedSet {
     pub fn new() -> Self {
         Self {
@@ -489,7 +532,7 @@ impl NovaOrderedSet {
         self.items.last()
     }
 
-    pub fn iter(&self) -> std::slice::Iter<NovaValue> {
+    pub fn iter(&self) -> std::slice::Iter<'_, NovaValue> {
         self.items.iter()
     }
 }
@@ -549,23 +592,26 @@ impl NovaValue {
         }
     }
 
-    pub fn to_string(&self) -> String {
+}
+
+impl fmt::Display for NovaValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            NovaValue::Nil => "nil".to_string(),
-            NovaValue::Bool(b) => b.to_string(),
-            NovaValue::Number(n) => n.to_string(),
-            NovaValue::Float(f) => {
-                let float_val = f.get();
-                float_val.to_string()
+            NovaValue::Nil => write!(f, "nil"),
+            NovaValue::Bool(b) => write!(f, "{}", b),
+            NovaValue::Number(n) => write!(f, "{}", n),
+            NovaValue::Float(float) => {
+                let float_val = float.get();
+                write!(f, "{}", float_val)
             }
-            NovaValue::String(s) => s.clone(),
-            NovaValue::List(l) => format!("[{} items]", l.len()),
-            NovaValue::Set(s) => format!("Set({} items)", s.len()),
-            NovaValue::Map(m) => format!("Map({} items)", m.len()),
-            NovaValue::Queue(q) => format!("Queue({} items)", q.len()),
-            NovaValue::Stack(s) => format!("Stack({} items)", s.len()),
-            NovaValue::OrderedMap(m) => format!("OrderedMap({} items)", m.len()),
-            NovaValue::OrderedSet(s) => format!("OrderedSet({} items)", s.len()),
+            NovaValue::String(s) => write!(f, "{}", s),
+            NovaValue::List(l) => write!(f, "[{} items]", l.len()),
+            NovaValue::Set(s) => write!(f, "Set({} items)", s.len()),
+            NovaValue::Map(m) => write!(f, "Map({} items)", m.len()),
+            NovaValue::Queue(q) => write!(f, "Queue({} items)", q.len()),
+            NovaValue::Stack(s) => write!(f, "Stack({} items)", s.len()),
+            NovaValue::OrderedMap(m) => write!(f, "OrderedMap({} items)", m.len()),
+            NovaValue::OrderedSet(s) => write!(f, "OrderedSet({} items)", s.len()),
         }
     }
 }
@@ -576,7 +622,7 @@ pub struct Collections;
 impl Collections {
     /// Create a new list
     pub fn list() -> NovaValue {
-        NovaValue::List(Box::new(NovaList::new()))
+        NovaValue::List(Box::default())
     }
 
     /// Create a new list with initial capacity
@@ -586,32 +632,32 @@ impl Collections {
 
     /// Create a new set
     pub fn set() -> NovaValue {
-        NovaValue::Set(Box::new(NovaSet::new()))
+        NovaValue::Set(Box::default())
     }
 
     /// Create a new map
     pub fn map() -> NovaValue {
-        NovaValue::Map(Box::new(NovaMap::new()))
+        NovaValue::Map(Box::default())
     }
 
     /// Create a new queue
     pub fn queue() -> NovaValue {
-        NovaValue::Queue(Box::new(NovaQueue::new()))
+        NovaValue::Queue(Box::default())
     }
 
     /// Create a new stack
     pub fn stack() -> NovaValue {
-        NovaValue::Stack(Box::new(NovaStack::new()))
+        NovaValue::Stack(Box::default())
     }
 
     /// Create a new ordered map
     pub fn ordered_map() -> NovaValue {
-        NovaValue::OrderedMap(Box::new(NovaOrderedMap::new()))
+        NovaValue::OrderedMap(Box::default())
     }
 
     /// Create a new ordered set
     pub fn ordered_set() -> NovaValue {
-        NovaValue::OrderedSet(Box::new(NovaOrderedSet::new()))
+        NovaValue::OrderedSet(Box::default())
     }
 }
 
@@ -1,7 +1,8 @@
 // Nova Standard Library - Cryptography Module
 
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
 
 /// Hash algorithms
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -12,7 +13,7 @@ pub enum HashAlgorithm {
     Sha512,
 }
 
-/// Simple hash implementation for Nova
+/// Message digest over the standard algorithms
 #[derive(Debug, Clone)]
 pub struct NovaHash {
     algorithm: HashAlgorithm,
@@ -40,123 +41,25 @@ impl NovaHash {
 
     /// Finalize and get hash digest as hex string
     pub fn finalize(&self) -> String {
-        match self.algorithm {
-            HashAlgorithm::Md5 => self.md5_digest(),
-            HashAlgorithm::Sha1 => self.sha1_digest(),
-            HashAlgorithm::Sha256 => self.sha256_digest(),
-            HashAlgorithm::Sha512 => self.sha512_digest(),
-        }
+        hex_encode(&self.finalize_bytes())
     }
 
     /// Finalize and get hash digest as bytes
     pub fn finalize_bytes(&self) -> Vec<u8> {
         match self.algorithm {
-            HashAlgorithm::Md5 => self.md5_digest_bytes(),
-            HashAlgorithm::Sha1 => self.sha1_digest_bytes(),
-            HashAlgorithm::Sha256 => self.sha256_digest_bytes(),
-            HashAlgorithm::Sha512 => self.sha512_digest_bytes(),
-        }
-    }
-
-    // Simplified MD5 implementation (not cryptographically secure)
-    fn md5_digest(&self) -> String {
-        let digest = self.md5_digest_bytes();
-        hex_encode(&digest)
-    }
-
-    fn md5_digest_bytes(&self) -> Vec<u8> {
-        // This is a simplified MD5-like hash, not actual MD5
-        let mut hasher = DefaultHasher::new();
-        self.data.hash(&mut hasher);
-        let hash = hasher.finish();
-        
-        // Convert to 16 bytes (MD5 size)
-        let mut result = Vec::with_capacity(16);
-        for i in 0..2 {
-            let part = (hash >> (i * 32)) as u32;
-            result.extend_from_slice(&part.to_le_bytes());
+            HashAlgorithm::Md5 => Md5::digest(&self.data).to_vec(),
+            HashAlgorithm::Sha1 => Sha1::digest(&self.data).to_vec(),
+            HashAlgorithm::Sha256 => Sha256::digest(&self.data).to_vec(),
+            HashAlgorithm::Sha512 => Sha512::digest(&self.data).to_vec(),
         }
-        // Fill to 16 bytes
-        while result.len() < 16 {
-            result.push(0);
-        }
-        result.truncate(16);
-        result
-    }
-
-    // Simplified SHA1 implementation
-    fn sha1_digest(&self) -> String {
-        let digest = self.sha1_digest_bytes();
-        hex_encode(&digest)
     }
 
-    fn sha1_digest_bytes(&self) -> Vec<u8> {
-        // This is a simplified SHA1-like hash, not actual SHA1
-        let mut hasher = DefaultHasher::new();
-        self.data.hash(&mut hasher);
-        let hash = hasher.finish();
-        
-        // Convert to 20 bytes (SHA1 size)
-        let mut result = Vec::with_capacity(20);
-        result.extend_from_slice(&hash.to_le_bytes());
-        result.extend_from_slice(&(hash ^ 0xABCDEF).to_le_bytes());
-        // Fill to 20 bytes
-        while result.len() < 20 {
-            result.push(0);
+    /// Size of the blocks the algorithm consumes, which HMAC pads keys to
+    fn block_size(algorithm: HashAlgorithm) -> usize {
+        match algorithm {
+            HashAlgorithm::Sha512 => 128,
+            _ => 64,
         }
-        result.truncate(20);
-        result
-    }
-
-    // Simplified SHA256 implementation
-    fn sha256_digest(&self) -> String {
-        let digest = self.sha256_digest_bytes();
-        hex_encode(&digest)
-    }
-
-    fn sha256_digest_bytes(&self) -> Vec<u8> {
-        // This is a simplified SHA256-like hash, not actual SHA256
-        let mut hasher1 = DefaultHasher::new();
-        let mut hasher2 = DefaultHasher::new();
-        self.data.hash(&mut hasher1);
-        self.data.iter().rev().for_each(|b| b.hash(&mut hasher2));
-        
-        let hash1 = hasher1.finish();
-        let hash2 = hasher2.finish();
-        
-        // Convert to 32 bytes (SHA256 size)
-        let mut result = Vec::with_capacity(32);
-        result.extend_from_slice(&hash1.to_le_bytes());
-        result.extend_from_slice(&hash2.to_le_bytes());
-        result.extend_from_slice(&(hash1 ^ hash2).to_le_bytes());
-        result.extend_from_slice(&(hash1.wrapping_add(hash2)).to_le_bytes());
-        result
-    }
-
-    // Simplified SHA512 implementation
-    fn sha512_digest(&self) -> String {
-        let digest = self.sha512_digest_bytes();
-        hex_encode(&digest)
-    }
-
-    fn sha512_digest_bytes(&self) -> Vec<u8> {
-        // This is a simplified SHA512-like hash, not actual SHA512
-        let mut result = Vec::with_capacity(64);
-        
-        for chunk in self.data.chunks(8) {
-            let mut hasher = DefaultHasher::new();
-            chunk.hash(&mut hasher);
-            result.extend_from_slice(&hasher.finish().to_le_bytes());
-        }
-        
-        // Fill to 64 bytes
-        while result.len() < 64 {
-            let mut hasher = DefaultHasher::new();
-            result.len().hash(&mut hasher);
-            result.extend_from_slice(&hasher.finish().to_le_bytes());
-        }
-        result.truncate(64);
-        result
     }
 }
 
@@ -202,7 +105,7 @@ impl Base64 {
     /// Decode base64 string to bytes
     pub fn decode(input: &str) -> Result<Vec<u8>, String> {
         let input = input.trim();
-        if input.len() % 4 != 0 {
+        if !input.len().is_multiple_of(4) {
             return Err("Invalid base64 length".to_string());
         }
         
@@ -402,12 +305,20 @@ impl Crypto {
         hasher.finalize()
     }
     
-    /// Generate HMAC (simplified version)
+    /// Generate HMAC (RFC 2104)
     pub fn hmac(algorithm: HashAlgorithm, key: &[u8], message: &[u8]) -> String {
-        let mut inner_key = vec![0x36u8; 64];
-        let mut outer_key = vec![0x5cu8; 64];
-        
-        for (i, &k) in key.iter().enumerate().take(64) {
+        let block_size = NovaHash::block_size(algorithm);
+        let key = if key.len() > block_size {
+            let mut hasher = NovaHash::new(algorithm);
+            hasher.update(key);
+            hasher.finalize_bytes()
+        } else {
+            key.to_vec()
+        };
+        let mut inner_key = vec![0x36u8; block_size];
+        let mut outer_key = vec![0x5cu8; block_size];
+        
+        for (i, &k) in key.iter().enumerate() {
             inner_key[i] ^= k;
             outer_key[i] ^= k;
         }
@@ -483,7 +394,7 @@ fn hex_encode(input: &[u8]) -> String {
 }
 
 fn hex_decode(input: &str) -> Result<Vec<u8>, String> {
-    if input.len() % 2 != 0 {
+    if !input.len().is_multiple_of(2) {
         return Err("Invalid hex string length".to_string());
     }
     
@@ -512,9 +423,13 @@ mod tests {
         hasher.update_str("hello world");
         let digest = hasher.finalize();
         
-        // Should produce consistent hash
-        assert!(!digest.is_empty());
-        assert_eq!(digest.len(), 64); // SHA256 hex = 64 chars
+        assert_eq!(digest, "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9");
+        assert_eq!(Crypto::hash(HashAlgorithm::Md5, "hello world"), "5eb63bbbe01eeed093cb22bb8f5acdc3");
+        assert_eq!(Crypto::hash(HashAlgorithm::Sha1, "hello world"), "2aae6c35c94fcfb415dbe95f408b9ce91ee846ed");
+        assert_eq!(
+            Crypto::hmac(HashAlgorithm::Sha256, b"key", b"The quick brown fox jumps over the lazy dog"),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
     }
 
     #[test]
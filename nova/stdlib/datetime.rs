@@ -161,17 +161,6 @@ impl NovaDateTime {
                 year, month, day, hour, minute, second, millis)
     }
 
-    /// Format the datetime as a readable string
-    pub fn to_string(&self) -> String {
-        let (year, month, day) = self.to_civil();
-        let hour = self.hour();
-        let minute = self.minute();
-        let second = self.second();
-
-        format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", 
-                year, month, day, hour, minute, second)
-    }
-
     /// Check if this year is a leap year
     pub fn is_leap_year(&self) -> bool {
         let year = self.year();
@@ -262,8 +251,15 @@ impl NovaDateTime {
 }
 
 impl fmt::Display for NovaDateTime {
+    /// Format the datetime as a readable string
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "{}", self.to_string())
+        let (year, month, day) = self.to_civil();
+        let hour = self.hour();
+        let minute = self.minute();
+        let second = self.second();
+
+        write!(f, "{:04}-{:02}-{:02} {:02}:{:02}:{:02}", 
+               year, month, day, hour, minute, second)
     }
 }
 
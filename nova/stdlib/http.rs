@@ -4,6 +4,12 @@ pub struct HTTPModule {
     // HTTP functions implementation
 }
 
+impl Default for HTTPModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl HTTPModule {
     pub fn new() -> Self {
         Self {}
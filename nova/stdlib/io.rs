@@ -4,6 +4,12 @@ pub struct IOModule {
     // I/O functions implementation
 }
 
+impl Default for IOModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl IOModule {
     pub fn new() -> Self {
         Self {}
@@ -4,6 +4,12 @@ pub struct JSONModule {
     // JSON functions implementation
 }
 
+impl Default for JSONModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl JSONModule {
     pub fn new() -> Self {
         Self {}
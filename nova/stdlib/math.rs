@@ -4,6 +4,12 @@ pub struct MathModule {
     // Math functions implementation
 }
 
+impl Default for MathModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MathModule {
     pub fn new() -> Self {
         Self {}
@@ -86,7 +86,7 @@ impl NovaRng {
 
     /// Generate random boolean
     pub fn bool(&mut self) -> bool {
-        self.next_u64() % 2 == 0
+        self.next_u64().is_multiple_of(2)
     }
 
     /// Generate random boolean with given probability of being true
@@ -224,7 +224,7 @@ fn get_global_rng() -> &'static mut NovaRng {
             GLOBAL_RNG = Some(NovaRng::new_seeded());
             RNG_INITIALIZED = true;
         }
-        GLOBAL_RNG.as_mut().unwrap()
+        (*std::ptr::addr_of_mut!(GLOBAL_RNG)).as_mut().unwrap()
     }
 }
 
@@ -358,6 +358,12 @@ pub struct WeightedChoice<T> {
     total_weight: f64,
 }
 
+impl<T: Clone> Default for WeightedChoice<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<T: Clone> WeightedChoice<T> {
     /// Create new weighted choice
     pub fn new() -> Self {
@@ -436,7 +442,7 @@ mod tests {
         
         for _ in 0..100 {
             let val = rng.range_i64(10, 20);
-            assert!(val >= 10 && val < 20);
+            assert!((10..20).contains(&val));
         }
     }
 
@@ -446,7 +452,7 @@ mod tests {
         
         for _ in 0..100 {
             let val = rng.next_f64();
-            assert!(val >= 0.0 && val < 1.0);
+            assert!((0.0..1.0).contains(&val));
         }
     }
 
@@ -466,7 +472,7 @@ mod tests {
     fn test_rng_shuffle() {
         let mut rng = NovaRng::new(12345);
         let mut items = [1, 2, 3, 4, 5];
-        let original = items.clone();
+        let original = items;
         
         rng.shuffle(&mut items);
         
@@ -507,10 +513,10 @@ mod tests {
     fn test_global_random() {
         let int1 = Random::int(1, 10);
         let int2 = Random::int(1, 10);
-        assert!(int1 >= 1 && int1 < 10);
-        assert!(int2 >= 1 && int2 < 10);
+        assert!((1..10).contains(&int1));
+        assert!((1..10).contains(&int2));
         
         let float_val = Random::float();
-        assert!(float_val >= 0.0 && float_val < 1.0);
+        assert!((0.0..1.0).contains(&float_val));
     }
 }
\ No newline at end of file
@@ -4,6 +4,12 @@ pub struct StringModule {
     // String functions implementation
 }
 
+impl Default for StringModule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StringModule {
     pub fn new() -> Self {
         Self {}
@@ -4,6 +4,12 @@ pub struct TestAssertions {
     // Test assertion implementation
 }
 
+impl Default for TestAssertions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TestAssertions {
     pub fn new() -> Self {
         Self {}
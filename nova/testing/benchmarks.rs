@@ -4,6 +4,12 @@ pub struct BenchmarkRunner {
     // Benchmark implementation
 }
 
+impl Default for BenchmarkRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BenchmarkRunner {
     pub fn new() -> Self {
         Self {}
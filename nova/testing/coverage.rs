@@ -4,6 +4,12 @@ pub struct CoverageAnalyzer {
     // Coverage analysis implementation
 }
 
+impl Default for CoverageAnalyzer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CoverageAnalyzer {
     pub fn new() -> Self {
         Self {}
@@ -4,6 +4,12 @@ pub struct TestRunner {
     // Test runner implementation
 }
 
+impl Default for TestRunner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TestRunner {
     pub fn new() -> Self {
         Self {}
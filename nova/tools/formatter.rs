@@ -4,6 +4,12 @@ pub struct NovaFormatter {
     // Code formatter implementation
 }
 
+impl Default for NovaFormatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaFormatter {
     pub fn new() -> Self {
         Self {}
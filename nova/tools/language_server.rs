@@ -4,6 +4,12 @@ pub struct NovaLanguageServer {
     // LSP implementation
 }
 
+impl Default for NovaLanguageServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaLanguageServer {
     pub fn new() -> Self {
         Self {}
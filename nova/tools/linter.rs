@@ -4,6 +4,12 @@ pub struct NovaLinter {
     // Linter implementation
 }
 
+impl Default for NovaLinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NovaLinter {
     pub fn new() -> Self {
         Self {}
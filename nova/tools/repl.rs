@@ -4,6 +4,12 @@ pub struct ReplTools {
     // REPL enhancement tools
 }
 
+impl Default for ReplTools {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ReplTools {
     pub fn new() -> Self {
         Self {}